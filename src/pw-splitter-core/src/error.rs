@@ -0,0 +1,92 @@
+use thiserror::Error;
+
+/// Errors from querying PipeWire and creating, changing or removing splits
+#[derive(Error, Debug)]
+pub enum PwSplitterError {
+    #[error("Failed to execute PipeWire command: {0}")]
+    CommandFailed(String),
+
+    #[error("PipeWire is not running; start it with 'systemctl --user start pipewire wireplumber'")]
+    PipeWireNotRunning,
+
+    #[error("'{0}' did not respond within {1:?}; is PipeWire restarting?")]
+    CommandTimeout(&'static str, std::time::Duration),
+
+    #[error("Failed to parse PipeWire output: {0}")]
+    ParseError(String),
+
+    #[error("Node not found: {0}")]
+    NodeNotFound(String),
+
+    #[error("'{0}' matches more than one node: {1}; use a node id instead")]
+    AmbiguousName(String, String),
+
+    #[error("No active connection found for source")]
+    NoActiveConnection,
+
+    #[error("'{0}' is not installed; install your distro's {1}")]
+    ToolNotInstalled(&'static str, &'static str),
+
+    #[error("Failed to spawn loopback: {0}")]
+    LoopbackSpawnFailed(String),
+
+    #[error("The split setup has already finished")]
+    SetupFinished,
+
+    #[error("Timed out waiting for ports of {0}")]
+    PortsTimeout(String),
+
+    #[error("{0} isn't producing audio yet (it has no output ports); start playback in it first")]
+    SourceNotPlaying(String),
+
+    #[error("Failed to create link: {0}")]
+    LinkCreationFailed(String),
+
+    #[error(
+        "{0} has no input ports to record into; it may be idle, so start recording in it first"
+    )]
+    DestHasNoInputs(String),
+
+    #[error("{0} could not be linked to {1}; the recording would be silent")]
+    RecordingNotLinked(String, String),
+
+    #[error("Failed to destroy link: {0}")]
+    LinkDestroyFailed(String),
+
+    #[error("Refusing to create split, it would cause a feedback loop: {0}")]
+    FeedbackLoop(String),
+
+    #[error("Invalid volume {0}: expected a value between 0.0 and 1.5")]
+    InvalidVolume(f32),
+
+    #[error(
+        "Invalid loopback latency '{0}': expected <quantum>/<rate> (e.g. 1024/48000) and a non-zero rate"
+    )]
+    InvalidLatency(String),
+
+    #[error("No split named '{0}' (see 'pw-splitter list')")]
+    SplitNotFound(String),
+
+    #[error(
+        "{0} is already split by '{1}'; stop that split first, or 'clone' it to record elsewhere too"
+    )]
+    SplitAlreadyExists(String, String),
+
+    #[error("A split named '{0}' already exists")]
+    NameTaken(String),
+
+    #[error("Invalid split name '{0}': it must be non-empty and contain no path separators")]
+    InvalidSplitName(String),
+
+    #[error("State file error: {0}")]
+    StateFileError(String),
+
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+
+    #[error("JSON error: {0}")]
+    JsonError(#[from] serde_json::Error),
+}
+
+/// Result type used throughout pw-splitter
+pub type Result<T> = std::result::Result<T, PwSplitterError>;
@@ -0,0 +1,126 @@
+//! A persistent log of failed split setups.
+//!
+//! Failed setups are appended to `errors.log` in the state directory,
+//! whatever the verbosity, so there's something to attach to a bug report
+//! without reproducing the failure. Once the log reaches
+//! [`MAX_ERROR_LOG_BYTES`] it is moved to `errors.log.1`, replacing the
+//! previous one, so it never grows without bound.
+
+use crate::splitter::SplitState;
+use std::error::Error;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Name of the log of failed setups
+pub const ERROR_LOG_FILE: &str = "errors.log";
+
+/// Size at which the log is rotated
+pub const MAX_ERROR_LOG_BYTES: u64 = 256 * 1024;
+
+/// Path of the log of failed setups
+pub fn error_log_path() -> PathBuf {
+    SplitState::state_dir().join(ERROR_LOG_FILE)
+}
+
+/// Append a record of a failed setup to the error log
+///
+/// `step` is the setup step that failed; the full chain of `error`'s causes
+/// is written. Failing to write the log is only reported through tracing.
+pub fn record_setup_failure(source: &str, dests: &[String], step: &str, error: &dyn Error) {
+    let mut record = format!(
+        "[{}] setup failed\n  source: {}\n  destinations: {}\n  step: {}\n  error: {}\n",
+        utc_timestamp(SystemTime::now()),
+        source,
+        dests.join(", "),
+        step,
+        error
+    );
+    let mut cause = error.source();
+    while let Some(e) = cause {
+        record.push_str(&format!("    caused by: {}\n", e));
+        cause = e.source();
+    }
+
+    let path = error_log_path();
+    let written = fs::create_dir_all(SplitState::state_dir()).and_then(|_| {
+        if fs::metadata(&path).is_ok_and(|m| m.len() >= MAX_ERROR_LOG_BYTES) {
+            fs::rename(&path, path.with_extension("log.1"))?;
+        }
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)?
+            .write_all(record.as_bytes())
+    });
+    if let Err(e) = written {
+        tracing::warn!("could not write {}: {}", path.display(), e);
+    }
+}
+
+/// Format a time as an ISO 8601 UTC timestamp, e.g. `2024-05-01T12:34:56Z`
+fn utc_timestamp(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let (days, rem) = (secs / 86_400, secs % 86_400);
+
+    // Civil date from days since the epoch (Howard Hinnant's algorithm)
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3_600,
+        rem % 3_600 / 60,
+        rem % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::PwSplitterError;
+    use crate::pipewire::mock::TestEnv;
+    use std::time::Duration;
+
+    fn at(secs: u64) -> String {
+        utc_timestamp(UNIX_EPOCH + Duration::from_secs(secs))
+    }
+
+    #[test]
+    fn timestamps_are_utc_dates() {
+        assert_eq!(at(0), "1970-01-01T00:00:00Z");
+        assert_eq!(at(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(at(951_868_799), "2000-02-29T23:59:59Z");
+    }
+
+    #[test]
+    fn full_log_is_rotated() {
+        let _env = TestEnv::new();
+        let path = error_log_path();
+        fs::create_dir_all(SplitState::state_dir()).unwrap();
+        fs::write(&path, vec![b'x'; MAX_ERROR_LOG_BYTES as usize]).unwrap();
+
+        let error = PwSplitterError::PipeWireNotRunning;
+        record_setup_failure("Firefox (node 42)", &[], "Wiring…", &error);
+
+        let log = fs::read_to_string(&path).unwrap();
+        assert!(log.contains("source: Firefox (node 42)"));
+        assert!(log.starts_with('['));
+        let rotated = fs::metadata(path.with_extension("log.1")).unwrap();
+        assert_eq!(rotated.len(), MAX_ERROR_LOG_BYTES);
+    }
+}
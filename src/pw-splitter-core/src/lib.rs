@@ -0,0 +1,29 @@
+//! Splitting a PipeWire application's audio between a recording destination
+//! and the local output, as used by the `pw-splitter` TUI and CLI.
+//!
+//! A split routes the source through one `pw-loopback` per recording
+//! destination plus one to the local sink, so the recording and what you
+//! hear can be adjusted independently. [`splitter::setup_split`] creates a
+//! split and returns its [`splitter::SplitState`], which is persisted so
+//! [`splitter::teardown_split`] can restore the original links later, even
+//! from another process.
+//!
+//! ```no_run
+//! use pw_splitter_core::{pipewire, splitter};
+//!
+//! # fn main() -> pw_splitter_core::error::Result<()> {
+//! for state in splitter::SplitState::list_all()? {
+//!     splitter::teardown_split(&state, true, pipewire::runner())?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Graph queries and commands live in [`pipewire`]; every command goes
+//! through a [`pipewire::CommandRunner`], so callers can swap in
+//! [`pipewire::DryRunRunner`] to record instead of change anything.
+
+pub mod error;
+pub mod failure_log;
+pub mod pipewire;
+pub mod splitter;
@@ -0,0 +1,277 @@
+//! Channel-aware matching of output ports to input ports.
+
+use crate::pipewire::types::{AudioPort, PortDirection};
+
+/// Channel name PipeWire uses for single-channel ports
+pub const MONO: &str = "MONO";
+
+/// Canonical form of a channel position, for comparing ports
+///
+/// Casing varies between clients, and apps that declare no channel map
+/// expose `AUX0`/`AUX1`, which carry left and right in practice.
+pub fn normalize_channel(channel: &str) -> String {
+    let channel = channel.to_ascii_uppercase();
+    match channel.as_str() {
+        "AUX0" => "FL".to_string(),
+        "AUX1" => "FR".to_string(),
+        _ => channel,
+    }
+}
+
+/// Whether two channel names denote the same position
+pub fn same_channel(a: &str, b: &str) -> bool {
+    normalize_channel(a) == normalize_channel(b)
+}
+
+/// Standard channel positions, as [`normalize_channel`] returns them
+const KNOWN_POSITIONS: [&str; 30] = [
+    MONO, "FL", "FR", "FC", "LFE", "SL", "SR", "FLC", "FRC", "RC", "RL", "RR", "TC", "TFL", "TFC",
+    "TFR", "TRL", "TRC", "TRR", "RLC", "RRC", "FLW", "FRW", "LFE2", "FLH", "FCH", "FRH", "TSL",
+    "TSR", "BC",
+];
+
+/// Whether any of `ports` carries a standard channel position
+///
+/// JACK-style apps expose numbered ports (`output_1`..`output_8`) labeled
+/// with nothing, `UNK` or `AUX2` and up, which channel matching can't place.
+pub fn has_channel_positions(ports: &[&AudioPort]) -> bool {
+    ports
+        .iter()
+        .any(|p| KNOWN_POSITIONS.contains(&normalize_channel(&p.channel).as_str()))
+}
+
+fn is_mono(port: &AudioPort) -> bool {
+    normalize_channel(&port.channel) == MONO
+}
+
+/// Ports of `node_id` in `direction` that carry an audio channel
+///
+/// Every channel position is routed (FL, FR, FC, LFE, RL, RR, ...), so
+/// surround sources keep all their channels through a split.
+pub fn routable_ports(
+    ports: &[AudioPort],
+    node_id: u32,
+    direction: PortDirection,
+) -> Vec<&AudioPort> {
+    ports
+        .iter()
+        .filter(|p| p.node_id == node_id && p.direction == direction && !p.channel.is_empty())
+        .collect()
+}
+
+/// Every port of `node_id` in `direction`, labeled or not, ordered by port id
+pub fn ports_in_order(
+    ports: &[AudioPort],
+    node_id: u32,
+    direction: PortDirection,
+) -> Vec<&AudioPort> {
+    let mut ports: Vec<&AudioPort> = ports
+        .iter()
+        .filter(|p| p.node_id == node_id && p.direction == direction)
+        .collect();
+    ports.sort_by_key(|p| p.port_id);
+    ports
+}
+
+/// Normalized channel positions of `node_id`'s ports in `direction`, in port order
+pub fn channel_layout(ports: &[AudioPort], node_id: u32, direction: PortDirection) -> Vec<String> {
+    routable_ports(ports, node_id, direction)
+        .into_iter()
+        .map(|p| normalize_channel(&p.channel))
+        .collect()
+}
+
+/// Channels of `outputs` that [`pair_ports`] left without an input
+///
+/// These are dropped when the two sides' channel maps differ, e.g. the
+/// center and LFE channels of a 5.1 source played into a stereo sink.
+pub fn unpaired_channels(
+    outputs: &[&AudioPort],
+    pairs: &[(&AudioPort, &AudioPort)],
+) -> Vec<String> {
+    outputs
+        .iter()
+        .filter(|o| !pairs.iter().any(|(paired, _)| paired.port_id == o.port_id))
+        .map(|o| o.channel.clone())
+        .collect()
+}
+
+/// Pair ports by position, for inputs whose channels match none of the outputs
+///
+/// Pro Audio and some HDMI profiles expose ports without the usual channel
+/// positions (e.g. `AUX0`, `AUX1`); linking in port order at least gets the
+/// first channels through. Ports are ordered by id on both sides.
+pub fn pair_ports_by_position<'a>(
+    outputs: &[&'a AudioPort],
+    inputs: &[&'a AudioPort],
+) -> Vec<(&'a AudioPort, &'a AudioPort)> {
+    let by_id = |ports: &[&'a AudioPort]| {
+        let mut ports = ports.to_vec();
+        ports.sort_by_key(|p| p.port_id);
+        ports
+    };
+    by_id(outputs).into_iter().zip(by_id(inputs)).collect()
+}
+
+/// Pair output ports with the input ports they should be linked to
+///
+/// Matching channels (see [`same_channel`]) are linked directly. A mono output fans out to both
+/// stereo inputs when the input side has no mono port, and stereo outputs are
+/// both linked into a mono input when the output side has no mono port.
+pub fn pair_ports<'a>(
+    outputs: &[&'a AudioPort],
+    inputs: &[&'a AudioPort],
+) -> Vec<(&'a AudioPort, &'a AudioPort)> {
+    let outputs_have_mono = outputs.iter().any(|p| is_mono(p));
+    let inputs_have_mono = inputs.iter().any(|p| is_mono(p));
+
+    let mut pairs = Vec::new();
+    for &output in outputs {
+        for &input in inputs {
+            let compatible = same_channel(&output.channel, &input.channel)
+                || (is_mono(output) && !inputs_have_mono)
+                || (is_mono(input) && !outputs_have_mono);
+
+            if compatible {
+                pairs.push((output, input));
+            }
+        }
+    }
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipewire::extract_ports;
+    use crate::pipewire::mock::MockRunner;
+    use serde_json::json;
+
+    /// A way of pairing output ports with input ports
+    type Pairing =
+        for<'a> fn(&[&'a AudioPort], &[&'a AudioPort]) -> Vec<(&'a AudioPort, &'a AudioPort)>;
+
+    /// Port names `pair` links from `outputs`'s output ports to `inputs`'s
+    /// input ports, in a graph parsed from pw-dump JSON
+    fn linked(
+        graph: &MockRunner,
+        outputs: u32,
+        inputs: u32,
+        pair: Pairing,
+    ) -> Vec<(String, String)> {
+        let ports = extract_ports(&graph.objects());
+        let outputs = ports_in_order(&ports, outputs, PortDirection::Output);
+        let inputs = ports_in_order(&ports, inputs, PortDirection::Input);
+        pair(&outputs, &inputs)
+            .into_iter()
+            .map(|(output, input)| (output.port_name.clone(), input.port_name.clone()))
+            .collect()
+    }
+
+    fn node(graph: &MockRunner, name: &str, direction: &str, channels: &[&str]) -> u32 {
+        let node = graph.add_node(name, "Stream/Output/Audio", json!({}));
+        graph.add_ports(node, direction, direction, channels);
+        node
+    }
+
+    #[test]
+    fn mono_source_fans_out_to_stereo() {
+        let graph = MockRunner::default();
+        let source = node(&graph, "Discord", "output", &["MONO"]);
+        let sink = node(&graph, "speakers", "input", &["FL", "FR"]);
+
+        assert_eq!(
+            linked(&graph, source, sink, pair_ports),
+            [
+                ("output_MONO".to_string(), "input_FL".to_string()),
+                ("output_MONO".to_string(), "input_FR".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn mono_links_to_mono_only() {
+        let graph = MockRunner::default();
+        let source = node(&graph, "Discord", "output", &["MONO"]);
+        let sink = node(&graph, "headset", "input", &["MONO", "FL", "FR"]);
+
+        assert_eq!(
+            linked(&graph, source, sink, pair_ports),
+            [("output_MONO".to_string(), "input_MONO".to_string())]
+        );
+    }
+
+    #[test]
+    fn stereo_source_mixes_into_mono() {
+        let graph = MockRunner::default();
+        let source = node(&graph, "Firefox", "output", &["FL", "FR"]);
+        let sink = node(&graph, "mic-loop", "input", &["MONO"]);
+
+        assert_eq!(
+            linked(&graph, source, sink, pair_ports),
+            [
+                ("output_FL".to_string(), "input_MONO".to_string()),
+                ("output_FR".to_string(), "input_MONO".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn normalize_channel_maps_aux_and_casing() {
+        assert_eq!(normalize_channel("aux0"), "FL");
+        assert_eq!(normalize_channel("AUX1"), "FR");
+        assert_eq!(normalize_channel("fl"), "FL");
+        assert_eq!(normalize_channel("AUX2"), "AUX2");
+        assert!(same_channel("Aux1", "fr"));
+    }
+
+    #[test]
+    fn aux_only_source_pairs_as_stereo() {
+        let graph = MockRunner::default();
+        let source = node(&graph, "mpv", "output", &["AUX0", "AUX1"]);
+        let sink = node(&graph, "speakers", "input", &["FL", "FR"]);
+
+        let ports = extract_ports(&graph.objects());
+        assert!(has_channel_positions(&routable_ports(
+            &ports,
+            source,
+            PortDirection::Output
+        )));
+        assert_eq!(
+            linked(&graph, source, sink, pair_ports),
+            [
+                ("output_AUX0".to_string(), "input_FL".to_string()),
+                ("output_AUX1".to_string(), "input_FR".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn numbered_ports_pair_in_port_order() {
+        let graph = MockRunner::default();
+        let source = graph.add_node("Ardour", "Stream/Output/Audio", json!({}));
+        for (name, channel) in [
+            ("output_1", None),
+            ("output_2", Some("UNK")),
+            ("output_3", Some("AUX2")),
+            ("output_4", Some("AUX3")),
+        ] {
+            graph.add_port(source, "output", name, channel);
+        }
+        let loopback = node(&graph, "loopback", "input", &["FL", "FR"]);
+
+        let ports = extract_ports(&graph.objects());
+        assert!(!has_channel_positions(&routable_ports(
+            &ports,
+            source,
+            PortDirection::Output
+        )));
+        assert_eq!(
+            linked(&graph, source, loopback, pair_ports_by_position),
+            [
+                ("output_1".to_string(), "input_FL".to_string()),
+                ("output_2".to_string(), "input_FR".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,458 @@
+use crate::error::{PwSplitterError, Result};
+use crate::pipewire::linker::create_link_by_id;
+use crate::pipewire::runner::{query_timeout, runner};
+use crate::pipewire::session::session;
+use crate::pipewire::types::*;
+use crate::pipewire::{channels, parser};
+use std::env;
+use std::io;
+use std::path::PathBuf;
+use std::process::Child;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Interval between graph queries while waiting for ports to appear
+pub const PORT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Default time allowed for freshly spawned nodes to create their ports
+pub const DEFAULT_PORT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// External PipeWire tools this crate shells out to
+///
+/// Links are made with the tool of [`link_backend`](super::link_backend), which
+/// is checked separately.
+pub const REQUIRED_TOOLS: [&str; 2] = ["pw-dump", "pw-loopback"];
+
+/// Typical package names providing `tool`, for install hints
+pub fn tool_package(tool: &str) -> &'static str {
+    match tool {
+        "wpctl" => "wireplumber package",
+        _ => "PipeWire utilities package (e.g. pipewire-utils, pipewire-bin or pipewire)",
+    }
+}
+
+/// Map a failure to run `tool`, singling out a missing binary
+///
+/// Otherwise the bare "No such file or directory" doesn't say what's missing.
+pub(super) fn run_error(
+    tool: &'static str,
+    e: io::Error,
+    wrap: impl FnOnce(String) -> PwSplitterError,
+) -> PwSplitterError {
+    if e.kind() == io::ErrorKind::NotFound {
+        PwSplitterError::ToolNotInstalled(tool, tool_package(tool))
+    } else {
+        wrap(e.to_string())
+    }
+}
+
+/// Locate an executable on `PATH`
+pub fn find_in_path(program: &str) -> Option<PathBuf> {
+    env::split_paths(&env::var_os("PATH")?)
+        .map(|dir| dir.join(program))
+        .find(|path| path.is_file())
+}
+
+/// Query all PipeWire objects, reusing a snapshot from the last few hundred
+/// milliseconds if there is one
+///
+/// Use [`get_fresh_pw_objects`] when waiting for something to appear.
+pub fn get_pw_objects() -> Result<Vec<PwObject>> {
+    session().objects(query_pw_objects)
+}
+
+/// Query all PipeWire objects, bypassing the snapshot cache
+pub fn get_fresh_pw_objects() -> Result<Vec<PwObject>> {
+    session().refresh(query_pw_objects)
+}
+
+/// Drop the cached snapshot after changing the graph
+pub fn invalidate_pw_objects() {
+    session().invalidate();
+}
+
+/// Query all PipeWire objects
+///
+/// With the `native-backend` feature the registry is read directly, falling
+/// back to pw-dump if connecting to PipeWire natively fails.
+fn query_pw_objects() -> Result<Vec<PwObject>> {
+    #[cfg(feature = "native-backend")]
+    match crate::pipewire::native::get_pw_objects() {
+        Ok(objects) => return Ok(objects),
+        Err(PwSplitterError::PipeWireNotRunning) => {
+            return Err(PwSplitterError::PipeWireNotRunning);
+        }
+        Err(e) => tracing::debug!("native backend failed, falling back to pw-dump: {}", e),
+    }
+
+    dump_pw_objects()
+}
+
+/// Run pw-dump and return parsed objects
+pub fn dump_pw_objects() -> Result<Vec<PwObject>> {
+    let output = runner().query("pw-dump", &[]).map_err(|e| {
+        if e.kind() == io::ErrorKind::TimedOut {
+            return PwSplitterError::CommandTimeout("pw-dump", query_timeout());
+        }
+        run_error("pw-dump", e, |e| {
+            PwSplitterError::CommandFailed(format!("pw-dump: {}", e))
+        })
+    })?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if is_connection_failure(&stderr) {
+            return Err(PwSplitterError::PipeWireNotRunning);
+        }
+        return Err(PwSplitterError::CommandFailed(format!(
+            "pw-dump failed: {}",
+            stderr
+        )));
+    }
+
+    let json_str = String::from_utf8_lossy(&output.stdout);
+    let objects = parser::parse_pw_dump(&json_str)?;
+    tracing::trace!("pw-dump returned {} objects", objects.len());
+    Ok(objects)
+}
+
+/// Whether a PipeWire tool's stderr says it couldn't reach the server
+///
+/// e.g. `can't connect: Host is down` when no PipeWire server is running.
+fn is_connection_failure(stderr: &str) -> bool {
+    ["can't connect", "Host is down", "Connection refused"]
+        .iter()
+        .any(|message| stderr.contains(message))
+}
+
+/// Re-run `query` every [`PORT_POLL_INTERVAL`] until `ready` accepts its result
+///
+/// Returns the accepted objects, or [`PwSplitterError::PortsTimeout`] naming
+/// `what` once `timeout` has elapsed. `query` is normally
+/// [`get_fresh_pw_objects`].
+pub fn wait_for_objects(
+    query: impl FnMut() -> Result<Vec<PwObject>>,
+    ready: impl Fn(&[PwObject]) -> bool,
+    what: &str,
+    timeout: Duration,
+) -> Result<Vec<PwObject>> {
+    poll_objects(query, ready, what, timeout, Instant::now, thread::sleep)
+}
+
+/// [`wait_for_objects`], telling time with `now` and waiting with `sleep`
+fn poll_objects(
+    mut query: impl FnMut() -> Result<Vec<PwObject>>,
+    ready: impl Fn(&[PwObject]) -> bool,
+    what: &str,
+    timeout: Duration,
+    now: impl Fn() -> Instant,
+    mut sleep: impl FnMut(Duration),
+) -> Result<Vec<PwObject>> {
+    let started = now();
+    let deadline = started + timeout;
+    loop {
+        let objects = query()?;
+        if ready(&objects) {
+            tracing::debug!("{} ready after {:?}", what, now() - started);
+            return Ok(objects);
+        }
+        if now() >= deadline {
+            tracing::warn!("gave up waiting for {} after {:?}", what, timeout);
+            return Err(PwSplitterError::PortsTimeout(what.to_string()));
+        }
+        sleep(PORT_POLL_INTERVAL);
+    }
+}
+
+/// Quote a property value for a pw-loopback props string
+///
+/// Descriptions can be user-chosen, so a stray `"` or `\` must not end the
+/// value early and let the rest be parsed as further properties.
+fn quote_prop(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        if matches!(c, '"' | '\\') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Spawn a pw-loopback process with no auto-connect on either side
+/// This allows us to manually wire both capture and playback
+///
+/// Both sides are tagged with `pw_splitter.split=<split_name>` and
+/// `pw_splitter.role=capture|playback` so they can be found reliably.
+///
+/// `channels` is the channel map to use (e.g. the source's `FL FR FC LFE RL
+/// RR`); when empty, pw-loopback's stereo default applies. `tuning` sets the
+/// latency and rate of both sides.
+///
+/// In a dry run the command is only recorded, and `None` is returned in
+/// place of the child.
+pub fn spawn_loopback_no_target(
+    split_name: &str,
+    loopback_name: &str,
+    loopback_desc: &str,
+    channels: &[String],
+    tuning: &LoopbackTuning,
+) -> Result<Option<Child>> {
+    let tags = |role: LoopbackRole| {
+        format!(
+            "pw_splitter.split={} pw_splitter.role={}",
+            quote_prop(split_name),
+            role.as_str()
+        )
+    };
+
+    // No autoconnect on capture side - we'll manually link from the source
+    let capture_props = format!(
+        "node.name={} node.description={} node.autoconnect=false {}{}",
+        loopback_name,
+        quote_prop(&format!("{} input", loopback_desc)),
+        tags(LoopbackRole::Capture),
+        tuning.props()
+    );
+
+    // No autoconnect on playback side - we'll manually link to the destination
+    let playback_props = format!(
+        "node.name={} node.description={} node.autoconnect=false {}{}",
+        loopback_name,
+        quote_prop(&format!("{} output", loopback_desc)),
+        tags(LoopbackRole::Playback),
+        tuning.props()
+    );
+
+    let mut args = Vec::new();
+    if !channels.is_empty() {
+        args.extend([
+            format!("--channels={}", channels.len()),
+            format!("--channel-map=[{}]", channels.join(",")),
+        ]);
+    }
+    args.extend([
+        format!("--capture-props={}", capture_props),
+        format!("--playback-props={}", playback_props),
+    ]);
+
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    invalidate_pw_objects();
+    let Some(child) = runner()
+        .spawn("pw-loopback", &args)
+        .map_err(|e| run_error("pw-loopback", e, PwSplitterError::LoopbackSpawnFailed))?
+    else {
+        return Ok(None);
+    };
+
+    tracing::debug!(
+        "spawned pw-loopback {} (pid {}, channels {:?})",
+        loopback_name,
+        child.id(),
+        channels
+    );
+    Ok(Some(child))
+}
+
+/// Connect a loopback's output ports to a recording destination's input ports
+///
+/// Works for both [`DestKind`]s: capture streams and virtual sinks both expose
+/// plain input ports, and linking by port id sidesteps duplicate node names.
+///
+/// The loopback must already have created its ports; callers are expected to
+/// wait for it after spawning.
+///
+/// Returns the loopback channels the destination has no port for.
+pub fn connect_loopback_to_recording_dest(
+    loopback_playback_name: &str,
+    dest_node_id: u32,
+) -> Result<Vec<String>> {
+    let objects = get_pw_objects()?;
+    let ports = parser::extract_ports(&objects);
+
+    // Find the loopback's playback side (the one with output ports); untagged
+    // loopbacks from older versions fall back to a plain name lookup
+    let loopback_node_id =
+        parser::find_tagged_loopback_node(&objects, loopback_playback_name, LoopbackRole::Playback)
+            .or_else(|| parser::find_node_by_name(&objects, loopback_playback_name));
+
+    let loopback_ports = if let Some(node_id) = loopback_node_id {
+        channels::routable_ports(&ports, node_id, PortDirection::Output)
+    } else {
+        // Fallback: search by port name pattern
+        Vec::new()
+    };
+
+    // Find the recording destination's input ports by node_id
+    // This is critical because multiple nodes can have the same node.name (e.g., "OBS")
+    let dest_ports = channels::routable_ports(&ports, dest_node_id, PortDirection::Input);
+    tracing::debug!(
+        "{} -> node {}: {} loopback port(s), {} destination port(s)",
+        loopback_playback_name,
+        dest_node_id,
+        loopback_ports.len(),
+        dest_ports.len()
+    );
+
+    if loopback_ports.is_empty() || dest_ports.is_empty() {
+        return Err(PwSplitterError::LinkCreationFailed(format!(
+            "Could not find ports for loopback ({} ports) or destination node {} ({} ports)",
+            loopback_ports.len(),
+            dest_node_id,
+            dest_ports.len()
+        )));
+    }
+
+    // Create links using PORT IDs to avoid ambiguity
+    // Multiple OBS nodes have the same node.name="OBS", so "OBS:input_FL" is ambiguous
+    // Using port IDs directly ensures we connect to the correct node
+    let pairs = channels::pair_ports(&loopback_ports, &dest_ports);
+    for (lb_port, dest_port) in &pairs {
+        let output_port = get_port_link_name(loopback_playback_name, &lb_port.port_name);
+        create_link_by_id(&output_port, dest_port.port_id)?;
+    }
+
+    Ok(channels::unpaired_channels(&loopback_ports, &pairs))
+}
+
+/// Set a node's volume (1.0 = 100%) using wpctl
+pub fn set_node_volume(node_id: u32, volume: f32) -> Result<()> {
+    let output = runner()
+        .run(
+            "wpctl",
+            &[
+                "set-volume",
+                &node_id.to_string(),
+                &format!("{:.3}", volume),
+            ],
+        )
+        .map_err(|e| {
+            run_error("wpctl", e, |e| {
+                PwSplitterError::CommandFailed(format!("wpctl: {}", e))
+            })
+        })?;
+
+    if !output.status.success() {
+        return Err(PwSplitterError::CommandFailed(format!(
+            "wpctl set-volume failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        )));
+    }
+
+    Ok(())
+}
+
+/// Set a node's per-channel volumes (linear gains, in channel order) using pw-cli
+pub fn set_node_channel_volumes(node_id: u32, volumes: &[f32]) -> Result<()> {
+    let volumes = volumes
+        .iter()
+        .map(|v| format!("{:.4}", v))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let output = runner()
+        .run(
+            "pw-cli",
+            &[
+                "set-param",
+                &node_id.to_string(),
+                "Props",
+                &format!("{{ channelVolumes: [ {} ] }}", volumes),
+            ],
+        )
+        .map_err(|e| {
+            run_error("pw-cli", e, |e| {
+                PwSplitterError::CommandFailed(format!("pw-cli: {}", e))
+            })
+        })?;
+
+    if !output.status.success() {
+        return Err(PwSplitterError::CommandFailed(format!(
+            "pw-cli set-param failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        )));
+    }
+
+    Ok(())
+}
+
+/// Get port name in pw-link format: "node_name:port_name"
+pub fn get_port_link_name(node_name: &str, port_name: &str) -> String {
+    format!("{}:{}", node_name, port_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipewire::mock::MockRunner;
+    use serde_json::json;
+    use std::cell::Cell;
+
+    /// A provider whose graph gains a node on the `ready_at`th query
+    fn provider(queries: &Cell<u32>, ready_at: u32) -> impl FnMut() -> Result<Vec<PwObject>> {
+        let graph = MockRunner::default();
+        move || {
+            queries.set(queries.get() + 1);
+            if queries.get() == ready_at {
+                graph.add_node("Firefox_to_Recording", "Stream/Input/Audio", json!({}));
+            }
+            Ok(graph.objects())
+        }
+    }
+
+    fn has_loopback(objects: &[PwObject]) -> bool {
+        parser::find_node_by_name(objects, "Firefox_to_Recording").is_some()
+    }
+
+    #[test]
+    fn wait_polls_until_ready() {
+        let queries = Cell::new(0);
+        let objects = wait_for_objects(
+            provider(&queries, 3),
+            has_loopback,
+            "loopback",
+            Duration::from_secs(2),
+        )
+        .unwrap();
+
+        assert!(has_loopback(&objects));
+        assert_eq!(queries.get(), 3);
+    }
+
+    #[test]
+    fn wait_gives_up_after_timeout() {
+        let queries = Cell::new(0);
+        // A clock that only moves when the poll sleeps
+        let clock = Cell::new(Instant::now());
+        let result = poll_objects(
+            provider(&queries, u32::MAX),
+            has_loopback,
+            "loopback",
+            PORT_POLL_INTERVAL * 3,
+            || clock.get(),
+            |interval| clock.set(clock.get() + interval),
+        );
+
+        assert!(matches!(result, Err(PwSplitterError::PortsTimeout(what)) if what == "loopback"));
+        // At the start and after each of the three intervals
+        assert_eq!(queries.get(), 4);
+    }
+
+    #[test]
+    fn wait_stops_on_query_error() {
+        let queries = Cell::new(0);
+        let result = wait_for_objects(
+            || {
+                queries.set(queries.get() + 1);
+                Err(PwSplitterError::PipeWireNotRunning)
+            },
+            has_loopback,
+            "loopback",
+            Duration::from_secs(2),
+        );
+
+        assert!(matches!(result, Err(PwSplitterError::PipeWireNotRunning)));
+        assert_eq!(queries.get(), 1);
+    }
+}
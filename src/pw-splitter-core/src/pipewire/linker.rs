@@ -0,0 +1,250 @@
+//! Creating and destroying links, with pw-link or pw-cli.
+//!
+//! Minimal PipeWire installs may ship pw-cli without pw-link. Both backends
+//! take ports as `node:port` names or ids and treat an existing link (when
+//! creating) or a missing one (when destroying) as success, so callers don't
+//! depend on which one is in use.
+
+use crate::error::{PwSplitterError, Result};
+use crate::pipewire::commands::{
+    find_in_path, get_fresh_pw_objects, get_pw_objects, invalidate_pw_objects, run_error,
+};
+use crate::pipewire::parser;
+use crate::pipewire::runner::{is_dry_run, runner};
+use crate::pipewire::types::*;
+use serde::Deserialize;
+use std::process::Output;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// Tool used to create and destroy links
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LinkBackend {
+    /// `pw-link`, the default when installed
+    PwLink,
+    /// `pw-cli create-link` and `pw-cli destroy`
+    PwCli,
+}
+
+impl LinkBackend {
+    /// The executable this backend runs
+    pub fn tool(self) -> &'static str {
+        match self {
+            LinkBackend::PwLink => "pw-link",
+            LinkBackend::PwCli => "pw-cli",
+        }
+    }
+
+    /// pw-link if it's installed, else pw-cli if that is
+    ///
+    /// With neither installed pw-link is kept, so the error names the usual tool.
+    pub fn detect() -> Self {
+        if find_in_path("pw-link").is_none() && find_in_path("pw-cli").is_some() {
+            LinkBackend::PwCli
+        } else {
+            LinkBackend::PwLink
+        }
+    }
+}
+
+/// The chosen backend: 0 until chosen, else 1 + its index
+static LINK_BACKEND: AtomicU8 = AtomicU8::new(0);
+
+/// Choose the link backend; `None` detects it from the installed tools
+pub fn set_link_backend(backend: Option<LinkBackend>) {
+    let backend = backend.unwrap_or_else(LinkBackend::detect);
+    if backend == LinkBackend::PwCli {
+        tracing::debug!("linking with pw-cli");
+    }
+    LINK_BACKEND.store(backend as u8 + 1, Ordering::Relaxed);
+}
+
+/// The backend links are made with, detecting it on first use if unset
+pub fn link_backend() -> LinkBackend {
+    match LINK_BACKEND.load(Ordering::Relaxed) {
+        0 => {
+            set_link_backend(None);
+            link_backend()
+        }
+        1 => LinkBackend::PwLink,
+        _ => LinkBackend::PwCli,
+    }
+}
+
+/// Create a link using port ID for the input (avoids ambiguity with duplicate node names)
+pub fn create_link_by_id(output_port: &str, input_port_id: u32) -> Result<()> {
+    link(output_port, &input_port_id.to_string()).map_err(|e| match e {
+        PwSplitterError::LinkCreationFailed(reason) => {
+            PwSplitterError::LinkCreationFailed(format!(
+                "Failed to link {} -> {}: {}",
+                output_port, input_port_id, reason
+            ))
+        }
+        e => e,
+    })
+}
+
+/// Create a link between two ports
+///
+/// The link can be created and then fail to negotiate (e.g. a format the
+/// device rejects). With `verify`, the graph is re-queried to confirm the link
+/// exists and isn't in the error state, at the cost of an extra pw-dump.
+pub fn create_link(output_port: &str, input_port: &str, verify: bool) -> Result<()> {
+    link(output_port, input_port)?;
+    if verify && !is_dry_run() {
+        verify_link(output_port, input_port)?;
+    }
+    Ok(())
+}
+
+/// Link two ports with the current backend; an existing link is success
+fn link(output_port: &str, input_port: &str) -> Result<()> {
+    let backend = link_backend();
+    let output = match backend {
+        LinkBackend::PwLink => {
+            tracing::debug!("pw-link {} {}", output_port, input_port);
+            invalidate_pw_objects();
+            runner().run("pw-link", &[output_port, input_port])
+        }
+        LinkBackend::PwCli => {
+            let objects = get_pw_objects()?;
+            let (output_node, output_id) =
+                resolve_port(&objects, output_port, PortDirection::Output)?;
+            let (input_node, input_id) = resolve_port(&objects, input_port, PortDirection::Input)?;
+            let args = [
+                output_node.to_string(),
+                output_id.to_string(),
+                input_node.to_string(),
+                input_id.to_string(),
+            ];
+            tracing::debug!("pw-cli create-link {}", args.join(" "));
+            invalidate_pw_objects();
+            // Without linger the link would go away when pw-cli exits
+            let mut args: Vec<&str> = args.iter().map(String::as_str).collect();
+            args.insert(0, "create-link");
+            args.push("object.linger=true");
+            runner().run("pw-cli", &args)
+        }
+    }
+    .map_err(|e| run_error(backend.tool(), e, PwSplitterError::LinkCreationFailed))?;
+
+    let message = output_text(&output);
+    if message.contains("File exists") {
+        return Ok(());
+    }
+    if failed(backend, &output, &message) {
+        return Err(PwSplitterError::LinkCreationFailed(message));
+    }
+    Ok(())
+}
+
+/// Check that a link between two ports is present and not failed
+fn verify_link(output_port: &str, input_port: &str) -> Result<()> {
+    let objects = get_fresh_pw_objects()?;
+    let failed = |reason: &str| {
+        PwSplitterError::LinkCreationFailed(format!("{} -> {} {}", output_port, input_port, reason))
+    };
+
+    let (Ok((_, output_id)), Ok((_, input_id))) = (
+        resolve_port(&objects, output_port, PortDirection::Output),
+        resolve_port(&objects, input_port, PortDirection::Input),
+    ) else {
+        return Err(failed("lost a port right after linking"));
+    };
+    match find_link(&objects, output_id, input_id) {
+        None => Err(failed("was not created")),
+        Some(link) => match link.info.as_ref().and_then(|info| info.state.as_deref()) {
+            Some("error") => Err(failed("failed to negotiate (format mismatch?)")),
+            // The native backend reports no state; presence is all it can confirm
+            _ => Ok(()),
+        },
+    }
+}
+
+/// Destroy a link between two ports; a link that doesn't exist is success
+pub fn destroy_link(output_port: &str, input_port: &str) -> Result<()> {
+    let backend = link_backend();
+    let output = match backend {
+        LinkBackend::PwLink => {
+            tracing::debug!("pw-link -d {} {}", output_port, input_port);
+            invalidate_pw_objects();
+            runner().run("pw-link", &["-d", output_port, input_port])
+        }
+        LinkBackend::PwCli => {
+            let objects = get_pw_objects()?;
+            let ports = (
+                resolve_port(&objects, output_port, PortDirection::Output),
+                resolve_port(&objects, input_port, PortDirection::Input),
+            );
+            let (Ok((_, output_id)), Ok((_, input_id))) = ports else {
+                return Ok(());
+            };
+            let Some(link) = find_link(&objects, output_id, input_id) else {
+                return Ok(());
+            };
+            tracing::debug!("pw-cli destroy {}", link.id);
+            invalidate_pw_objects();
+            runner().run("pw-cli", &["destroy", &link.id.to_string()])
+        }
+    }
+    .map_err(|e| run_error(backend.tool(), e, PwSplitterError::LinkDestroyFailed))?;
+
+    let message = output_text(&output);
+    // Ignore errors about non-existent links
+    if failed(backend, &output, &message)
+        && !message.contains("No such file")
+        && !message.is_empty()
+    {
+        return Err(PwSplitterError::LinkDestroyFailed(message));
+    }
+    Ok(())
+}
+
+/// Whether a link command failed
+///
+/// pw-cli can report an error yet exit successfully, so its output counts too.
+fn failed(backend: LinkBackend, output: &Output, message: &str) -> bool {
+    !output.status.success()
+        || (backend == LinkBackend::PwCli && message.to_lowercase().contains("error"))
+}
+
+/// A command's stderr, or its stdout if stderr is empty (pw-cli reports
+/// some errors there)
+fn output_text(output: &Output) -> String {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if stderr.trim().is_empty() {
+        String::from_utf8_lossy(&output.stdout).trim().to_string()
+    } else {
+        stderr.trim().to_string()
+    }
+}
+
+/// Node and port id of a port given as an id or a `node:port` name
+fn resolve_port(objects: &[PwObject], port: &str, direction: PortDirection) -> Result<(u32, u32)> {
+    let ports = parser::extract_ports(objects);
+    let found = match port.parse::<u32>() {
+        Ok(id) => ports.iter().find(|p| p.port_id == id),
+        Err(_) => port.rsplit_once(':').and_then(|(node_name, port_name)| {
+            ports.iter().find(|p| {
+                p.direction == direction
+                    && p.port_name == port_name
+                    && parser::get_node_name(objects, p.node_id).as_deref() == Some(node_name)
+            })
+        }),
+    };
+    found
+        .map(|p| (p.node_id, p.port_id))
+        .ok_or_else(|| PwSplitterError::NodeNotFound(format!("port {}", port)))
+}
+
+/// The link from `output_id` to `input_id`, if there is one
+fn find_link(objects: &[PwObject], output_id: u32, input_id: u32) -> Option<&PwLink> {
+    objects.iter().find_map(|obj| match obj {
+        PwObject::Link(
+            link @ PwLink {
+                info: Some(info), ..
+            },
+        ) if info.output_port_id == output_id && info.input_port_id == input_id => Some(link),
+        _ => None,
+    })
+}
@@ -0,0 +1,95 @@
+//! Structured `media.class` values.
+//!
+//! Streams read `Stream/<direction>/<media>` and devices `<media>/<role>`,
+//! either optionally followed by a subtype, e.g. `Stream/Output/Audio` or
+//! `Audio/Source/Virtual`. Comparing whole strings misses those subtypes, and
+//! prefix checks would take `Audio/Sinking` for a sink.
+
+/// Whether a node is an application's stream or a device
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MediaCategory {
+    /// `Stream/...`: a client's playback or capture stream
+    Stream,
+    /// `<media>/Sink|Source`: hardware, or a virtual device acting as one
+    Device,
+}
+
+/// Which way media flows through a node
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MediaDirection {
+    /// Produces media: playback streams (`Stream/Output`) and sources
+    Output,
+    /// Consumes media: capture streams (`Stream/Input`) and sinks
+    Input,
+}
+
+/// A parsed `media.class`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MediaClass<'a> {
+    pub category: MediaCategory,
+    pub direction: MediaDirection,
+    /// `Audio`, `Video` or `Midi`
+    pub media: &'a str,
+    /// Anything after the base class, e.g. `Virtual`
+    pub subtype: Option<&'a str>,
+}
+
+impl<'a> MediaClass<'a> {
+    /// Parse a `media.class`; `None` for classes that are neither a stream
+    /// nor a sink or source (e.g. `Audio/Duplex`, `Midi/Bridge`)
+    pub fn parse(media_class: &'a str) -> Option<Self> {
+        let (category, direction, media, subtype) = match media_class.split_once('/')? {
+            ("Stream", rest) => {
+                let mut parts = rest.splitn(3, '/');
+                let direction = parts.next()?;
+                (
+                    MediaCategory::Stream,
+                    direction,
+                    parts.next()?,
+                    parts.next(),
+                )
+            }
+            (media, rest) => {
+                let mut parts = rest.splitn(2, '/');
+                let role = match parts.next()? {
+                    "Source" => "Output",
+                    "Sink" => "Input",
+                    _ => return None,
+                };
+                (MediaCategory::Device, role, media, parts.next())
+            }
+        };
+        let direction = match direction {
+            "Output" => MediaDirection::Output,
+            "Input" => MediaDirection::Input,
+            _ => return None,
+        };
+        Some(Self {
+            category,
+            direction,
+            media,
+            subtype,
+        })
+    }
+
+    /// Whether this is an audio stream flowing `direction`, of any subtype
+    pub fn is_audio_stream(&self, direction: MediaDirection) -> bool {
+        self.category == MediaCategory::Stream && self.is_audio(direction)
+    }
+
+    /// Whether this is an audio device flowing `direction` (a sink for
+    /// input, a source for output), of any subtype
+    pub fn is_audio_device(&self, direction: MediaDirection) -> bool {
+        self.category == MediaCategory::Device && self.is_audio(direction)
+    }
+
+    /// Whether the node only stands in for a device or stream, e.g. an
+    /// `Audio/Source/Virtual` made by a filter chain
+    pub fn is_virtual(&self) -> bool {
+        self.subtype == Some("Virtual")
+    }
+
+    fn is_audio(&self, direction: MediaDirection) -> bool {
+        self.media == "Audio" && self.direction == direction
+    }
+}
@@ -0,0 +1,514 @@
+//! A fake PipeWire session for tests.
+//!
+//! [`MockRunner`] records every command it is given and answers from an
+//! in-memory graph: pw-dump lists it, pw-link adds and removes links, and
+//! pw-loopback adds both sides of a loopback. Each loopback is backed by an
+//! idle process whose command line reads like pw-loopback's, so killing and
+//! health checks behave as they would for the real thing.
+
+use crate::pipewire::runner::{CommandRunner, format_command};
+use crate::pipewire::session::session;
+use crate::pipewire::{
+    AudioSource, LinkBackend, PwObject, RecordingDest, SourceClasses, extract_audio_sources,
+    extract_recording_dests, find_source_connections, parse_pw_dump, set_link_backend,
+};
+use crate::splitter::{SetupOutcome, SplitConfig, SplitResult, setup_split};
+use serde_json::{Value, json};
+use std::env;
+use std::fs;
+use std::io;
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Output, Stdio};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+/// Script of a stand-in loopback process: idle until killed, leaving at most
+/// a one-second `sleep` behind
+const LOOPBACK_SCRIPT: &str = "while sleep 1; do :; done";
+
+/// A [`CommandRunner`] backed by a fake graph
+#[derive(Default)]
+pub struct MockRunner {
+    graph: Mutex<Graph>,
+    calls: Mutex<Vec<String>>,
+    /// Commands starting with any of these fail
+    failing: Mutex<Vec<String>>,
+    /// Pids of the stand-in loopback processes
+    spawned: Mutex<Vec<u32>>,
+}
+
+#[derive(Default)]
+struct Graph {
+    last_id: u32,
+    objects: Vec<Value>,
+}
+
+impl Graph {
+    fn next_id(&mut self) -> u32 {
+        self.last_id += 1;
+        self.last_id
+    }
+
+    /// Port id of `port`, given as an id or a `node:port` name
+    fn resolve_port(&self, port: &str, direction: &str) -> Option<u32> {
+        if let Ok(id) = port.parse() {
+            return Some(id);
+        }
+        let (node_name, port_name) = port.rsplit_once(':')?;
+        self.objects.iter().find_map(|obj| {
+            let info = &obj["info"];
+            let node_id = info["props"]["node.id"].as_u64()?;
+            let matches = info["direction"] == direction
+                && info["props"]["port.name"] == port_name
+                && self.node(node_id as u32)?["info"]["props"]["node.name"] == node_name;
+            matches.then(|| obj["id"].as_u64().unwrap() as u32)
+        })
+    }
+
+    fn node(&self, id: u32) -> Option<&Value> {
+        self.objects
+            .iter()
+            .find(|obj| obj["id"] == id && obj["type"] == "PipeWire:Interface:Node")
+    }
+
+    fn port_node(&self, port_id: u32) -> Option<u32> {
+        self.objects
+            .iter()
+            .find(|obj| obj["id"] == port_id)
+            .and_then(|port| port["info"]["props"]["node.id"].as_u64())
+            .map(|id| id as u32)
+    }
+
+    fn link(&mut self, output_port: u32, input_port: u32) -> Option<u32> {
+        let (output_node, input_node) = (self.port_node(output_port)?, self.port_node(input_port)?);
+        if let Some(existing) = self.find_link(output_port, input_port) {
+            return Some(existing);
+        }
+        let id = self.next_id();
+        self.objects.push(json!({
+            "id": id,
+            "type": "PipeWire:Interface:Link",
+            "info": {
+                "output-node-id": output_node,
+                "output-port-id": output_port,
+                "input-node-id": input_node,
+                "input-port-id": input_port,
+                "state": "active",
+            },
+        }));
+        Some(id)
+    }
+
+    fn find_link(&self, output_port: u32, input_port: u32) -> Option<u32> {
+        self.objects.iter().find_map(|obj| {
+            let info = &obj["info"];
+            (obj["type"] == "PipeWire:Interface:Link"
+                && info["output-port-id"] == output_port
+                && info["input-port-id"] == input_port)
+                .then(|| obj["id"].as_u64().unwrap() as u32)
+        })
+    }
+}
+
+impl MockRunner {
+    /// Add a node, returning its id
+    pub fn add_node(&self, name: &str, media_class: &str, props: Value) -> u32 {
+        let mut graph = self.graph();
+        let id = graph.next_id();
+        let mut all_props = json!({
+            "node.name": name,
+            "media.class": media_class,
+            "object.serial": 1000 + id,
+        });
+        if let (Some(all), Value::Object(extra)) = (all_props.as_object_mut(), props) {
+            all.extend(extra);
+        }
+        graph.objects.push(json!({
+            "id": id,
+            "type": "PipeWire:Interface:Node",
+            "info": { "state": "running", "props": all_props },
+        }));
+        id
+    }
+
+    /// Add a port to `node`, returning its id
+    ///
+    /// `direction` is `"input"` or `"output"`; `channel` `None` leaves
+    /// `audio.channel` unset, as JACK-style apps do.
+    pub fn add_port(&self, node: u32, direction: &str, name: &str, channel: Option<&str>) -> u32 {
+        let mut graph = self.graph();
+        let id = graph.next_id();
+        let mut props = json!({ "node.id": node, "port.name": name, "object.id": id });
+        if let Some(channel) = channel {
+            props["audio.channel"] = json!(channel);
+        }
+        graph.objects.push(json!({
+            "id": id,
+            "type": "PipeWire:Interface:Port",
+            "info": { "direction": direction, "props": props },
+        }));
+        id
+    }
+
+    /// Add a port per channel, named `<prefix>_<channel>`
+    pub fn add_ports(
+        &self,
+        node: u32,
+        direction: &str,
+        prefix: &str,
+        channels: &[&str],
+    ) -> Vec<u32> {
+        channels
+            .iter()
+            .map(|channel| {
+                self.add_port(
+                    node,
+                    direction,
+                    &format!("{}_{}", prefix, channel),
+                    Some(channel),
+                )
+            })
+            .collect()
+    }
+
+    /// Link two ports, returning the link's id
+    pub fn add_link(&self, output_port: u32, input_port: u32) -> u32 {
+        self.graph()
+            .link(output_port, input_port)
+            .expect("linking unknown ports")
+    }
+
+    /// The graph as pw-dump would print it
+    pub fn dump(&self) -> String {
+        Value::Array(self.graph().objects.clone()).to_string()
+    }
+
+    /// The graph, parsed
+    pub fn objects(&self) -> Vec<PwObject> {
+        parse_pw_dump(&self.dump()).unwrap()
+    }
+
+    /// Whether a link from `output_port` to `input_port` exists
+    pub fn has_link(&self, output_port: u32, input_port: u32) -> bool {
+        self.graph().find_link(output_port, input_port).is_some()
+    }
+
+    /// Make every command starting with `prefix` (e.g. `pw-link -d`) fail
+    pub fn fail(&self, prefix: &str) {
+        self.failing.lock().unwrap().push(prefix.to_string());
+    }
+
+    /// Graph-changing commands run so far, as shell lines
+    pub fn calls(&self) -> Vec<String> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    /// Pids of the loopback processes spawned so far
+    pub fn spawned(&self) -> Vec<u32> {
+        self.spawned.lock().unwrap().clone()
+    }
+
+    fn graph(&self) -> MutexGuard<'_, Graph> {
+        self.graph.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn record(&self, program: &str, args: &[&str]) -> bool {
+        let line = format_command(program, args);
+        let fails = self
+            .failing
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|prefix| line.starts_with(prefix.as_str()));
+        self.calls.lock().unwrap().push(line);
+        !fails
+    }
+
+    /// Add both sides of a loopback spawned with `args`
+    fn add_loopback(&self, args: &[&str]) {
+        let channels: Vec<&str> = args
+            .iter()
+            .find_map(|arg| arg.strip_prefix("--channel-map=["))
+            .map_or(vec!["FL", "FR"], |map| {
+                map.trim_end_matches(']').split(',').collect()
+            });
+        for (flag, class, direction, prefix) in [
+            ("--capture-props=", "Stream/Input/Audio", "input", "input"),
+            (
+                "--playback-props=",
+                "Stream/Output/Audio",
+                "output",
+                "output",
+            ),
+        ] {
+            let props = args.iter().find_map(|arg| arg.strip_prefix(flag)).unwrap();
+            let prop = |key: &str| {
+                let start = props.find(&format!("{}=", key))? + key.len() + 1;
+                let value = &props[start..];
+                Some(match value.strip_prefix('"') {
+                    Some(quoted) => quoted.split('"').next()?,
+                    None => value.split_whitespace().next()?,
+                })
+            };
+            let node = self.add_node(
+                prop("node.name").unwrap(),
+                class,
+                json!({
+                    "pw_splitter.split": prop("pw_splitter.split"),
+                    "pw_splitter.role": prop("pw_splitter.role"),
+                }),
+            );
+            self.add_ports(node, direction, prefix, &channels);
+        }
+    }
+
+    /// Run `command` against the graph
+    fn apply(&self, program: &str, args: &[&str]) {
+        let mut graph = self.graph();
+        match (program, args) {
+            ("pw-link", ["-d", output, input]) => {
+                let ports = (
+                    graph.resolve_port(output, "output"),
+                    graph.resolve_port(input, "input"),
+                );
+                if let (Some(output), Some(input)) = ports
+                    && let Some(link) = graph.find_link(output, input)
+                {
+                    graph.objects.retain(|obj| obj["id"] != link);
+                }
+            }
+            ("pw-link", [output, input]) => {
+                let ports = (
+                    graph.resolve_port(output, "output"),
+                    graph.resolve_port(input, "input"),
+                );
+                if let (Some(output), Some(input)) = ports {
+                    graph.link(output, input);
+                }
+            }
+            ("kill", [_, pid]) => {
+                let pid: u32 = pid.parse().unwrap();
+                if self.spawned.lock().unwrap().contains(&pid) {
+                    let _ = Command::new("kill").args(args).status();
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Drop for MockRunner {
+    /// Kill the loopbacks a test left running
+    fn drop(&mut self) {
+        for pid in self.spawned() {
+            if is_alive(pid) {
+                let _ = Command::new("kill")
+                    .args(["-KILL", &pid.to_string()])
+                    .status();
+            }
+        }
+    }
+}
+
+fn output(success: bool, stdout: String) -> Output {
+    Output {
+        status: ExitStatus::from_raw(if success { 0 } else { 1 << 8 }),
+        stdout: stdout.into_bytes(),
+        stderr: if success {
+            Vec::new()
+        } else {
+            b"mock failure".to_vec()
+        },
+    }
+}
+
+impl CommandRunner for MockRunner {
+    fn query(&self, program: &str, _args: &[&str]) -> io::Result<Output> {
+        match program {
+            "pw-dump" => Ok(output(true, self.dump())),
+            _ => Ok(output(true, String::new())),
+        }
+    }
+
+    fn run(&self, program: &str, args: &[&str]) -> io::Result<Output> {
+        if !self.record(program, args) {
+            return Ok(output(false, String::new()));
+        }
+        self.apply(program, args);
+        Ok(output(true, String::new()))
+    }
+
+    fn spawn(&self, program: &str, args: &[&str]) -> io::Result<Option<Child>> {
+        if !self.record(program, args) {
+            return Err(io::Error::other("mock failure"));
+        }
+        if program == "pw-loopback" {
+            self.add_loopback(args);
+        }
+        let child = Command::new("sh")
+            .arg0(program)
+            .args(["-c", LOOPBACK_SCRIPT, program])
+            .args(args)
+            .stdin(Stdio::null())
+            .spawn()?;
+        self.spawned.lock().unwrap().push(child.id());
+        Ok(Some(child))
+    }
+}
+
+static TEST_LOCK: Mutex<()> = Mutex::new(());
+
+/// Exclusive use of the process-wide state a test touches
+///
+/// Holds a lock so tests sharing the snapshot cache, link backend and state
+/// directory run one at a time, and points the state directory at a fresh
+/// temporary one, removed on drop.
+pub struct TestEnv {
+    state_dir: PathBuf,
+    legacy_state_dir: PathBuf,
+    _lock: MutexGuard<'static, ()>,
+}
+
+impl TestEnv {
+    pub fn new() -> Self {
+        let lock = TEST_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+        let state_dir = env::temp_dir().join(format!("pw-splitter-test-{}", std::process::id()));
+        let legacy_state_dir = state_dir.with_extension("legacy");
+        let _ = fs::remove_dir_all(&state_dir);
+        let _ = fs::remove_dir_all(&legacy_state_dir);
+        // SAFETY: every test reading the environment holds `TEST_LOCK`
+        unsafe {
+            env::set_var("PW_SPLITTER_STATE_DIR", &state_dir);
+            env::set_var("PW_SPLITTER_LEGACY_STATE_DIR", &legacy_state_dir);
+        }
+        session().invalidate();
+        set_link_backend(Some(LinkBackend::PwLink));
+        Self {
+            state_dir,
+            legacy_state_dir,
+            _lock: lock,
+        }
+    }
+
+    /// Where this test's splits from older versions live
+    pub fn legacy_state_dir(&self) -> &Path {
+        &self.legacy_state_dir
+    }
+
+    /// Make saving split `name` fail, as on a read-only or full disk
+    ///
+    /// A directory takes the place of its temporary file, so writing that
+    /// fails, while an existing state file is left as it is and a missing one
+    /// still counts as free.
+    pub fn break_state_file(&self, name: &str) {
+        fs::create_dir_all(self.state_dir.join(format!("{}.json.tmp", name))).unwrap();
+    }
+}
+
+impl Drop for TestEnv {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.state_dir);
+        let _ = fs::remove_dir_all(&self.legacy_state_dir);
+        session().invalidate();
+    }
+}
+
+/// A browser playing to the speakers, and a recorder's capture stream
+pub struct Desktop {
+    pub mock: Arc<MockRunner>,
+    pub source: u32,
+    pub source_ports: Vec<u32>,
+    pub speaker_ports: Vec<u32>,
+    pub recorder: u32,
+}
+
+impl Desktop {
+    pub fn new() -> Self {
+        let mock = Arc::new(MockRunner::default());
+        let source = mock.add_node(
+            "Firefox",
+            "Stream/Output/Audio",
+            json!({ "application.name": "Firefox", "media.name": "Video" }),
+        );
+        let source_ports = mock.add_ports(source, "output", "output", &["FL", "FR"]);
+        let speakers = mock.add_node(
+            "alsa_output.speakers",
+            "Audio/Sink",
+            json!({ "node.description": "Speakers" }),
+        );
+        let speaker_ports = mock.add_ports(speakers, "input", "playback", &["FL", "FR"]);
+        for (&output, &input) in source_ports.iter().zip(&speaker_ports) {
+            mock.add_link(output, input);
+        }
+        let recorder = mock.add_node(
+            "OBS",
+            "Stream/Input/Audio",
+            json!({ "application.name": "OBS", "media.name": "Desktop" }),
+        );
+        mock.add_ports(recorder, "input", "input", &["FL", "FR"]);
+        Self {
+            mock,
+            source,
+            source_ports,
+            speaker_ports,
+            recorder,
+        }
+    }
+
+    /// The source, as the source list shows it
+    pub fn audio_source(&self) -> AudioSource {
+        extract_audio_sources(&self.mock.objects(), SourceClasses::default())
+            .into_iter()
+            .find(|s| s.node_id == self.source)
+            .unwrap()
+    }
+
+    /// The recorder, as the destination list shows it
+    pub fn recording_dest(&self) -> RecordingDest {
+        extract_recording_dests(&self.mock.objects())
+            .into_iter()
+            .find(|d| d.node_id == self.recorder)
+            .unwrap()
+    }
+
+    /// Config splitting the source into the recorder, the local copy staying
+    /// on the speakers
+    pub fn config(&self) -> SplitConfig {
+        SplitConfig {
+            source: self.audio_source(),
+            recording_dests: vec![self.recording_dest()],
+            original_connections: find_source_connections(self.source, &self.mock.objects()),
+            local_volume: 1.0,
+            recording_volume: 1.0,
+            local_sinks: Vec::new(),
+            recording_channel_map: Default::default(),
+            local_channel_map: Default::default(),
+            tuning: Default::default(),
+            local_description: None,
+            shares_source_with: None,
+            loopback_prefix: None,
+        }
+    }
+
+    /// Set up a split with `config` through the mock, expecting a real run
+    pub fn set_up(&self, config: SplitConfig) -> crate::error::Result<SplitResult> {
+        match setup_split(config, Duration::from_secs(1), self.mock.clone())? {
+            SetupOutcome::Created(result) => Ok(*result),
+            SetupOutcome::DryRun(_) => panic!("the mock runner doesn't dry-run"),
+        }
+    }
+
+    /// Whether the source plays straight to the speakers, as before a split
+    pub fn source_on_speakers(&self) -> bool {
+        self.source_ports
+            .iter()
+            .zip(&self.speaker_ports)
+            .all(|(&output, &input)| self.mock.has_link(output, input))
+    }
+}
+
+/// Whether a process is still around (not yet reaped)
+pub fn is_alive(pid: u32) -> bool {
+    PathBuf::from(format!("/proc/{}", pid)).exists()
+}
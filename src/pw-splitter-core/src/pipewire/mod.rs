@@ -0,0 +1,20 @@
+pub mod channels;
+pub mod commands;
+pub mod linker;
+pub mod media_class;
+#[cfg(test)]
+pub(crate) mod mock;
+#[cfg(feature = "native-backend")]
+pub mod native;
+pub mod parser;
+pub mod runner;
+pub mod session;
+pub mod types;
+
+pub use channels::*;
+pub use commands::*;
+pub use linker::*;
+pub use media_class::*;
+pub use parser::*;
+pub use runner::*;
+pub use types::*;
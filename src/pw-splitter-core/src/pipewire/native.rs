@@ -0,0 +1,212 @@
+//! Native graph enumeration through libpipewire's registry.
+//!
+//! Avoids spawning `pw-dump` for every query. Registry globals only carry a
+//! few properties (not `media.name`, `factory.name` or the `pw_splitter.*`
+//! tags), so every node is bound to read the full properties from its info,
+//! as pw-dump does. Ports and links get by with what their globals publish.
+
+use crate::error::{PwSplitterError, Result};
+use crate::pipewire::types::*;
+use ::pipewire as pw;
+use pw::node::{Node, NodeListener};
+use pw::registry::GlobalObject;
+use pw::spa::utils::dict::DictRef;
+use pw::types::ObjectType;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Enumerate nodes, ports and links from the PipeWire registry
+pub fn get_pw_objects() -> Result<Vec<PwObject>> {
+    let mainloop = pw::main_loop::MainLoop::new(None).map_err(connection_error)?;
+    let context = pw::context::Context::new(&mainloop).map_err(connection_error)?;
+    // The context can be created without a server; connecting is what fails
+    let core = context
+        .connect(None)
+        .map_err(|_| PwSplitterError::PipeWireNotRunning)?;
+    let registry = Rc::new(core.get_registry().map_err(connection_error)?);
+
+    let objects = Rc::new(RefCell::new(Vec::new()));
+    // Bound nodes and their listeners, kept alive until their info arrives
+    let nodes: Rc<RefCell<Vec<(Node, NodeListener)>>> = Rc::default();
+    let _registry_listener = registry
+        .add_listener_local()
+        .global({
+            let objects = objects.clone();
+            let nodes = nodes.clone();
+            let registry = Rc::downgrade(&registry);
+            move |global| {
+                let Some(object) = to_pw_object(global) else {
+                    return;
+                };
+                if global.type_ == ObjectType::Node
+                    && let Some(registry) = registry.upgrade()
+                    && let Ok(node) = registry.bind::<Node, _>(global)
+                {
+                    let listener = node
+                        .add_listener_local()
+                        .info({
+                            let objects = objects.clone();
+                            move |info| {
+                                if let Some(props) = info.props() {
+                                    update_node_props(&mut objects.borrow_mut(), info.id(), props);
+                                }
+                            }
+                        })
+                        .register();
+                    nodes.borrow_mut().push((node, listener));
+                }
+                objects.borrow_mut().push(object);
+            }
+        })
+        .register();
+
+    // Every existing global has been announced once the server answers a
+    // sync issued after the registry was bound; a second one waits for the
+    // info of the nodes bound meanwhile
+    roundtrip(&core, &mainloop)?;
+    roundtrip(&core, &mainloop)?;
+
+    Ok(objects.take())
+}
+
+/// Run `mainloop` until the server has handled every request sent so far
+fn roundtrip(core: &pw::core::Core, mainloop: &pw::main_loop::MainLoop) -> Result<()> {
+    let pending = core.sync(0).map_err(connection_error)?;
+    let _core_listener = core
+        .add_listener_local()
+        .done({
+            let mainloop = mainloop.clone();
+            move |id, seq| {
+                if id == pw::core::PW_ID_CORE && seq == pending {
+                    mainloop.quit();
+                }
+            }
+        })
+        .register();
+
+    mainloop.run();
+    Ok(())
+}
+
+fn connection_error(e: pw::Error) -> PwSplitterError {
+    PwSplitterError::CommandFailed(format!("PipeWire connection: {}", e))
+}
+
+/// Replace the registry properties of node `id` with the full ones from its info
+fn update_node_props(objects: &mut [PwObject], id: u32, props: &DictRef) {
+    for object in objects {
+        if let PwObject::Node(node) = object
+            && node.id == id
+            && let Some(info) = &mut node.info
+        {
+            info.props = Some(node_props(id, props));
+        }
+    }
+}
+
+fn node_props(id: u32, props: &DictRef) -> NodeProps {
+    let string = |key: &str| props.get(key).map(str::to_string);
+    NodeProps {
+        node_name: string("node.name"),
+        node_description: string("node.description"),
+        application_name: string("application.name"),
+        media_name: string("media.name"),
+        media_class: string("media.class"),
+        factory_name: string("factory.name"),
+        pw_splitter_split: string("pw_splitter.split"),
+        pw_splitter_role: string("pw_splitter.role"),
+        object_id: Some(id),
+        object_serial: props.get("object.serial").and_then(|v| v.parse().ok()),
+    }
+}
+
+fn to_pw_object(global: &GlobalObject<&DictRef>) -> Option<PwObject> {
+    let props = global.props?;
+    let string = |key: &str| props.get(key).map(str::to_string);
+    let number = |key: &str| props.get(key).and_then(|v| v.parse::<u32>().ok());
+
+    match global.type_ {
+        ObjectType::Node => Some(PwObject::Node(PwNode {
+            id: global.id,
+            info: Some(NodeInfo {
+                state: None,
+                props: Some(node_props(global.id, props)),
+            }),
+        })),
+        ObjectType::Port => Some(PwObject::Port(PwPort {
+            id: global.id,
+            info: Some(PortInfo {
+                // Registry globals use "in"/"out", pw-dump uses "input"/"output"
+                direction: match props.get("port.direction") {
+                    Some("in") => Some("input".to_string()),
+                    Some("out") => Some("output".to_string()),
+                    _ => None,
+                },
+                props: Some(PortProps {
+                    node_id: number("node.id"),
+                    port_id: number("port.id"),
+                    port_name: string("port.name"),
+                    audio_channel: string("audio.channel"),
+                    object_id: Some(global.id),
+                    ..Default::default()
+                }),
+            }),
+        })),
+        ObjectType::Link => Some(PwObject::Link(PwLink {
+            id: global.id,
+            info: Some(LinkInfo {
+                output_node_id: number("link.output.node")?,
+                output_port_id: number("link.output.port")?,
+                input_node_id: number("link.input.node")?,
+                input_port_id: number("link.input.port")?,
+                state: None,
+                props: None,
+            }),
+        })),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipewire::{extract_recording_dests, find_tagged_loopback_node};
+    use pw::properties::properties;
+
+    fn node(id: u32) -> PwObject {
+        PwObject::Node(PwNode {
+            id,
+            info: Some(NodeInfo {
+                state: None,
+                props: Some(NodeProps::default()),
+            }),
+        })
+    }
+
+    #[test]
+    fn node_info_props_reach_null_sink_and_loopback_detection() {
+        let mut objects = vec![node(40), node(41)];
+        let sink = properties! {
+            "node.name" => "obs_sink",
+            "media.class" => "Audio/Sink",
+            "factory.name" => "support.null-audio-sink",
+        };
+        let loopback = properties! {
+            "node.name" => "Firefox_to_Local",
+            "media.class" => "Stream/Output/Audio",
+            "pw_splitter.split" => "Firefox_Split",
+            "pw_splitter.role" => "playback",
+        };
+
+        update_node_props(&mut objects, 40, sink.dict());
+        update_node_props(&mut objects, 41, loopback.dict());
+
+        let dests = extract_recording_dests(&objects);
+        assert_eq!(dests.len(), 1);
+        assert_eq!((dests[0].node_id, dests[0].kind), (40, DestKind::Sink));
+        assert_eq!(
+            find_tagged_loopback_node(&objects, "Firefox_to_Local", LoopbackRole::Playback),
+            Some(41)
+        );
+    }
+}
@@ -0,0 +1,551 @@
+use crate::error::{PwSplitterError, Result};
+use crate::pipewire::media_class::{MediaClass, MediaDirection};
+use crate::pipewire::types::*;
+use std::collections::{HashMap, HashSet};
+
+/// Parse the JSON output from pw-dump
+pub fn parse_pw_dump(json_str: &str) -> Result<Vec<PwObject>> {
+    serde_json::from_str(json_str).map_err(|e| PwSplitterError::ParseError(e.to_string()))
+}
+
+/// Extract all audio sources (Stream/Output/Audio) from pw-dump objects
+pub fn extract_audio_sources(objects: &[PwObject], classes: SourceClasses) -> Vec<AudioSource> {
+    let ports = extract_ports(objects);
+    objects
+        .iter()
+        .filter_map(|obj| {
+            if let PwObject::Node(node) = obj {
+                let info = node.info.as_ref()?;
+                let props = info.props.as_ref()?;
+                let class = MediaClass::parse(props.media_class.as_ref()?)?;
+
+                // Our own loopbacks are never a sensible source
+                if classes.matches(&class) && props.pw_splitter_split.is_none() {
+                    // Devices have no application name; their description reads best.
+                    // A sink is split through its monitor, so it's labeled as such,
+                    // and an unnamed virtual node as virtual rather than an app
+                    return Some(AudioSource {
+                        node_id: node.id,
+                        node_name: props.node_name.clone().unwrap_or_default(),
+                        application_name: props
+                            .application_name
+                            .clone()
+                            .or_else(|| props.node_description.clone())
+                            .unwrap_or_else(|| props.node_name.clone().unwrap_or_default()),
+                        media_name: if is_sink_class(&class) {
+                            "Monitor".to_string()
+                        } else {
+                            props.media_name.clone().unwrap_or_else(|| {
+                                if class.is_virtual() {
+                                    "Virtual"
+                                } else {
+                                    "Audio"
+                                }
+                                .to_string()
+                            })
+                        },
+                        channel_count: ports
+                            .iter()
+                            .filter(|p| {
+                                p.node_id == node.id && p.direction == PortDirection::Output
+                            })
+                            .count(),
+                        serial: props.object_serial,
+                    });
+                }
+            }
+            None
+        })
+        .collect()
+}
+
+/// Node ids of the entries of `nodes` whose application name is `name` (case-insensitive)
+pub fn nodes_named<T: ApplicationNode>(nodes: &[T], name: &str) -> Vec<u32> {
+    nodes
+        .iter()
+        .filter(|n| n.application_name().eq_ignore_ascii_case(name))
+        .map(|n| n.node_id())
+        .collect()
+}
+
+/// Index of the single entry of `nodes` whose application name is `name`
+///
+/// Several nodes can share an application name (e.g. two OBS inputs); rather
+/// than guess, that's an error listing each one's media name and node id.
+pub fn find_unique_by_name<T: ApplicationNode>(nodes: &[T], name: &str) -> Result<Option<usize>> {
+    let ids = nodes_named(nodes, name);
+    match ids.as_slice() {
+        [] => Ok(None),
+        [id] => Ok(nodes.iter().position(|n| n.node_id() == *id)),
+        _ => Err(PwSplitterError::AmbiguousName(
+            name.to_string(),
+            nodes
+                .iter()
+                .filter(|n| ids.contains(&n.node_id()))
+                .map(|n| format!("[{}] (id {})", n.media_name(), n.node_id()))
+                .collect::<Vec<_>>()
+                .join(", "),
+        )),
+    }
+}
+
+/// Factory used for null/virtual sinks (e.g. `pactl load-module module-null-sink`)
+const NULL_SINK_FACTORY: &str = "support.null-audio-sink";
+
+/// Extract all recording destinations from pw-dump objects
+///
+/// These are capture streams (Stream/Input/Audio) plus virtual null sinks
+/// (Audio/Sink) that a recorder monitors instead of capturing an app directly.
+pub fn extract_recording_dests(objects: &[PwObject]) -> Vec<RecordingDest> {
+    objects
+        .iter()
+        .filter_map(|obj| {
+            if let PwObject::Node(node) = obj {
+                let info = node.info.as_ref()?;
+                let props = info.props.as_ref()?;
+                let class = MediaClass::parse(props.media_class.as_ref()?)?;
+                let node_name = props.node_name.clone().unwrap_or_default();
+
+                if class.is_audio_stream(MediaDirection::Input)
+                    && class.subtype.is_none()
+                    && props.pw_splitter_split.is_none()
+                {
+                    return Some(RecordingDest {
+                        node_id: node.id,
+                        application_name: props
+                            .application_name
+                            .clone()
+                            .unwrap_or_else(|| node_name.clone()),
+                        media_name: props
+                            .media_name
+                            .clone()
+                            .unwrap_or_else(|| "Audio".to_string()),
+                        node_name,
+                        kind: DestKind::Stream,
+                        serial: props.object_serial,
+                    });
+                }
+
+                if is_sink_class(&class)
+                    && class.subtype.is_none()
+                    && props.factory_name.as_deref() == Some(NULL_SINK_FACTORY)
+                {
+                    return Some(RecordingDest {
+                        node_id: node.id,
+                        application_name: props
+                            .node_description
+                            .clone()
+                            .unwrap_or_else(|| node_name.clone()),
+                        media_name: "Virtual Sink".to_string(),
+                        node_name,
+                        kind: DestKind::Sink,
+                        serial: props.object_serial,
+                    });
+                }
+            }
+            None
+        })
+        .collect()
+}
+
+/// Extract all audio sinks (Audio/Sink) from pw-dump objects
+pub fn extract_audio_sinks(objects: &[PwObject]) -> Vec<AudioSink> {
+    objects
+        .iter()
+        .filter_map(|obj| {
+            if let PwObject::Node(node) = obj {
+                let info = node.info.as_ref()?;
+                let props = info.props.as_ref()?;
+                let class = MediaClass::parse(props.media_class.as_ref()?)?;
+
+                if is_sink_class(&class) && class.subtype.is_none() {
+                    return Some(AudioSink {
+                        node_id: node.id,
+                        node_name: props.node_name.clone().unwrap_or_default(),
+                        description: props
+                            .node_description
+                            .clone()
+                            .unwrap_or_else(|| props.node_name.clone().unwrap_or_default()),
+                    });
+                }
+            }
+            None
+        })
+        .collect()
+}
+
+/// Name of the user's default sink, as `wpctl status` marks it
+///
+/// Read from the session manager's `default` metadata, where WirePlumber
+/// stores the sink it currently plays to. `None` if there's no such entry,
+/// e.g. when the native backend (which doesn't list metadata) is in use.
+pub fn default_sink_name(objects: &[PwObject]) -> Option<String> {
+    objects.iter().find_map(|obj| match obj {
+        PwObject::Metadata(metadata)
+            if metadata.props.as_ref()?.metadata_name.as_deref() == Some("default") =>
+        {
+            metadata
+                .metadata
+                .iter()
+                .find(|e| e.subject == 0 && e.key == "default.audio.sink")?
+                .value
+                .get("name")?
+                .as_str()
+                .map(str::to_string)
+        }
+        _ => None,
+    })
+}
+
+/// Extract all ports from pw-dump objects
+pub fn extract_ports(objects: &[PwObject]) -> Vec<AudioPort> {
+    objects
+        .iter()
+        .filter_map(|obj| {
+            if let PwObject::Port(port) = obj {
+                let info = port.info.as_ref()?;
+                let props = info.props.as_ref()?;
+
+                let direction = match info.direction.as_deref() {
+                    Some("input") => PortDirection::Input,
+                    Some("output") => PortDirection::Output,
+                    _ => return None,
+                };
+
+                let port_name = props.port_name.clone().unwrap_or_default();
+                // Some drivers leave a sink's monitor ports (`monitor_FL`, ...)
+                // without audio.channel; the name still says which they carry
+                let channel = props.audio_channel.clone().unwrap_or_else(|| {
+                    port_name
+                        .strip_prefix("monitor_")
+                        .unwrap_or_default()
+                        .to_string()
+                });
+                return Some(AudioPort {
+                    port_id: port.id,
+                    node_id: props.node_id?,
+                    port_name,
+                    channel,
+                    direction,
+                });
+            }
+            None
+        })
+        .collect()
+}
+
+/// Extract all links from pw-dump objects
+pub fn extract_links(objects: &[PwObject]) -> Vec<AudioLink> {
+    objects
+        .iter()
+        .filter_map(|obj| {
+            if let PwObject::Link(link) = obj {
+                let info = link.info.as_ref()?;
+                return Some(AudioLink {
+                    link_id: link.id,
+                    output_node_id: info.output_node_id,
+                    output_port_id: info.output_port_id,
+                    input_node_id: info.input_node_id,
+                    input_port_id: info.input_port_id,
+                });
+            }
+            None
+        })
+        .collect()
+}
+
+/// Find all connections for a source node (what it's currently outputting to)
+pub fn find_source_connections(source_node_id: u32, objects: &[PwObject]) -> Vec<SourceConnection> {
+    let links = extract_links(objects);
+    let sinks = extract_audio_sinks(objects);
+    let recording_dests = extract_recording_dests(objects);
+
+    // Build a map of node_id -> node_name for quick lookup
+    let mut node_names: HashMap<u32, String> = HashMap::new();
+    for sink in &sinks {
+        node_names.insert(sink.node_id, sink.node_name.clone());
+    }
+    for dest in &recording_dests {
+        node_names.insert(dest.node_id, dest.node_name.clone());
+    }
+
+    // Also check all nodes for names
+    for obj in objects {
+        if let PwObject::Node(node) = obj
+            && let Some(info) = &node.info
+            && let Some(props) = &info.props
+            && let Some(name) = &props.node_name
+        {
+            node_names.insert(node.id, name.clone());
+        }
+    }
+
+    // Find links from our source
+    let source_links: Vec<_> = links
+        .iter()
+        .filter(|link| link.output_node_id == source_node_id)
+        .cloned()
+        .collect();
+
+    // Group by target node
+    let mut connections_map: HashMap<u32, Vec<AudioLink>> = HashMap::new();
+    for link in source_links {
+        connections_map
+            .entry(link.input_node_id)
+            .or_default()
+            .push(link);
+    }
+
+    connections_map
+        .into_iter()
+        .map(|(target_id, links)| SourceConnection {
+            source_node_id,
+            target_node_id: target_id,
+            target_node_name: node_names
+                .get(&target_id)
+                .cloned()
+                .unwrap_or_else(|| format!("Unknown({})", target_id)),
+            links,
+        })
+        .collect()
+}
+
+/// Find one side of a loopback spawned by pw-splitter, by its tags
+///
+/// Loopbacks spawned by older versions carry no tags and aren't found.
+pub fn find_tagged_loopback_node(
+    objects: &[PwObject],
+    loopback_name: &str,
+    role: LoopbackRole,
+) -> Option<u32> {
+    objects.iter().find_map(|obj| {
+        if let PwObject::Node(node) = obj
+            && let Some(info) = &node.info
+            && let Some(props) = &info.props
+            && props.pw_splitter_split.is_some()
+            && props.pw_splitter_role.as_deref() == Some(role.as_str())
+            && props.node_name.as_deref() == Some(loopback_name)
+        {
+            return Some(node.id);
+        }
+        None
+    })
+}
+
+/// Version of the PipeWire server, from the core object
+pub fn server_version(objects: &[PwObject]) -> Option<String> {
+    objects.iter().find_map(|obj| match obj {
+        PwObject::Core(core) => core.info.as_ref()?.version.clone(),
+        _ => None,
+    })
+}
+
+/// Names of all nodes in the graph
+pub fn node_names(objects: &[PwObject]) -> HashSet<String> {
+    objects
+        .iter()
+        .filter_map(|obj| match obj {
+            PwObject::Node(node) => node.info.as_ref()?.props.as_ref()?.node_name.clone(),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Find a node by name
+pub fn find_node_by_name(objects: &[PwObject], name: &str) -> Option<u32> {
+    find_node_by_name_and_serial(objects, name, None)
+}
+
+/// Find a node by name, and by `object.serial` too when `serial` is given
+///
+/// Node ids are recycled, so the serial is what tells the node a split was
+/// created for apart from a later one that happens to share its name.
+pub fn find_node_by_name_and_serial(
+    objects: &[PwObject],
+    name: &str,
+    serial: Option<u64>,
+) -> Option<u32> {
+    for obj in objects {
+        if let PwObject::Node(node) = obj
+            && let Some(info) = &node.info
+            && let Some(props) = &info.props
+            && props.node_name.as_deref() == Some(name)
+            && serial_matches(serial, props.object_serial)
+        {
+            return Some(node.id);
+        }
+    }
+    None
+}
+
+/// Whether a saved `object.serial` is consistent with a live node's
+///
+/// Unknown on either side (state from older versions, or a backend that
+/// doesn't report it) counts as a match, so callers fall back to names alone.
+pub fn serial_matches(saved: Option<u64>, live: Option<u64>) -> bool {
+    match (saved, live) {
+        (Some(saved), Some(live)) => saved == live,
+        _ => true,
+    }
+}
+
+/// Get a node's `object.serial` by ID
+pub fn get_node_serial(objects: &[PwObject], node_id: u32) -> Option<u64> {
+    objects.iter().find_map(|obj| match obj {
+        PwObject::Node(node) if node.id == node_id => {
+            node.info.as_ref()?.props.as_ref()?.object_serial
+        }
+        _ => None,
+    })
+}
+
+/// Get a node's `media.name` by ID
+pub fn get_node_media_name(objects: &[PwObject], node_id: u32) -> Option<String> {
+    objects.iter().find_map(|obj| match obj {
+        PwObject::Node(node) if node.id == node_id => {
+            node.info.as_ref()?.props.as_ref()?.media_name.clone()
+        }
+        _ => None,
+    })
+}
+
+/// Get node name by ID
+pub fn get_node_name(objects: &[PwObject], node_id: u32) -> Option<String> {
+    for obj in objects {
+        if let PwObject::Node(node) = obj
+            && node.id == node_id
+            && let Some(info) = &node.info
+            && let Some(props) = &info.props
+        {
+            return props.node_name.clone();
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipewire::mock::MockRunner;
+    use serde_json::json;
+
+    /// One node of each media class a source list might meet
+    fn every_class() -> MockRunner {
+        let graph = MockRunner::default();
+        for class in [
+            "Stream/Output/Audio",
+            "Stream/Output/Audio/Virtual",
+            "Audio/Source",
+            "Audio/Source/Virtual",
+            "Audio/Sink",
+            "Stream/Input/Audio",
+            "Audio/Duplex",
+            "Audio/Sinking",
+            "Video/Source",
+            "Stream/Output/Video",
+        ] {
+            graph.add_node(class, class, json!({}));
+        }
+        graph.add_node(
+            "Firefox_to_Local",
+            "Stream/Output/Audio",
+            json!({ "pw_splitter.split": "Firefox_Split" }),
+        );
+        graph
+    }
+
+    fn source_classes(classes: SourceClasses) -> Vec<(String, String)> {
+        extract_audio_sources(&every_class().objects(), classes)
+            .into_iter()
+            .map(|s| (s.node_name, s.media_name))
+            .collect()
+    }
+
+    fn pair(class: &str, media_name: &str) -> (String, String) {
+        (class.to_string(), media_name.to_string())
+    }
+
+    #[test]
+    fn sources_are_streams_by_default() {
+        assert_eq!(
+            source_classes(SourceClasses::default()),
+            [
+                pair("Stream/Output/Audio", "Audio"),
+                pair("Stream/Output/Audio/Virtual", "Virtual"),
+            ]
+        );
+    }
+
+    #[test]
+    fn sources_include_devices_on_request() {
+        let classes = SourceClasses {
+            include_devices: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            source_classes(classes),
+            [
+                pair("Stream/Output/Audio", "Audio"),
+                pair("Stream/Output/Audio/Virtual", "Virtual"),
+                pair("Audio/Source", "Audio"),
+                pair("Audio/Source/Virtual", "Virtual"),
+            ]
+        );
+    }
+
+    #[test]
+    fn sources_include_sink_monitors_on_request() {
+        let classes = SourceClasses {
+            include_sink_monitors: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            source_classes(classes),
+            [
+                pair("Stream/Output/Audio", "Audio"),
+                pair("Stream/Output/Audio/Virtual", "Virtual"),
+                pair("Audio/Sink", "Monitor"),
+            ]
+        );
+    }
+
+    fn recorder(graph: &MockRunner, app: &str, media_name: &str) -> u32 {
+        graph.add_node(
+            app,
+            "Stream/Input/Audio",
+            json!({ "application.name": app, "media.name": media_name }),
+        )
+    }
+
+    #[test]
+    fn shared_application_name_is_ambiguous() {
+        let graph = MockRunner::default();
+        let desktop = recorder(&graph, "OBS", "Desktop Audio");
+        let mic = recorder(&graph, "OBS", "Mic/Aux");
+        let dests = extract_recording_dests(&graph.objects());
+
+        assert_eq!(nodes_named(&dests, "obs"), [desktop, mic]);
+        match find_unique_by_name(&dests, "OBS") {
+            Err(PwSplitterError::AmbiguousName(name, matches)) => {
+                assert_eq!(name, "OBS");
+                assert_eq!(
+                    matches,
+                    format!("[Desktop Audio] (id {}), [Mic/Aux] (id {})", desktop, mic)
+                );
+            }
+            other => panic!("expected an ambiguous name, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn unique_application_name_resolves() {
+        let graph = MockRunner::default();
+        recorder(&graph, "OBS", "Desktop Audio");
+        let discord = recorder(&graph, "Discord", "Voice");
+        let dests = extract_recording_dests(&graph.objects());
+
+        let index = find_unique_by_name(&dests, "discord").unwrap().unwrap();
+        assert_eq!(dests[index].node_id, discord);
+        assert!(find_unique_by_name(&dests, "Audacity").unwrap().is_none());
+    }
+}
@@ -0,0 +1,263 @@
+//! Running the external PipeWire tools.
+//!
+//! Every command goes through a [`CommandRunner`], so the setup and teardown
+//! logic can run against something other than a live PipeWire session: a
+//! [`DryRunRunner`] for `--dry-run`, or in tests a mock that records
+//! invocations and answers from a fake graph.
+//!
+//! Read-only queries (such as pw-dump) are kept apart from commands that
+//! change the graph, as a dry run still needs the live graph to plan from.
+
+use std::cell::RefCell;
+use std::io::{self, Read};
+use std::process::{Child, Command, ExitStatus, Output, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Default limit on how long a query may run
+pub const DEFAULT_QUERY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Interval between checks on a running query
+const QUERY_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+static QUERY_TIMEOUT_MS: AtomicU64 = AtomicU64::new(DEFAULT_QUERY_TIMEOUT.as_millis() as u64);
+
+/// Set how long [`SystemRunner`] lets a query run before killing it
+pub fn set_query_timeout(timeout: Duration) {
+    QUERY_TIMEOUT_MS.store(timeout.as_millis() as u64, Ordering::Relaxed);
+}
+
+/// The current query timeout
+pub fn query_timeout() -> Duration {
+    Duration::from_millis(QUERY_TIMEOUT_MS.load(Ordering::Relaxed))
+}
+
+/// Runs external commands on behalf of the crate
+pub trait CommandRunner: Send + Sync {
+    /// Run a read-only query (e.g. pw-dump) and collect its output
+    ///
+    /// Fails with [`io::ErrorKind::TimedOut`] if it runs longer than
+    /// [`query_timeout`].
+    fn query(&self, program: &str, args: &[&str]) -> io::Result<Output>;
+
+    /// Run a command that changes the graph and collect its output
+    fn run(&self, program: &str, args: &[&str]) -> io::Result<Output>;
+
+    /// Start a long-running command with no stdio; `None` if nothing was started
+    fn spawn(&self, program: &str, args: &[&str]) -> io::Result<Option<Child>>;
+
+    /// Whether graph-changing commands are only recorded, not run
+    fn is_dry_run(&self) -> bool {
+        false
+    }
+}
+
+/// Runs commands for real with [`std::process::Command`]
+pub struct SystemRunner;
+
+impl CommandRunner for SystemRunner {
+    // pw-dump hangs while the PipeWire server restarts, so queries are bounded
+    fn query(&self, program: &str, args: &[&str]) -> io::Result<Output> {
+        output_with_timeout(Command::new(program).args(args), query_timeout())
+    }
+
+    fn run(&self, program: &str, args: &[&str]) -> io::Result<Output> {
+        Command::new(program).args(args).output()
+    }
+
+    fn spawn(&self, program: &str, args: &[&str]) -> io::Result<Option<Child>> {
+        Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map(Some)
+    }
+}
+
+/// Runs queries, but records graph-changing commands instead of running
+/// them, reporting success
+#[derive(Default)]
+pub struct DryRunRunner {
+    commands: Mutex<Vec<String>>,
+}
+
+impl DryRunRunner {
+    /// The commands recorded so far, as shell lines, in order; clears them
+    pub fn take_commands(&self) -> Vec<String> {
+        std::mem::take(&mut self.commands.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    fn record(&self, command: String) {
+        self.commands
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(command);
+    }
+}
+
+impl CommandRunner for DryRunRunner {
+    fn query(&self, program: &str, args: &[&str]) -> io::Result<Output> {
+        SystemRunner.query(program, args)
+    }
+
+    fn run(&self, program: &str, args: &[&str]) -> io::Result<Output> {
+        self.record(format_command(program, args));
+        Ok(Output {
+            status: ExitStatus::default(),
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+        })
+    }
+
+    fn spawn(&self, program: &str, args: &[&str]) -> io::Result<Option<Child>> {
+        self.record(format!("{} &", format_command(program, args)));
+        Ok(None)
+    }
+
+    fn is_dry_run(&self) -> bool {
+        true
+    }
+}
+
+/// Like [`Command::output`], but kills the command once `timeout` has elapsed
+fn output_with_timeout(command: &mut Command, timeout: Duration) -> io::Result<Output> {
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    // Drain the pipes while waiting, or a large output would block the child
+    let drain = |pipe: Option<Box<dyn Read + Send>>| {
+        thread::spawn(move || {
+            let mut buffer = Vec::new();
+            if let Some(mut pipe) = pipe {
+                let _ = pipe.read_to_end(&mut buffer);
+            }
+            buffer
+        })
+    };
+    let stdout = drain(child.stdout.take().map(|p| Box::new(p) as _));
+    let stderr = drain(child.stderr.take().map(|p| Box::new(p) as _));
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("no response within {:?}", timeout),
+            ));
+        }
+        thread::sleep(QUERY_POLL_INTERVAL);
+    };
+
+    Ok(Output {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    })
+}
+
+static RUNNER: OnceLock<Arc<dyn CommandRunner>> = OnceLock::new();
+
+thread_local! {
+    static SCOPED_RUNNER: RefCell<Option<Arc<dyn CommandRunner>>> = const { RefCell::new(None) };
+}
+
+/// Install the process-wide runner; only the first call has an effect
+///
+/// Must happen before the first command, which otherwise installs
+/// [`SystemRunner`].
+pub fn set_runner(runner: Arc<dyn CommandRunner>) {
+    let _ = RUNNER.set(runner);
+}
+
+/// The runner in effect on this thread: a [`with_runner`] override, else the
+/// process-wide one
+pub fn runner() -> Arc<dyn CommandRunner> {
+    SCOPED_RUNNER
+        .with_borrow(Option::clone)
+        .unwrap_or_else(|| RUNNER.get_or_init(|| Arc::new(SystemRunner)).clone())
+}
+
+/// Run `f` with every command on this thread going through `runner`
+pub fn with_runner<T>(runner: Arc<dyn CommandRunner>, f: impl FnOnce() -> T) -> T {
+    /// Restores the previous override, even if `f` panics
+    struct Restore(Option<Arc<dyn CommandRunner>>);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            SCOPED_RUNNER.set(self.0.take());
+        }
+    }
+
+    let _restore = Restore(SCOPED_RUNNER.replace(Some(runner)));
+    f()
+}
+
+/// Whether graph changes are only being recorded (`--dry-run`)
+pub fn is_dry_run() -> bool {
+    runner().is_dry_run()
+}
+
+/// Render a command as a shell line, quoting arguments where needed
+pub fn format_command(program: &str, args: &[&str]) -> String {
+    std::iter::once(program)
+        .chain(args.iter().copied())
+        .map(shell_quote)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn shell_quote(arg: &str) -> String {
+    let plain = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_.,:=/+@%[]".contains(c));
+    if plain {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipewire::mock::MockRunner;
+
+    #[test]
+    fn with_runner_overrides_only_inside() {
+        let mock = Arc::new(MockRunner::default());
+        with_runner(mock.clone(), || {
+            runner().run("pw-link", &["a:out", "b:in"]).unwrap();
+            assert!(!is_dry_run());
+        });
+        assert_eq!(mock.calls(), ["pw-link a:out b:in"]);
+
+        let dry_run = Arc::new(DryRunRunner::default());
+        with_runner(dry_run.clone(), || {
+            with_runner(mock.clone(), || assert!(!is_dry_run()));
+            assert!(is_dry_run());
+            runner().run("pw-link", &["-d", "a:out", "b:in"]).unwrap();
+        });
+        assert_eq!(dry_run.take_commands(), ["pw-link -d a:out b:in"]);
+        assert!(dry_run.take_commands().is_empty());
+    }
+
+    #[test]
+    fn format_command_quotes_when_needed() {
+        assert_eq!(
+            format_command("pw-link", &["Firefox:output_FL", "it's here"]),
+            r"pw-link Firefox:output_FL 'it'\''s here'"
+        );
+    }
+}
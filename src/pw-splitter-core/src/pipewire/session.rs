@@ -0,0 +1,65 @@
+//! Short-lived caching of graph queries.
+//!
+//! A single operation (opening the TUI, setting up a split) queries the graph
+//! several times in quick succession. [`PwSession`] serves repeated queries
+//! from a snapshot for a few hundred milliseconds, and commands that change
+//! the graph drop the snapshot.
+
+use crate::error::Result;
+use crate::pipewire::types::PwObject;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+/// How long a graph snapshot is reused
+pub const SNAPSHOT_TTL: Duration = Duration::from_millis(300);
+
+/// A cache of the most recent graph query
+pub struct PwSession {
+    snapshot: Option<(Instant, Vec<PwObject>)>,
+    ttl: Duration,
+}
+
+impl PwSession {
+    /// A session reusing snapshots for `ttl`
+    pub const fn new(ttl: Duration) -> Self {
+        Self {
+            snapshot: None,
+            ttl,
+        }
+    }
+
+    /// The cached objects if recent enough, else the result of `query`
+    pub fn objects(
+        &mut self,
+        query: impl FnOnce() -> Result<Vec<PwObject>>,
+    ) -> Result<Vec<PwObject>> {
+        match &self.snapshot {
+            Some((taken, objects)) if taken.elapsed() < self.ttl => Ok(objects.clone()),
+            _ => self.refresh(query),
+        }
+    }
+
+    /// Run `query` regardless of the cache, and cache its result
+    pub fn refresh(
+        &mut self,
+        query: impl FnOnce() -> Result<Vec<PwObject>>,
+    ) -> Result<Vec<PwObject>> {
+        self.snapshot = None;
+        let objects = query()?;
+        self.snapshot = Some((Instant::now(), objects.clone()));
+        Ok(objects)
+    }
+
+    /// Drop the cached objects, e.g. after changing the graph
+    pub fn invalidate(&mut self) {
+        self.snapshot = None;
+    }
+}
+
+static SESSION: Mutex<PwSession> = Mutex::new(PwSession::new(SNAPSHOT_TTL));
+
+/// The process-wide session behind [`get_pw_objects`](crate::pipewire::get_pw_objects)
+pub fn session() -> MutexGuard<'static, PwSession> {
+    // The cache stays consistent even if a query panicked
+    SESSION.lock().unwrap_or_else(PoisonError::into_inner)
+}
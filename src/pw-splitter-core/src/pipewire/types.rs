@@ -0,0 +1,438 @@
+#![allow(dead_code)]
+use crate::error::{PwSplitterError, Result};
+use crate::pipewire::media_class::{MediaClass, MediaDirection};
+use serde::{Deserialize, Serialize};
+
+/// Represents a PipeWire object from pw-dump
+#[derive(Debug, Deserialize, Clone)]
+#[serde(tag = "type")]
+pub enum PwObject {
+    #[serde(rename = "PipeWire:Interface:Node")]
+    Node(PwNode),
+    #[serde(rename = "PipeWire:Interface:Port")]
+    Port(PwPort),
+    #[serde(rename = "PipeWire:Interface:Link")]
+    Link(PwLink),
+    #[serde(rename = "PipeWire:Interface:Core")]
+    Core(PwCore),
+    #[serde(rename = "PipeWire:Interface:Metadata")]
+    Metadata(PwMetadata),
+    #[serde(other)]
+    Other,
+}
+
+/// The PipeWire core object, describing the server itself
+#[derive(Debug, Deserialize, Clone)]
+pub struct PwCore {
+    pub id: u32,
+    pub info: Option<CoreInfo>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct CoreInfo {
+    pub name: Option<String>,
+    pub version: Option<String>,
+}
+
+/// A metadata object, such as the session manager's `default` one that
+/// `pw-metadata` shows
+#[derive(Debug, Deserialize, Clone)]
+pub struct PwMetadata {
+    pub id: u32,
+    pub props: Option<MetadataProps>,
+    #[serde(default)]
+    pub metadata: Vec<MetadataEntry>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct MetadataProps {
+    #[serde(rename = "metadata.name")]
+    pub metadata_name: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct MetadataEntry {
+    pub subject: u32,
+    pub key: String,
+    pub value: serde_json::Value,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct PwNode {
+    pub id: u32,
+    pub info: Option<NodeInfo>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct NodeInfo {
+    pub state: Option<String>,
+    pub props: Option<NodeProps>,
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct NodeProps {
+    #[serde(rename = "node.name")]
+    pub node_name: Option<String>,
+    #[serde(rename = "node.description")]
+    pub node_description: Option<String>,
+    #[serde(rename = "application.name")]
+    pub application_name: Option<String>,
+    #[serde(rename = "media.name")]
+    pub media_name: Option<String>,
+    #[serde(rename = "media.class")]
+    pub media_class: Option<String>,
+    #[serde(rename = "factory.name")]
+    pub factory_name: Option<String>,
+    /// Split owning this node, set on loopbacks spawned by pw-splitter
+    #[serde(rename = "pw_splitter.split")]
+    pub pw_splitter_split: Option<String>,
+    /// [`LoopbackRole`] of this node, set on loopbacks spawned by pw-splitter
+    #[serde(rename = "pw_splitter.role")]
+    pub pw_splitter_role: Option<String>,
+    #[serde(rename = "object.id")]
+    pub object_id: Option<u32>,
+    /// Never reused within a PipeWire session, unlike the node id
+    #[serde(rename = "object.serial")]
+    pub object_serial: Option<u64>,
+}
+
+/// Side of a pw-loopback, as tagged by the `pw_splitter.role` property
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoopbackRole {
+    /// The capture stream, with input ports
+    Capture,
+    /// The playback stream, with output ports
+    Playback,
+}
+
+impl LoopbackRole {
+    /// Value of the `pw_splitter.role` property
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LoopbackRole::Capture => "capture",
+            LoopbackRole::Playback => "playback",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct PwPort {
+    pub id: u32,
+    pub info: Option<PortInfo>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct PortInfo {
+    pub direction: Option<String>,
+    pub props: Option<PortProps>,
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct PortProps {
+    #[serde(rename = "node.id")]
+    pub node_id: Option<u32>,
+    #[serde(rename = "port.id")]
+    pub port_id: Option<u32>,
+    #[serde(rename = "port.name")]
+    pub port_name: Option<String>,
+    #[serde(rename = "audio.channel")]
+    pub audio_channel: Option<String>,
+    #[serde(rename = "object.id")]
+    pub object_id: Option<u32>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct PwLink {
+    pub id: u32,
+    pub info: Option<LinkInfo>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct LinkInfo {
+    pub output_node_id: u32,
+    pub output_port_id: u32,
+    pub input_node_id: u32,
+    pub input_port_id: u32,
+    pub state: Option<String>,
+    pub props: Option<LinkProps>,
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct LinkProps {
+    #[serde(rename = "link.output.node")]
+    pub link_output_node: Option<u32>,
+    #[serde(rename = "link.output.port")]
+    pub link_output_port: Option<u32>,
+    #[serde(rename = "link.input.node")]
+    pub link_input_node: Option<u32>,
+    #[serde(rename = "link.input.port")]
+    pub link_input_port: Option<u32>,
+}
+
+// Simplified types for our application
+
+/// Latency and sample rate of spawned loopbacks; unset values keep
+/// pw-loopback's defaults
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LoopbackTuning {
+    /// `node.latency` as `<quantum>/<rate>`, e.g. `256/48000` for low latency
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency: Option<String>,
+    /// Sample rate of the loopback streams in Hz (`audio.rate`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate: Option<u32>,
+}
+
+impl LoopbackTuning {
+    /// Reject values pw-loopback would misread, before anything is spawned
+    pub fn validate(&self) -> Result<()> {
+        if let Some(latency) = &self.latency {
+            let valid = latency.split_once('/').is_some_and(|(quantum, rate)| {
+                quantum.parse::<u32>().is_ok_and(|q| q > 0)
+                    && rate.parse::<u32>().is_ok_and(|r| r > 0)
+            });
+            if !valid {
+                return Err(PwSplitterError::InvalidLatency(latency.clone()));
+            }
+        }
+        if self.rate == Some(0) {
+            return Err(PwSplitterError::InvalidLatency("rate 0".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Stream properties to append to both sides of a loopback
+    pub fn props(&self) -> String {
+        let mut props = String::new();
+        if let Some(latency) = &self.latency {
+            props.push_str(&format!(" node.latency={}", latency));
+        }
+        if let Some(rate) = self.rate {
+            props.push_str(&format!(" audio.rate={}", rate));
+        }
+        props
+    }
+}
+
+/// Media classes offered as split sources
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SourceClasses {
+    /// Also offer capture devices (`Audio/Source`), e.g. microphones,
+    /// bluetooth headsets and virtual sources
+    pub include_devices: bool,
+    /// Also offer sinks (`Audio/Sink`), split through their monitor ports,
+    /// to split everything playing to them
+    pub include_sink_monitors: bool,
+}
+
+impl SourceClasses {
+    /// Whether a node of `class` may be split
+    ///
+    /// Application streams match with any subclass, e.g. `Stream/Output/Audio/Virtual`.
+    pub fn matches(&self, class: &MediaClass) -> bool {
+        class.is_audio_stream(MediaDirection::Output)
+            || (self.include_devices && class.is_audio_device(MediaDirection::Output))
+            || (self.include_sink_monitors && is_sink_class(class))
+    }
+}
+
+/// Whether `class` is a sink, whose output ports are its monitor
+pub fn is_sink_class(class: &MediaClass) -> bool {
+    class.is_audio_device(MediaDirection::Input)
+}
+
+/// An audio source (application producing audio)
+#[derive(Debug, Clone)]
+pub struct AudioSource {
+    pub node_id: u32,
+    pub node_name: String,
+    pub application_name: String,
+    pub media_name: String,
+    /// Number of output ports (0 while the stream hasn't created them yet)
+    pub channel_count: usize,
+    /// `object.serial` of the node, if PipeWire reported one
+    pub serial: Option<u64>,
+}
+
+impl AudioSource {
+    pub fn display_name(&self) -> String {
+        format!("{} [{}]", self.application_name, self.media_name)
+    }
+
+    /// Common name of the source's channel layout, e.g. `stereo` or `5.1`
+    pub fn channel_label(&self) -> Option<String> {
+        match self.channel_count {
+            0 => None,
+            1 => Some("mono".to_string()),
+            2 => Some("stereo".to_string()),
+            6 => Some("5.1".to_string()),
+            8 => Some("7.1".to_string()),
+            n => Some(format!("{}ch", n)),
+        }
+    }
+
+    /// Generate a safe name for use in PipeWire object names
+    ///
+    /// Characters other than ASCII letters, digits and `_` are dropped. That can
+    /// turn different names into the same one ("Game!" and "Game?"), so a
+    /// short hash of the full name is appended whenever anything was dropped.
+    pub fn safe_name(&self) -> String {
+        let safe: String = self
+            .application_name
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric() || *c == '_')
+            .collect();
+        if safe == self.application_name {
+            safe
+        } else {
+            format!("{}_{:04x}", safe, short_hash(&self.application_name))
+        }
+    }
+}
+
+/// FNV-1a folded to 16 bits; unlike `DefaultHasher`, the same in every build
+fn short_hash(text: &str) -> u16 {
+    let hash = text.bytes().fold(0x811c_9dc5_u32, |hash, byte| {
+        (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193)
+    });
+    (hash >> 16) as u16 ^ hash as u16
+}
+
+/// A node the user may pick by application name
+pub trait ApplicationNode {
+    fn node_id(&self) -> u32;
+    fn application_name(&self) -> &str;
+    fn media_name(&self) -> &str;
+}
+
+impl ApplicationNode for AudioSource {
+    fn node_id(&self) -> u32 {
+        self.node_id
+    }
+
+    fn application_name(&self) -> &str {
+        &self.application_name
+    }
+
+    fn media_name(&self) -> &str {
+        &self.media_name
+    }
+}
+
+/// What kind of node a recording destination is
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DestKind {
+    /// An application capture stream (Stream/Input/Audio), e.g. an OBS input
+    #[default]
+    Stream,
+    /// A virtual/null sink (Audio/Sink) that the recorder monitors
+    Sink,
+}
+
+/// A recording destination (application capturing audio, or a virtual sink)
+#[derive(Debug, Clone)]
+pub struct RecordingDest {
+    pub node_id: u32,
+    pub node_name: String,
+    pub application_name: String,
+    pub media_name: String,
+    pub kind: DestKind,
+    /// `object.serial` of the node, if PipeWire reported one
+    pub serial: Option<u64>,
+}
+
+impl RecordingDest {
+    pub fn display_name(&self) -> String {
+        format!("{} [{}]", self.application_name, self.media_name)
+    }
+}
+
+impl ApplicationNode for RecordingDest {
+    fn node_id(&self) -> u32 {
+        self.node_id
+    }
+
+    fn application_name(&self) -> &str {
+        &self.application_name
+    }
+
+    fn media_name(&self) -> &str {
+        &self.media_name
+    }
+}
+
+/// An audio sink (speaker/output device)
+#[derive(Debug, Clone)]
+pub struct AudioSink {
+    pub node_id: u32,
+    pub node_name: String,
+    pub description: String,
+}
+
+/// A port on a node
+#[derive(Debug, Clone)]
+pub struct AudioPort {
+    pub port_id: u32,
+    pub node_id: u32,
+    pub port_name: String,
+    pub channel: String,
+    pub direction: PortDirection,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PortDirection {
+    Input,
+    Output,
+}
+
+/// An existing link between ports
+#[derive(Debug, Clone)]
+pub struct AudioLink {
+    pub link_id: u32,
+    pub output_node_id: u32,
+    pub output_port_id: u32,
+    pub input_node_id: u32,
+    pub input_port_id: u32,
+}
+
+/// Connection info for a source
+#[derive(Debug, Clone)]
+pub struct SourceConnection {
+    pub source_node_id: u32,
+    pub target_node_id: u32,
+    pub target_node_name: String,
+    pub links: Vec<AudioLink>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(application_name: &str) -> AudioSource {
+        AudioSource {
+            node_id: 42,
+            node_name: application_name.to_string(),
+            application_name: application_name.to_string(),
+            media_name: "Playback".to_string(),
+            channel_count: 2,
+            serial: None,
+        }
+    }
+
+    #[test]
+    fn safe_name_keeps_plain_names() {
+        assert_eq!(source("Firefox_2").safe_name(), "Firefox_2");
+    }
+
+    #[test]
+    fn safe_name_is_ascii_for_non_ascii_names() {
+        let name = source("Café Müller").safe_name();
+
+        assert!(name.starts_with("CafMller_"));
+        assert!(name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'));
+        assert_ne!(name, source("Caf Mller").safe_name());
+    }
+}
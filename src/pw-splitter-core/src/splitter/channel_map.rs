@@ -0,0 +1,222 @@
+//! Which source channels feed a loopback's inputs.
+//!
+//! The recording and local loopbacks each have their own mapping, so e.g. the
+//! recording gets the full mix while the local monitor is mono-summed.
+
+use crate::pipewire::{self, AudioPort};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// How the source's channels are linked into a loopback
+///
+/// In the config and state files this is `"matching"`, `"mono"`, or a table
+/// of loopback channel to the source channels feeding it, e.g.
+/// `{ FL = ["FL", "FR"], FR = ["FL", "FR"] }`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "ChannelMapSpec", into = "ChannelMapSpec")]
+pub enum ChannelMapping {
+    /// Each channel to the same channel, with mono fanning out to stereo
+    #[default]
+    Matching,
+    /// Every source channel summed into each loopback channel
+    Mono,
+    /// Loopback channel -> source channels; unlisted channels are matched
+    ///
+    /// Channel names compare like [`pipewire::same_channel`].
+    Custom(BTreeMap<String, Vec<String>>),
+}
+
+/// Serialized form of a [`ChannelMapping`]
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum ChannelMapSpec {
+    Preset(String),
+    Custom(BTreeMap<String, Vec<String>>),
+}
+
+impl TryFrom<ChannelMapSpec> for ChannelMapping {
+    type Error = String;
+
+    fn try_from(spec: ChannelMapSpec) -> Result<Self, Self::Error> {
+        match spec {
+            ChannelMapSpec::Preset(preset) => match preset.as_str() {
+                "matching" => Ok(ChannelMapping::Matching),
+                "mono" => Ok(ChannelMapping::Mono),
+                _ => Err(format!(
+                    "unknown channel map '{}': expected \"matching\", \"mono\" or a table",
+                    preset
+                )),
+            },
+            ChannelMapSpec::Custom(map) => Ok(ChannelMapping::Custom(map)),
+        }
+    }
+}
+
+impl From<ChannelMapping> for ChannelMapSpec {
+    fn from(mapping: ChannelMapping) -> Self {
+        match mapping {
+            ChannelMapping::Matching => ChannelMapSpec::Preset("matching".to_string()),
+            ChannelMapping::Mono => ChannelMapSpec::Preset("mono".to_string()),
+            ChannelMapping::Custom(map) => ChannelMapSpec::Custom(map),
+        }
+    }
+}
+
+impl ChannelMapping {
+    /// Pair source output ports with the loopback input ports they feed
+    pub fn pair_ports<'a>(
+        &self,
+        source_ports: &[&'a AudioPort],
+        loopback_ports: &[&'a AudioPort],
+    ) -> Vec<(&'a AudioPort, &'a AudioPort)> {
+        let matching = || pipewire::pair_ports(source_ports, loopback_ports);
+        match self {
+            ChannelMapping::Matching => matching(),
+            ChannelMapping::Mono => source_ports
+                .iter()
+                .flat_map(|&src| loopback_ports.iter().map(move |&lb| (src, lb)))
+                .collect(),
+            ChannelMapping::Custom(map) => {
+                let feeds_of = |lb: &AudioPort| {
+                    map.iter()
+                        .find(|(channel, _)| pipewire::same_channel(channel, &lb.channel))
+                        .map(|(_, feeds)| feeds)
+                };
+                let mut pairs: Vec<_> = matching()
+                    .into_iter()
+                    .filter(|(_, lb)| feeds_of(lb).is_none())
+                    .collect();
+                for &lb in loopback_ports {
+                    let Some(feeds) = feeds_of(lb) else {
+                        continue;
+                    };
+                    pairs.extend(
+                        source_ports
+                            .iter()
+                            .filter(|src| {
+                                feeds
+                                    .iter()
+                                    .any(|f| pipewire::same_channel(f, &src.channel))
+                            })
+                            .map(|&src| (src, lb)),
+                    );
+                }
+                pairs
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipewire::PortDirection;
+
+    fn ports(node_id: u32, direction: PortDirection, channels: &[&str]) -> Vec<AudioPort> {
+        channels
+            .iter()
+            .zip(1..)
+            .map(|(channel, i)| AudioPort {
+                port_id: node_id * 100 + i,
+                node_id,
+                port_name: format!("node{}:{}", node_id, channel),
+                channel: channel.to_string(),
+                direction,
+            })
+            .collect()
+    }
+
+    /// Source channel -> loopback channel for each link `mapping` makes
+    fn links(
+        mapping: &ChannelMapping,
+        source: &[&str],
+        loopback: &[&str],
+    ) -> Vec<(String, String)> {
+        let source = ports(1, PortDirection::Output, source);
+        let loopback = ports(2, PortDirection::Input, loopback);
+        let mut links: Vec<_> = mapping
+            .pair_ports(
+                &source.iter().collect::<Vec<_>>(),
+                &loopback.iter().collect::<Vec<_>>(),
+            )
+            .into_iter()
+            .map(|(src, lb)| (src.channel.clone(), lb.channel.clone()))
+            .collect();
+        links.sort();
+        links
+    }
+
+    fn pairs(links: &[(&str, &str)]) -> Vec<(String, String)> {
+        links
+            .iter()
+            .map(|(src, lb)| (src.to_string(), lb.to_string()))
+            .collect()
+    }
+
+    fn custom(table: &[(&str, &[&str])]) -> ChannelMapping {
+        ChannelMapping::Custom(
+            table
+                .iter()
+                .map(|(lb, feeds)| {
+                    (
+                        lb.to_string(),
+                        feeds.iter().map(|f| f.to_string()).collect(),
+                    )
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn matching_links_each_channel_to_itself() {
+        let links = links(&ChannelMapping::Matching, &["FL", "FR"], &["FL", "FR"]);
+
+        assert_eq!(links, pairs(&[("FL", "FL"), ("FR", "FR")]));
+    }
+
+    #[test]
+    fn custom_table_can_swap_channels() {
+        let swap = custom(&[("FL", &["FR"]), ("FR", &["FL"])]);
+
+        let links = links(&swap, &["FL", "FR"], &["FL", "FR"]);
+
+        assert_eq!(links, pairs(&[("FL", "FR"), ("FR", "FL")]));
+    }
+
+    #[test]
+    fn mono_source_fans_out_to_both_channels() {
+        let links = links(&ChannelMapping::Matching, &["MONO"], &["FL", "FR"]);
+
+        assert_eq!(links, pairs(&[("MONO", "FL"), ("MONO", "FR")]));
+    }
+
+    #[test]
+    fn mono_mapping_sums_every_channel() {
+        let links = links(&ChannelMapping::Mono, &["FL", "FR"], &["FL", "FR"]);
+
+        assert_eq!(
+            links,
+            pairs(&[("FL", "FL"), ("FL", "FR"), ("FR", "FL"), ("FR", "FR")])
+        );
+    }
+
+    #[test]
+    fn unknown_source_channel_leaves_its_loopback_channel_unfed() {
+        let mapping = custom(&[("FL", &["XX"])]);
+
+        let links = links(&mapping, &["FL", "FR"], &["FL", "FR"]);
+
+        assert_eq!(links, pairs(&[("FR", "FR")]));
+    }
+
+    #[test]
+    fn unknown_preset_is_rejected() {
+        let error = serde_json::from_str::<ChannelMapping>("\"surround\"").unwrap_err();
+
+        assert!(error.to_string().contains("unknown channel map 'surround'"));
+        assert_eq!(
+            serde_json::from_str::<ChannelMapping>("\"mono\"").unwrap(),
+            ChannelMapping::Mono
+        );
+    }
+}
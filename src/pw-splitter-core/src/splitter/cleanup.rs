@@ -0,0 +1,507 @@
+use crate::error::{PwSplitterError, Result};
+use crate::pipewire::{self, CommandRunner};
+use crate::splitter::links::{record_created_links, restore_link};
+use crate::splitter::setup::{
+    connect_loopback_to_sink, connect_source_to_loopback, source_channels, wait_for_loopback_ports,
+};
+use crate::splitter::state::{SplitState, local_loopback_desc};
+use crate::splitter::volume;
+use std::fs;
+use std::path::Path;
+use std::process::Child;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Tear down an active split and restore original connections
+///
+/// With `restore_links` false the loopbacks are killed and the state file is
+/// removed, but the links the split replaced are left alone; for when their
+/// other end is gone or has been replaced by something the source shouldn't
+/// play to. Every command goes through `runner`, normally [`pipewire::runner()`].
+pub fn teardown_split(
+    state: &SplitState,
+    restore_links: bool,
+    runner: Arc<dyn CommandRunner>,
+) -> Result<()> {
+    pipewire::with_runner(runner, || teardown(state, restore_links))
+}
+
+fn teardown(state: &SplitState, restore_links: bool) -> Result<()> {
+    tracing::info!("tearing down split {}", state.name);
+
+    // Step 1: Destroy the links the split made; a loopback killed abruptly
+    // could otherwise leave some behind
+    for link in &state.created_links {
+        if let Err(e) = pipewire::destroy_link(&link.output_port, &link.input_port) {
+            tracing::warn!(
+                "could not destroy {} -> {}: {}",
+                link.output_port,
+                link.input_port,
+                e
+            );
+        }
+    }
+
+    // Step 2: Kill loopback processes
+    for recording in &state.recordings {
+        kill_loopback(recording.pid, &recording.loopback_name);
+    }
+    kill_loopback(state.loopback_to_local_pid, &state.local_loopback_name);
+
+    // Step 3: Restore original links
+    let original_links = if restore_links {
+        state.original_links.as_slice()
+    } else {
+        &[]
+    };
+    for link in original_links {
+        // The original target may be gone (e.g. unplugged); restore what we can
+        if let Err(e) = restore_link(link) {
+            tracing::warn!(
+                "could not restore {} -> {}: {}",
+                link.output_port,
+                link.input_port,
+                e
+            );
+        }
+    }
+
+    // Step 4: Delete state file
+    if !pipewire::is_dry_run() {
+        state.delete()?;
+    }
+
+    Ok(())
+}
+
+/// Stop a split by name, restoring its original links if `restore_links`
+pub fn stop_split(name: &str, restore_links: bool) -> Result<()> {
+    let state = SplitState::load(name)?;
+    teardown_split(&state, restore_links, pipewire::runner())
+}
+
+/// Outcome of [`stop_all_splits`]
+#[derive(Debug, Default)]
+pub struct StopAllReport {
+    /// Names of the splits that were torn down
+    pub stopped: Vec<String>,
+    /// Splits whose teardown failed, with the reason
+    pub failed: Vec<(String, PwSplitterError)>,
+}
+
+/// Stop all active splits accepted by `predicate`
+///
+/// Pass `|_| true` to stop everything; filters compose by combining them
+/// into a single predicate. A failing split doesn't stop the others.
+pub fn stop_all_splits(
+    predicate: impl Fn(&SplitState) -> bool,
+    restore_links: bool,
+) -> Result<StopAllReport> {
+    let states = SplitState::list_all()?;
+    let mut report = StopAllReport::default();
+
+    for state in states.into_iter().filter(|s| predicate(s)) {
+        match teardown_split(&state, restore_links, pipewire::runner()) {
+            Ok(()) => report.stopped.push(state.name),
+            Err(e) => report.failed.push((state.name, e)),
+        }
+    }
+
+    Ok(report)
+}
+
+/// Whether none of a split's loopbacks are running any more
+///
+/// Such splits are left behind by crashes and reboots; nothing routes through
+/// them, but their state file (and the source's original links) remain.
+pub fn is_orphaned(state: &SplitState) -> bool {
+    let (recording_running, local_running) = check_loopbacks_running(state);
+    !local_running && !recording_running.contains(&true)
+}
+
+/// Outcome of [`prune_splits`]
+#[derive(Debug, Default)]
+pub struct PruneReport {
+    /// Orphaned splits that were pruned, or in a dry run would be
+    pub pruned: Vec<SplitState>,
+    /// Orphans whose teardown failed, with the reason
+    pub failed: Vec<(String, PwSplitterError)>,
+}
+
+/// Restore and delete every orphaned split
+///
+/// With `older_than`, only orphans at least that old are pruned, so a split
+/// that is still being set up (or was just restarted) is left alone. With
+/// `dry_run`, nothing is changed and the splits that would be pruned are
+/// reported. A failing split doesn't stop the others.
+pub fn prune_splits(dry_run: bool, older_than: Option<Duration>) -> Result<PruneReport> {
+    let orphans = SplitState::list_all()?
+        .into_iter()
+        .filter(|s| older_than.is_none_or(|min_age| s.age() >= min_age))
+        .filter(is_orphaned);
+
+    let mut report = PruneReport::default();
+    for state in orphans {
+        if dry_run {
+            report.pruned.push(state);
+            continue;
+        }
+        // No loopback to kill; this restores the original links, if their
+        // ports still exist, and removes the state file
+        match teardown_split(&state, true, pipewire::runner()) {
+            Ok(()) => report.pruned.push(state),
+            Err(e) => report.failed.push((state.name, e)),
+        }
+    }
+
+    Ok(report)
+}
+
+/// Add tags to an existing split, ignoring ones it already has
+pub fn tag_split(name: &str, tags: &[String]) -> Result<()> {
+    let mut state = SplitState::load(name)?;
+    for tag in tags {
+        if !state.has_tag(tag) {
+            state.tags.push(tag.clone());
+        }
+    }
+    state.save()
+}
+
+/// Give a split a new name, returning the renamed state
+///
+/// Only the state file changes; the loopback node names stay the same, so
+/// nothing needs relinking.
+pub fn rename_split(old_name: &str, new_name: &str) -> Result<SplitState> {
+    SplitState::validate_name(new_name)?;
+    let old = SplitState::load(old_name)?;
+    if SplitState::exists(new_name) {
+        return Err(PwSplitterError::NameTaken(new_name.to_string()));
+    }
+
+    let mut state = old.clone();
+    state.name = new_name.to_string();
+    // Write the new file first, so a failure can't lose the split
+    state.save()?;
+    old.delete()?;
+    Ok(state)
+}
+
+/// Kill and respawn every loopback of a split, keeping its routing and saved links
+///
+/// Returns the updated state, carrying the new PIDs.
+pub fn restart_split(name: &str) -> Result<SplitState> {
+    let mut state = SplitState::load(name)?;
+    tracing::info!("restarting split {}", state.name);
+
+    let loopbacks: Vec<(u32, String)> = state
+        .recordings
+        .iter()
+        .map(|r| (r.pid, r.loopback_name.clone()))
+        .chain([(
+            state.loopback_to_local_pid,
+            state.local_loopback_name.clone(),
+        )])
+        .collect();
+    for (pid, name) in &loopbacks {
+        kill_loopback(*pid, name);
+    }
+    // New loopbacks reuse the node names, so the old nodes must be gone first
+    for (pid, name) in &loopbacks {
+        wait_for_exit(*pid, name);
+    }
+
+    for index in 0..state.recordings.len() {
+        restart_loopback_to_recording(&mut state, index)?;
+    }
+    restart_loopback_to_local(&mut state)?;
+
+    Ok(state)
+}
+
+/// Kill a loopback process by PID, if the PID still belongs to that loopback
+///
+/// After a reboot or PID reuse the saved PID may name an unrelated process,
+/// which is left alone.
+fn kill_loopback(pid: u32, loopback_name: &str) {
+    if !is_process_running(pid) {
+        return;
+    }
+    if !is_loopback_running(pid, loopback_name) {
+        tracing::warn!(
+            "pid {} is not the pw-loopback for {}, not killing it",
+            pid,
+            loopback_name
+        );
+        return;
+    }
+
+    tracing::debug!("killing {} (pid {})", loopback_name, pid);
+    let _ = pipewire::runner().run("kill", &["-TERM", &pid.to_string()]);
+    pipewire::invalidate_pw_objects();
+}
+
+/// Check if loopback processes are still running
+///
+/// Returns one flag per entry of `state.recordings`, then the local loopback's.
+pub fn check_loopbacks_running(state: &SplitState) -> (Vec<bool>, bool) {
+    let recording_running = state
+        .recordings
+        .iter()
+        .map(|r| is_loopback_running(r.pid, &r.loopback_name))
+        .collect();
+    let local_running =
+        is_loopback_running(state.loopback_to_local_pid, &state.local_loopback_name);
+    (recording_running, local_running)
+}
+
+/// Wait (bounded by the port timeout) for a killed process to exit
+fn wait_for_exit(pid: u32, loopback_name: &str) {
+    let deadline = Instant::now() + pipewire::DEFAULT_PORT_TIMEOUT;
+    while is_loopback_running(pid, loopback_name) && Instant::now() < deadline {
+        thread::sleep(pipewire::PORT_POLL_INTERVAL);
+    }
+}
+
+/// Check if a process is running
+fn is_process_running(pid: u32) -> bool {
+    // Check /proc/<pid> exists
+    std::path::Path::new(&format!("/proc/{}", pid)).exists()
+}
+
+/// Check if `pid` is a running pw-loopback for `loopback_name`
+fn is_loopback_running(pid: u32, loopback_name: &str) -> bool {
+    fs::read(format!("/proc/{}/cmdline", pid))
+        .is_ok_and(|cmdline| cmdline_matches_loopback(&cmdline, loopback_name))
+}
+
+/// Check whether a `/proc/<pid>/cmdline` is pw-loopback spawned for `loopback_name`
+///
+/// The name must appear as a whole `node.name=` property, so `X_to_Recording`
+/// doesn't match the loopback `X_to_Recording_2`.
+fn cmdline_matches_loopback(cmdline: &[u8], loopback_name: &str) -> bool {
+    let cmdline = String::from_utf8_lossy(cmdline);
+    let mut args = cmdline.split('\0').filter(|a| !a.is_empty());
+
+    let is_pw_loopback = args
+        .next()
+        .is_some_and(|program| Path::new(program).file_name() == Some("pw-loopback".as_ref()));
+    let node_name = format!("node.name={}", loopback_name);
+
+    // Props arrive as `--capture-props=node.name=X node.description=...`
+    is_pw_loopback
+        && args.any(|arg| {
+            let props = arg.split_once("-props=").map_or(arg, |(_, props)| props);
+            props.split_whitespace().any(|prop| prop == node_name)
+        })
+}
+
+/// Restart the crashed recording loopback at `index` in `state.recordings`
+///
+/// Returns the new process; dropping the handle leaves it running.
+pub fn restart_loopback_to_recording(state: &mut SplitState, index: usize) -> Result<Child> {
+    let recording = state.recordings.get_mut(index).ok_or_else(|| {
+        PwSplitterError::StateFileError(format!("no recording loopback #{}", index))
+    })?;
+    let loopback_desc = format!(
+        "{} -> {}",
+        state.source_application_name, recording.dest_application_name
+    );
+
+    let child = pipewire::spawn_loopback_no_target(
+        &state.name,
+        &recording.loopback_name,
+        &loopback_desc,
+        &source_channels(state.source_node_id)?,
+        &state.tuning,
+    )?
+    .ok_or_else(not_in_dry_run)?;
+
+    recording.pid = child.id();
+    let loopback_name = recording.loopback_name.clone();
+    let dest_node_id = recording.dest_node_id;
+
+    finish_restart(child, || {
+        wait_for_loopback_ports(&loopback_name, pipewire::DEFAULT_PORT_TIMEOUT)?;
+
+        // The old node's links died with it: source -> capture, playback -> destination
+        connect_source_to_loopback(
+            state.source_node_id,
+            &loopback_name,
+            &state.recording_channel_map,
+        )?;
+        pipewire::connect_loopback_to_recording_dest(&loopback_name, dest_node_id)?;
+        volume::reapply_recording_volume(&loopback_name, state.recording_volume)?;
+
+        record_created_links(state);
+        state.save()
+    })
+}
+
+/// Restart the local loopback process
+///
+/// Returns the new process; dropping the handle leaves it running.
+pub fn restart_loopback_to_local(state: &mut SplitState) -> Result<Child> {
+    let loopback_desc = local_loopback_desc(
+        state.local_description.as_deref(),
+        &state.source_application_name,
+    );
+
+    let child = pipewire::spawn_loopback_no_target(
+        &state.name,
+        &state.local_loopback_name,
+        &loopback_desc,
+        &source_channels(state.source_node_id)?,
+        &state.tuning,
+    )?
+    .ok_or_else(not_in_dry_run)?;
+
+    state.loopback_to_local_pid = child.id();
+
+    finish_restart(child, || {
+        wait_for_loopback_ports(&state.local_loopback_name, pipewire::DEFAULT_PORT_TIMEOUT)?;
+
+        connect_source_to_loopback(
+            state.source_node_id,
+            &state.local_loopback_name,
+            &state.local_channel_map,
+        )?;
+        for sink_name in state.local_output_names() {
+            connect_loopback_to_sink(&state.local_loopback_name, sink_name)?;
+        }
+
+        // A fresh node starts at 100%; restore the user's level and balance to
+        // avoid a loudness jump
+        volume::reapply_local_volume(state)?;
+
+        record_created_links(state);
+        state.save()
+    })
+}
+
+/// A restart needs the new loopback running; a dry run spawns nothing
+fn not_in_dry_run() -> PwSplitterError {
+    PwSplitterError::LoopbackSpawnFailed("loopbacks can't be restarted in a dry run".to_string())
+}
+
+/// Wire up a restarted loopback with `finish`, killing and reaping it if that fails
+///
+/// A half-wired loopback left running would be mistaken for a working one,
+/// and nothing would ever wait on it.
+fn finish_restart(mut child: Child, finish: impl FnOnce() -> Result<()>) -> Result<Child> {
+    match finish() {
+        Ok(()) => Ok(child),
+        Err(e) => {
+            let _ = child.kill();
+            let _ = child.wait();
+            Err(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipewire::mock::{Desktop, TestEnv, is_alive};
+
+    #[test]
+    fn teardown_restores_original_links() {
+        let _env = TestEnv::new();
+        let desktop = Desktop::new();
+        let mut result = desktop.set_up(desktop.config()).unwrap();
+
+        teardown_split(&result.state, true, desktop.mock.clone()).unwrap();
+
+        let calls = desktop.mock.calls();
+        assert!(calls.contains(&format!(
+            "kill -TERM {}",
+            result.state.loopback_to_local_pid
+        )));
+        assert!(calls.contains(
+            &"pw-link -d Firefox_to_Local:output_FL alsa_output.speakers:playback_FL".to_string()
+        ));
+        assert!(desktop.source_on_speakers());
+        assert!(!SplitState::exists(&result.state.name));
+
+        result.loopback_to_local.wait().unwrap();
+        for child in &mut result.loopbacks_to_recording {
+            child.wait().unwrap();
+        }
+        assert!(!is_alive(result.state.loopback_to_local_pid));
+    }
+
+    #[test]
+    fn teardown_without_restore_leaves_source_unlinked() {
+        let _env = TestEnv::new();
+        let desktop = Desktop::new();
+        let result = desktop.set_up(desktop.config()).unwrap();
+
+        teardown_split(&result.state, false, desktop.mock.clone()).unwrap();
+
+        assert!(!desktop.source_on_speakers());
+        assert!(!SplitState::exists(&result.state.name));
+    }
+
+    #[test]
+    fn failed_restart_kills_the_new_loopback() {
+        let _env = TestEnv::new();
+        let desktop = Desktop::new();
+        let mut result = desktop.set_up(desktop.config()).unwrap();
+        result.loopback_to_local.kill().unwrap();
+        result.loopback_to_local.wait().unwrap();
+        desktop
+            .mock
+            .fail("pw-link Firefox:output_FL Firefox_to_Local");
+
+        let error = pipewire::with_runner(desktop.mock.clone(), || {
+            restart_loopback_to_local(&mut result.state)
+        })
+        .err()
+        .unwrap();
+
+        assert!(matches!(error, PwSplitterError::LinkCreationFailed(_)));
+        let restarted = *desktop.mock.spawned().last().unwrap();
+        assert_eq!(result.state.loopback_to_local_pid, restarted);
+        assert!(!is_alive(restarted));
+    }
+
+    fn cmdline(args: &[&str]) -> Vec<u8> {
+        args.iter()
+            .flat_map(|arg| [arg.as_bytes(), b"\0"].concat())
+            .collect()
+    }
+
+    #[test]
+    fn cmdline_matches_only_the_named_loopback() {
+        let loopback = cmdline(&[
+            "/usr/bin/pw-loopback",
+            "--channels=2",
+            "--capture-props=node.name=Firefox_to_Recording node.description=\"Firefox input\"",
+            "--playback-props=node.name=Firefox_to_Recording node.autoconnect=false",
+        ]);
+
+        assert!(cmdline_matches_loopback(&loopback, "Firefox_to_Recording"));
+        assert!(!cmdline_matches_loopback(
+            &loopback,
+            "Firefox_to_Recording_2"
+        ));
+        assert!(!cmdline_matches_loopback(&loopback, "Firefox"));
+    }
+
+    #[test]
+    fn cmdline_of_another_program_never_matches() {
+        let reused_pid = cmdline(&["/usr/bin/vim", "node.name=Firefox_to_Recording"]);
+        let wrapped = cmdline(&[
+            "/usr/bin/pw-loopback-helper",
+            "--capture-props=node.name=Firefox_to_Recording",
+        ]);
+
+        assert!(!cmdline_matches_loopback(
+            &reused_pid,
+            "Firefox_to_Recording"
+        ));
+        assert!(!cmdline_matches_loopback(&wrapped, "Firefox_to_Recording"));
+        assert!(!cmdline_matches_loopback(b"", "Firefox_to_Recording"));
+    }
+}
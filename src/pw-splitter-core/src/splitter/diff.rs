@@ -0,0 +1,141 @@
+//! The links a split will cut and make, previewed before it's created.
+//!
+//! Cut links are the source's current links, port by port. New links are
+//! listed per node pair, since which ports get linked depends on the channel
+//! mappings and on ports the loopbacks only create once they're spawned.
+
+use crate::pipewire::{self, AudioSink, AudioSource, PwObject, RecordingDest, SourceConnection};
+use crate::splitter::setup::primary_output;
+use crate::splitter::stepped::{recording_loopback_names, unique_loopback_prefix};
+
+/// Before/after view of the links a split changes
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LinkDiff {
+    /// Links from the source that will be removed, as `node:port -> node:port`
+    pub cut: Vec<String>,
+    /// Links that will be created, as `node -> node`
+    pub made: Vec<String>,
+    /// Prefix of the loopbacks named in `made`; pass it on in
+    /// [`SplitConfig::loopback_prefix`] so setup names them the same
+    ///
+    /// [`SplitConfig::loopback_prefix`]: crate::splitter::SplitConfig::loopback_prefix
+    pub loopback_prefix: Option<String>,
+}
+
+/// Work out what splitting `source` into `dests` would change in the live graph
+///
+/// `connections` are the source's current connections; `local_sinks` empty
+/// means the local copy goes where setup would send it, the primary one.
+pub fn plan_link_diff(
+    source: &AudioSource,
+    connections: &[SourceConnection],
+    dests: &[RecordingDest],
+    local_sinks: &[AudioSink],
+    objects: &[PwObject],
+) -> LinkDiff {
+    let ports = pipewire::extract_ports(objects);
+    let port_label = |node_id: u32, port_id: u32| {
+        let node = pipewire::get_node_name(objects, node_id).unwrap_or_else(|| node_id.to_string());
+        let port = ports
+            .iter()
+            .find(|p| p.port_id == port_id)
+            .map(|p| p.port_name.clone())
+            .unwrap_or_else(|| port_id.to_string());
+        format!("{}:{}", node, port)
+    };
+
+    let cut = pipewire::extract_links(objects)
+        .into_iter()
+        .filter(|l| {
+            l.output_node_id == source.node_id
+                && connections
+                    .iter()
+                    .any(|c| c.target_node_id == l.input_node_id)
+        })
+        .map(|l| {
+            format!(
+                "{} -> {}",
+                port_label(l.output_node_id, l.output_port_id),
+                port_label(l.input_node_id, l.input_port_id)
+            )
+        })
+        .collect();
+
+    let safe_name = unique_loopback_prefix(&source.safe_name(), dests.len(), objects);
+    let local_loopback_name = format!("{}_to_Local", safe_name);
+    let local_outputs: Vec<String> = if local_sinks.is_empty() {
+        let dest_ids: Vec<u32> = dests.iter().map(|d| d.node_id).collect();
+        primary_output(connections, &dest_ids, objects)
+            .map(|c| c.target_node_name.clone())
+            .into_iter()
+            .collect()
+    } else {
+        local_sinks.iter().map(|s| s.node_name.clone()).collect()
+    };
+
+    let mut made = Vec::new();
+    for (dest, name) in dests
+        .iter()
+        .zip(recording_loopback_names(&safe_name, dests.len()))
+    {
+        made.push(format!("{} -> {}", source.node_name, name));
+        made.push(format!("{} -> {}", name, dest.display_name()));
+    }
+    made.push(format!("{} -> {}", source.node_name, local_loopback_name));
+    for output in local_outputs {
+        made.push(format!("{} -> {}", local_loopback_name, output));
+    }
+
+    LinkDiff {
+        cut,
+        made,
+        loopback_prefix: Some(safe_name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipewire::mock::{Desktop, TestEnv};
+    use crate::splitter::{SetupProgress, setup_split_stepped};
+    use serde_json::json;
+
+    #[test]
+    fn setup_names_loopbacks_as_previewed() {
+        let _env = TestEnv::new();
+        let desktop = Desktop::new();
+        // Not a split's, so only the graph knows the name is taken
+        desktop
+            .mock
+            .add_node("Firefox_to_Local", "Stream/Output/Audio", json!({}));
+        let objects = desktop.mock.objects();
+        let mut config = desktop.config();
+
+        let diff = plan_link_diff(
+            &config.source,
+            &config.original_connections,
+            &config.recording_dests,
+            &[],
+            &objects,
+        );
+        assert_eq!(diff.loopback_prefix.as_deref(), Some("Firefox_1"));
+        assert!(
+            diff.made
+                .contains(&"Firefox -> Firefox_1_to_Recording".to_string())
+        );
+
+        config.loopback_prefix = diff.loopback_prefix;
+        let mut setup = setup_split_stepped(config).with_runner(desktop.mock.clone());
+        let result = loop {
+            if let SetupProgress::Complete(result) = setup.advance().unwrap() {
+                break result;
+            }
+        };
+        assert_eq!(result.state.name, "Firefox_1_Split");
+        assert_eq!(
+            result.state.recordings[0].loopback_name,
+            "Firefox_1_to_Recording"
+        );
+        assert_eq!(result.state.local_loopback_name, "Firefox_1_to_Local");
+    }
+}
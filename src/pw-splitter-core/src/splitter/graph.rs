@@ -0,0 +1,268 @@
+//! Link-graph analysis used to catch audio feedback loops before wiring a split.
+//!
+//! The graph is modelled at node level: a link from any port of node A to any
+//! port of node B is an edge `A -> B`, and audio entering a node is assumed to
+//! leave through its output links (true for sinks and their monitors).
+
+use crate::error::{PwSplitterError, Result};
+use crate::pipewire::{self, MediaClass, MediaDirection, PwObject};
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, VecDeque};
+
+/// A directed edge between two nodes
+pub type NodeEdge = (u32, u32);
+
+/// Placeholder ids for loopback nodes that haven't been spawned yet
+///
+/// Recording loopback `i` uses `PLANNED_RECORDING_BASE - 2 * i` (capture) and
+/// the id just below it (playback).
+const PLANNED_LOCAL_CAPTURE: u32 = u32::MAX;
+const PLANNED_LOCAL_PLAYBACK: u32 = u32::MAX - 1;
+const PLANNED_RECORDING_BASE: u32 = u32::MAX - 2;
+
+/// Lowest id treated as a placeholder (allows for 1024 recording loopbacks)
+const PLANNED_MIN: u32 = PLANNED_RECORDING_BASE - 2048;
+
+/// Find a cycle through `node`, returned as the node ids along it
+///
+/// The first and last entries of the returned path are `node`. The search is
+/// breadth-first, so the shortest such cycle is reported.
+pub fn find_cycle_through(node: u32, edges: &[NodeEdge]) -> Option<Vec<u32>> {
+    let mut adjacency: HashMap<u32, Vec<u32>> = HashMap::new();
+    for &(from, to) in edges {
+        adjacency.entry(from).or_default().push(to);
+    }
+
+    let mut parent: HashMap<u32, u32> = HashMap::new();
+    let mut queue = VecDeque::from([node]);
+    while let Some(current) = queue.pop_front() {
+        for &next in adjacency.get(&current).into_iter().flatten() {
+            if next == node {
+                let mut cycle = vec![node];
+                let mut at = current;
+                while at != node {
+                    cycle.push(at);
+                    at = parent[&at];
+                }
+                cycle.push(node);
+                cycle.reverse();
+                return Some(cycle);
+            }
+            if let Entry::Vacant(entry) = parent.entry(next) {
+                entry.insert(current);
+                queue.push_back(next);
+            }
+        }
+    }
+    None
+}
+
+/// Nodes involved in a split that is about to be created
+pub struct PlannedSplit<'a> {
+    pub source_node_id: u32,
+    /// Targets the source will be disconnected from
+    pub disconnected_target_ids: &'a [u32],
+    pub recording_dest_node_ids: &'a [u32],
+    pub local_sink_node_ids: &'a [u32],
+}
+
+/// Refuse a planned split whose routing would feed audio back into itself
+///
+/// Only cycles through the split's own loopbacks count: a feedback path the
+/// graph already has isn't made by the split, and mustn't block it.
+pub fn check_for_feedback(objects: &[PwObject], plan: &PlannedSplit) -> Result<()> {
+    let mut planned_captures = vec![PLANNED_LOCAL_CAPTURE];
+    let mut edges: Vec<NodeEdge> = pipewire::extract_links(objects)
+        .iter()
+        .filter(|l| {
+            !(l.output_node_id == plan.source_node_id
+                && plan.disconnected_target_ids.contains(&l.input_node_id))
+        })
+        .map(|l| (l.output_node_id, l.input_node_id))
+        .collect();
+
+    edges.extend(loopback_internal_edges(objects));
+    for (i, &dest_id) in plan.recording_dest_node_ids.iter().enumerate() {
+        let capture = PLANNED_RECORDING_BASE - 2 * i as u32;
+        let playback = capture - 1;
+        planned_captures.push(capture);
+        edges.extend([
+            (plan.source_node_id, capture),
+            (capture, playback),
+            (playback, dest_id),
+        ]);
+    }
+    edges.extend([
+        (plan.source_node_id, PLANNED_LOCAL_CAPTURE),
+        (PLANNED_LOCAL_CAPTURE, PLANNED_LOCAL_PLAYBACK),
+    ]);
+    edges.extend(
+        plan.local_sink_node_ids
+            .iter()
+            .map(|&sink_id| (PLANNED_LOCAL_PLAYBACK, sink_id)),
+    );
+
+    // Every new link touches a loopback, and a loopback is only entered
+    // through its capture side
+    match planned_captures
+        .iter()
+        .find_map(|&capture| find_cycle_through(capture, &edges))
+    {
+        Some(cycle) => Err(PwSplitterError::FeedbackLoop(
+            cycle
+                .iter()
+                .map(|&id| describe_node(objects, id))
+                .collect::<Vec<_>>()
+                .join(" -> "),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Loopback-style nodes (e.g. pw-loopback) appear as a capture stream and a
+/// playback stream sharing a `node.name`; audio flows between them internally
+/// without a link, so add that hop explicitly.
+fn loopback_internal_edges(objects: &[PwObject]) -> Vec<NodeEdge> {
+    let mut inputs: HashMap<&str, Vec<u32>> = HashMap::new();
+    let mut outputs: HashMap<&str, Vec<u32>> = HashMap::new();
+
+    for obj in objects {
+        if let PwObject::Node(node) = obj
+            && let Some(info) = &node.info
+            && let Some(props) = &info.props
+            && let Some(name) = props.node_name.as_deref()
+        {
+            let class = props.media_class.as_deref().and_then(MediaClass::parse);
+            if class.is_some_and(|c| c.is_audio_stream(MediaDirection::Input)) {
+                inputs.entry(name).or_default().push(node.id);
+            } else if class.is_some_and(|c| c.is_audio_stream(MediaDirection::Output)) {
+                outputs.entry(name).or_default().push(node.id);
+            }
+        }
+    }
+
+    let mut edges = Vec::new();
+    for (name, capture_ids) in &inputs {
+        for &playback_id in outputs.get(name).into_iter().flatten() {
+            edges.extend(
+                capture_ids
+                    .iter()
+                    .map(|&capture_id| (capture_id, playback_id)),
+            );
+        }
+    }
+    edges
+}
+
+fn describe_node(objects: &[PwObject], node_id: u32) -> String {
+    match node_id {
+        PLANNED_LOCAL_CAPTURE | PLANNED_LOCAL_PLAYBACK => "[To Local]".to_string(),
+        PLANNED_MIN..=PLANNED_RECORDING_BASE => "[To Recording]".to_string(),
+        _ => pipewire::get_node_name(objects, node_id).unwrap_or_else(|| node_id.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipewire::mock::MockRunner;
+    use serde_json::json;
+
+    #[test]
+    fn simple_cycle_is_found() {
+        let edges = [(1, 2), (2, 3), (3, 1), (3, 4)];
+
+        assert_eq!(find_cycle_through(1, &edges), Some(vec![1, 2, 3, 1]));
+        assert_eq!(find_cycle_through(2, &edges), Some(vec![2, 3, 1, 2]));
+        assert_eq!(find_cycle_through(4, &edges), None);
+    }
+
+    #[test]
+    fn dag_has_no_cycle() {
+        let edges = [(1, 2), (1, 3), (2, 3), (3, 4)];
+
+        assert!((1..=4).all(|node| find_cycle_through(node, &edges).is_none()));
+    }
+
+    #[test]
+    fn cycle_elsewhere_is_not_through_node() {
+        assert_eq!(find_cycle_through(1, &[(1, 2), (2, 3), (3, 2)]), None);
+    }
+
+    /// A sink, and a pw-loopback-style pair named `Echo` playing into it
+    struct EchoSink {
+        mock: MockRunner,
+        sink: u32,
+        echo_capture: u32,
+    }
+
+    impl EchoSink {
+        fn new() -> Self {
+            let mock = MockRunner::default();
+            let sink = mock.add_node("speakers", "Audio/Sink", json!({}));
+            let sink_in = mock.add_port(sink, "input", "playback_FL", Some("FL"));
+            let echo_capture = mock.add_node("Echo", "Stream/Input/Audio", json!({}));
+            let echo_playback = mock.add_node("Echo", "Stream/Output/Audio", json!({}));
+            let echo_out = mock.add_port(echo_playback, "output", "output_FL", Some("FL"));
+            mock.add_link(echo_out, sink_in);
+            Self {
+                mock,
+                sink,
+                echo_capture,
+            }
+        }
+    }
+
+    #[test]
+    fn recording_into_a_loopback_back_to_the_source_is_feedback() {
+        let graph = EchoSink::new();
+
+        // Splitting the sink's monitor into Echo, which plays into the sink
+        let error = check_for_feedback(
+            &graph.mock.objects(),
+            &PlannedSplit {
+                source_node_id: graph.sink,
+                disconnected_target_ids: &[],
+                recording_dest_node_ids: &[graph.echo_capture],
+                local_sink_node_ids: &[],
+            },
+        )
+        .unwrap_err();
+
+        assert!(matches!(
+            error,
+            PwSplitterError::FeedbackLoop(cycle) if cycle
+                == "[To Recording] -> [To Recording] -> Echo -> Echo -> speakers -> [To Recording]"
+        ));
+    }
+
+    #[test]
+    fn existing_feedback_path_does_not_block_a_split() {
+        let graph = EchoSink::new();
+        // The sink's monitor already feeds Echo, which plays back into it
+        let monitor = graph
+            .mock
+            .add_port(graph.sink, "output", "monitor_FL", Some("FL"));
+        let echo_in = graph
+            .mock
+            .add_port(graph.echo_capture, "input", "input_FL", Some("FL"));
+        graph.mock.add_link(monitor, echo_in);
+        let source = graph
+            .mock
+            .add_node("Firefox", "Stream/Output/Audio", json!({}));
+        let recorder = graph.mock.add_node("OBS", "Stream/Input/Audio", json!({}));
+
+        // Playing the local copy into the sink reaches that loop, but the
+        // split's loopbacks aren't part of it
+        check_for_feedback(
+            &graph.mock.objects(),
+            &PlannedSplit {
+                source_node_id: source,
+                disconnected_target_ids: &[],
+                recording_dest_node_ids: &[recorder],
+                local_sink_node_ids: &[graph.sink],
+            },
+        )
+        .unwrap();
+    }
+}
@@ -0,0 +1,165 @@
+//! Per-link health of an existing split, checked against the live graph, and
+//! bookkeeping for restarting its crashed loopbacks.
+
+use crate::pipewire::{self, PwObject};
+use crate::splitter::cleanup::check_loopbacks_running;
+use crate::splitter::setup::{
+    find_loopback_capture_node, find_loopback_playback_node, loopback_ports_ready,
+};
+use crate::splitter::state::SplitState;
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+/// Minimum time between restart attempts of the same loopback
+pub const RESTART_COOLDOWN: Duration = Duration::from_secs(5);
+
+/// Consecutive failed restarts after which a loopback is given up on
+pub const MAX_RESTART_ATTEMPTS: u32 = 3;
+
+/// Live state of one of a split's loopbacks
+#[derive(Serialize)]
+pub struct LoopbackHealth {
+    pub loopback_name: String,
+    pub pid: u32,
+    pub running: bool,
+    /// Both sides exist and have created their ports
+    pub ports_ready: bool,
+    /// The links this loopback needs, in signal order
+    pub links: Vec<LinkCheck>,
+}
+
+/// Whether an expected link between two nodes exists
+#[derive(Serialize)]
+pub struct LinkCheck {
+    /// e.g. `source -> OBS_to_Recording (capture)`
+    pub description: String,
+    pub present: bool,
+}
+
+impl LoopbackHealth {
+    /// Running, with ports, and with every expected link present
+    pub fn is_healthy(&self) -> bool {
+        self.running && self.ports_ready && self.links.iter().all(|l| l.present)
+    }
+}
+
+/// Check every loopback of `state` against the current graph
+///
+/// Recording loopbacks come first, in `state.recordings` order, then the local one.
+pub fn inspect_split(state: &SplitState, objects: &[PwObject]) -> Vec<LoopbackHealth> {
+    let (recording_running, local_running) = check_loopbacks_running(state);
+
+    let mut health: Vec<LoopbackHealth> = state
+        .recordings
+        .iter()
+        .zip(recording_running)
+        .map(|(recording, running)| {
+            inspect_loopback(
+                objects,
+                state,
+                &recording.loopback_name,
+                recording.pid,
+                running,
+                &[(
+                    Some(recording.dest_node_id),
+                    &recording.dest_application_name,
+                )],
+            )
+        })
+        .collect();
+
+    let sinks: Vec<(Option<u32>, &str)> = state
+        .local_output_names()
+        .map(|name| (pipewire::find_node_by_name(objects, name), name))
+        .collect();
+    health.push(inspect_loopback(
+        objects,
+        state,
+        &state.local_loopback_name,
+        state.loopback_to_local_pid,
+        local_running,
+        &sinks,
+    ));
+    health
+}
+
+fn inspect_loopback(
+    objects: &[PwObject],
+    state: &SplitState,
+    loopback_name: &str,
+    pid: u32,
+    running: bool,
+    targets: &[(Option<u32>, &str)],
+) -> LoopbackHealth {
+    let links = pipewire::extract_links(objects);
+    let linked = |from: Option<u32>, to: Option<u32>| match (from, to) {
+        (Some(from), Some(to)) => links
+            .iter()
+            .any(|l| l.output_node_id == from && l.input_node_id == to),
+        _ => false,
+    };
+
+    let capture = find_loopback_capture_node(objects, loopback_name);
+    let playback = find_loopback_playback_node(objects, loopback_name);
+
+    LoopbackHealth {
+        loopback_name: loopback_name.to_string(),
+        pid,
+        running,
+        ports_ready: loopback_ports_ready(objects, loopback_name),
+        links: [LinkCheck {
+            description: format!(
+                "{} -> {} (capture)",
+                state.source_application_name, loopback_name
+            ),
+            present: linked(Some(state.source_node_id), capture),
+        }]
+        .into_iter()
+        .chain(targets.iter().map(|&(target_id, target_name)| LinkCheck {
+            description: format!("{} (playback) -> {}", loopback_name, target_name),
+            present: linked(playback, target_id),
+        }))
+        .collect(),
+    }
+}
+
+/// Restart attempts of a single loopback
+#[derive(Debug, Clone, Default)]
+pub struct RestartTracker {
+    /// Consecutive failed restarts
+    failures: u32,
+    /// Successful restarts in total
+    restarts: u32,
+    last_attempt: Option<Instant>,
+}
+
+impl RestartTracker {
+    /// Whether the cooldown has passed and we haven't given up yet
+    pub fn may_attempt(&self) -> bool {
+        !self.gave_up()
+            && self
+                .last_attempt
+                .is_none_or(|at| at.elapsed() >= RESTART_COOLDOWN)
+    }
+
+    /// Note a restart attempt and whether it worked
+    pub fn record(&mut self, succeeded: bool) {
+        self.last_attempt = Some(Instant::now());
+        if succeeded {
+            self.failures = 0;
+            self.restarts += 1;
+        } else {
+            self.failures += 1;
+        }
+    }
+
+    /// How often the loopback has been restarted successfully
+    pub fn restarts(&self) -> u32 {
+        self.restarts
+    }
+
+    /// Whether restarting has failed too often to keep trying
+    pub fn gave_up(&self) -> bool {
+        self.failures >= MAX_RESTART_ATTEMPTS
+    }
+}
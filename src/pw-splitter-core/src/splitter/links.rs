@@ -0,0 +1,297 @@
+//! Saving the links a split replaces, and restoring them on teardown.
+//!
+//! Links are saved with the `node:port` names pw-link takes as well as the
+//! node and port ids. Names alone can be ambiguous (every OBS input is named
+//! "OBS"), and ids are reused once nodes go away, so restoring only trusts a
+//! port that matches the saved name, and prefers the saved id or media name
+//! among several such ports.
+
+use crate::error::Result;
+use crate::pipewire::{self, AudioPort, AudioSource, PortDirection, PwObject, SourceConnection};
+use crate::splitter::state::{SavedLink, SplitState};
+use std::collections::HashSet;
+
+/// Disconnect source from a target, returning the saved links
+///
+/// Every existing link between the two nodes is saved, whatever its channel
+/// or the target's media class, so teardown restores exactly what was there.
+pub(super) fn disconnect_source_from_target(
+    source: &AudioSource,
+    connection: &SourceConnection,
+    objects: &[PwObject],
+) -> Option<Vec<SavedLink>> {
+    let ports = pipewire::extract_ports(objects);
+    let port_name = |port_id: u32| {
+        ports
+            .iter()
+            .find(|p| p.port_id == port_id)
+            .map(|p| p.port_name.as_str())
+    };
+    let mut saved_links = Vec::new();
+
+    // Get node names for pw-link
+    let source_node_name = pipewire::get_node_name(objects, source.node_id)?;
+    let target_node_name = pipewire::get_node_name(objects, connection.target_node_id)?;
+    let source_media_name = pipewire::get_node_media_name(objects, source.node_id);
+    let target_media_name = pipewire::get_node_media_name(objects, connection.target_node_id);
+
+    // Disconnect each link
+    let links = pipewire::extract_links(objects).into_iter().filter(|l| {
+        l.output_node_id == source.node_id && l.input_node_id == connection.target_node_id
+    });
+    for link in links {
+        let (Some(src_port), Some(tgt_port)) = (
+            port_name(link.output_port_id),
+            port_name(link.input_port_id),
+        ) else {
+            tracing::warn!("link {} has unknown ports, leaving it alone", link.link_id);
+            continue;
+        };
+        let output_port = pipewire::get_port_link_name(&source_node_name, src_port);
+        let input_port = pipewire::get_port_link_name(&target_node_name, tgt_port);
+
+        match pipewire::destroy_link(&output_port, &input_port) {
+            Ok(()) => saved_links.push(SavedLink {
+                output_port,
+                input_port,
+                output_node_id: Some(link.output_node_id),
+                output_port_id: Some(link.output_port_id),
+                output_media_name: source_media_name.clone(),
+                input_node_id: Some(link.input_node_id),
+                input_port_id: Some(link.input_port_id),
+                input_media_name: target_media_name.clone(),
+            }),
+            Err(e) => tracing::warn!("could not disconnect {}: {}", output_port, e),
+        }
+    }
+
+    if saved_links.is_empty() {
+        None
+    } else {
+        Some(saved_links)
+    }
+}
+
+/// Record the links into and out of the split's loopbacks in `created_links`
+///
+/// Teardown destroys these before killing the loopbacks, rather than relying
+/// on a loopback's death to take its links with it. Reading them back from
+/// the graph also catches links made while restarting or relinking. If the
+/// graph can't be read, the previous record is kept, and teardown still
+/// kills the loopbacks.
+pub(super) fn record_created_links(state: &mut SplitState) {
+    let objects = match pipewire::get_fresh_pw_objects() {
+        Ok(objects) => objects,
+        Err(e) => {
+            tracing::warn!("could not record the links of {}: {}", state.name, e);
+            return;
+        }
+    };
+    let names: HashSet<&str> = state.loopback_names().collect();
+    let node_name = |node_id| pipewire::get_node_name(&objects, node_id);
+    let loopback_ids: HashSet<u32> = objects
+        .iter()
+        .filter_map(|obj| match obj {
+            PwObject::Node(node) => Some(node.id),
+            _ => None,
+        })
+        .filter(|&id| node_name(id).is_some_and(|name| names.contains(name.as_str())))
+        .collect();
+
+    let ports = pipewire::extract_ports(&objects);
+    let port_name = |port_id: u32| {
+        ports
+            .iter()
+            .find(|p| p.port_id == port_id)
+            .map(|p| p.port_name.as_str())
+    };
+    state.created_links = pipewire::extract_links(&objects)
+        .into_iter()
+        .filter(|l| {
+            loopback_ids.contains(&l.output_node_id) || loopback_ids.contains(&l.input_node_id)
+        })
+        .filter_map(|l| {
+            Some(SavedLink {
+                output_port: pipewire::get_port_link_name(
+                    &node_name(l.output_node_id)?,
+                    port_name(l.output_port_id)?,
+                ),
+                input_port: pipewire::get_port_link_name(
+                    &node_name(l.input_node_id)?,
+                    port_name(l.input_port_id)?,
+                ),
+                output_node_id: Some(l.output_node_id),
+                output_port_id: Some(l.output_port_id),
+                output_media_name: pipewire::get_node_media_name(&objects, l.output_node_id),
+                input_node_id: Some(l.input_node_id),
+                input_port_id: Some(l.input_port_id),
+                input_media_name: pipewire::get_node_media_name(&objects, l.input_node_id),
+            })
+        })
+        .collect();
+    tracing::debug!(
+        "split {} made {} link(s)",
+        state.name,
+        state.created_links.len()
+    );
+}
+
+/// Recreate a saved link, by port id where both ports can be identified
+///
+/// Otherwise the saved names are handed to pw-link as they are.
+pub(super) fn restore_link(link: &SavedLink) -> Result<()> {
+    let objects = pipewire::get_pw_objects()?;
+    let ports = pipewire::extract_ports(&objects);
+    let output = resolve_port(
+        &ports,
+        &objects,
+        &link.output_port,
+        (link.output_node_id, link.output_port_id),
+        link.output_media_name.as_deref(),
+        PortDirection::Output,
+    );
+    let input = resolve_port(
+        &ports,
+        &objects,
+        &link.input_port,
+        (link.input_node_id, link.input_port_id),
+        link.input_media_name.as_deref(),
+        PortDirection::Input,
+    );
+
+    match (output, input) {
+        (Some(output_id), Some(input_id)) => {
+            pipewire::create_link_by_id(&output_id.to_string(), input_id)
+        }
+        _ => pipewire::create_link(&link.output_port, &link.input_port, false),
+    }
+}
+
+/// Id of the live port that best matches a saved `node:port` name
+///
+/// `saved_ids` are the node and port ids at the time the link was saved.
+/// Returns `None` when no port, or several equally good ones, match.
+fn resolve_port(
+    ports: &[AudioPort],
+    objects: &[PwObject],
+    link_name: &str,
+    saved_ids: (Option<u32>, Option<u32>),
+    media_name: Option<&str>,
+    direction: PortDirection,
+) -> Option<u32> {
+    let (node_name, port_name) = link_name.rsplit_once(':')?;
+    let candidates: Vec<&AudioPort> = ports
+        .iter()
+        .filter(|p| {
+            p.direction == direction
+                && p.port_name == port_name
+                && pipewire::get_node_name(objects, p.node_id).as_deref() == Some(node_name)
+        })
+        .collect();
+
+    let (saved_node_id, saved_port_id) = saved_ids;
+    if let Some(port) = candidates
+        .iter()
+        .find(|p| Some(p.port_id) == saved_port_id || Some(p.node_id) == saved_node_id)
+    {
+        return Some(port.port_id);
+    }
+
+    // The node restarted; tell same-named nodes apart by media name
+    let matching: Vec<&&AudioPort> = candidates
+        .iter()
+        .filter(|p| {
+            media_name.is_none_or(|media_name| {
+                pipewire::get_node_media_name(objects, p.node_id).as_deref() == Some(media_name)
+            })
+        })
+        .collect();
+    match matching.as_slice() {
+        [port] => Some(port.port_id),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipewire::mock::{Desktop, TestEnv};
+    use crate::splitter::teardown_split;
+    use serde_json::json;
+
+    #[test]
+    fn teardown_restores_links_to_every_target() {
+        let _env = TestEnv::new();
+        let desktop = Desktop::new();
+        // Also feeding another recorder, on a single channel
+        let other = desktop.mock.add_node(
+            "Discord",
+            "Stream/Input/Audio",
+            json!({ "application.name": "Discord" }),
+        );
+        let other_port = desktop
+            .mock
+            .add_port(other, "input", "input_MONO", Some("MONO"));
+        desktop.mock.add_link(desktop.source_ports[0], other_port);
+
+        let result = desktop.set_up(desktop.config()).unwrap();
+        assert_eq!(result.state.original_links.len(), 3);
+        assert!(!desktop.source_on_speakers());
+        assert!(!desktop.mock.has_link(desktop.source_ports[0], other_port));
+
+        teardown_split(&result.state, true, desktop.mock.clone()).unwrap();
+
+        assert!(desktop.source_on_speakers());
+        assert!(desktop.mock.has_link(desktop.source_ports[0], other_port));
+    }
+
+    #[test]
+    fn created_links_are_recorded() {
+        let _env = TestEnv::new();
+        let desktop = Desktop::new();
+        // Unrelated to the split, so not recorded
+        let chat = desktop
+            .mock
+            .add_node("Discord", "Stream/Output/Audio", json!({}));
+        let chat_port = desktop
+            .mock
+            .add_port(chat, "output", "output_MONO", Some("MONO"));
+        desktop.mock.add_link(chat_port, desktop.speaker_ports[0]);
+        let mut state = desktop.set_up(desktop.config()).unwrap().state;
+
+        state.created_links.clear();
+        pipewire::with_runner(desktop.mock.clone(), || record_created_links(&mut state));
+
+        let mut links: Vec<(&str, &str)> = state
+            .created_links
+            .iter()
+            .map(|l| (l.output_port.as_str(), l.input_port.as_str()))
+            .collect();
+        links.sort();
+        assert_eq!(
+            links,
+            [
+                ("Firefox:output_FL", "Firefox_to_Local:input_FL"),
+                ("Firefox:output_FL", "Firefox_to_Recording:input_FL"),
+                ("Firefox:output_FR", "Firefox_to_Local:input_FR"),
+                ("Firefox:output_FR", "Firefox_to_Recording:input_FR"),
+                (
+                    "Firefox_to_Local:output_FL",
+                    "alsa_output.speakers:playback_FL"
+                ),
+                (
+                    "Firefox_to_Local:output_FR",
+                    "alsa_output.speakers:playback_FR"
+                ),
+                ("Firefox_to_Recording:output_FL", "OBS:input_FL"),
+                ("Firefox_to_Recording:output_FR", "OBS:input_FR"),
+            ]
+        );
+        assert!(
+            state
+                .created_links
+                .iter()
+                .all(|l| l.output_port_id.is_some())
+        );
+    }
+}
@@ -0,0 +1,21 @@
+pub mod channel_map;
+pub mod cleanup;
+pub mod diff;
+pub mod graph;
+pub mod health;
+pub mod links;
+pub mod reattach;
+pub mod setup;
+pub mod state;
+pub mod stepped;
+pub mod volume;
+
+pub use channel_map::*;
+pub use cleanup::*;
+pub use diff::*;
+pub use health::*;
+pub use reattach::*;
+pub use setup::*;
+pub use state::*;
+pub use stepped::*;
+pub use volume::*;
@@ -0,0 +1,167 @@
+//! Re-linking a split after its source or a recording destination restarts.
+//!
+//! A restarted application gets a new node id, so the loopbacks keep running
+//! but capture (or deliver) nothing. Reattaching the source is opt-in: the
+//! replacement is recognised only by its node and application names, which
+//! another app could share, and it gets disconnected from its own outputs.
+//! Relinking a destination only adds links from our loopback, so it happens
+//! automatically.
+//!
+//! Node ids are recycled, so a node with the saved id and name may still be a
+//! different one; where the state has the node's `object.serial`, that must
+//! match as well.
+
+use crate::error::{PwSplitterError, Result};
+use crate::pipewire::{self, AudioSource, PwObject, RecordingDest, SourceClasses};
+use crate::splitter::links::{disconnect_source_from_target, record_created_links};
+use crate::splitter::setup::connect_source_to_loopback;
+use crate::splitter::state::{RecordingLoopback, SplitState};
+
+/// Whether the split's source node is still the one it was created for
+pub fn source_alive(state: &SplitState, objects: &[PwObject]) -> bool {
+    pipewire::get_node_name(objects, state.source_node_id)
+        .is_some_and(|name| name == state.source_node_name)
+        && pipewire::serial_matches(
+            state.source_serial,
+            pipewire::get_node_serial(objects, state.source_node_id),
+        )
+}
+
+/// The node that replaced a split's vanished source, if there's exactly one
+///
+/// Returns `None` while the original source is alive, and when several
+/// candidates match, rather than guessing between them.
+pub fn find_restarted_source(state: &SplitState, objects: &[PwObject]) -> Option<AudioSource> {
+    if source_alive(state, objects) {
+        return None;
+    }
+
+    let any_class = SourceClasses {
+        include_devices: true,
+        include_sink_monitors: true,
+    };
+    let mut candidates: Vec<AudioSource> = pipewire::extract_audio_sources(objects, any_class)
+        .into_iter()
+        .filter(|s| {
+            s.node_name == state.source_node_name
+                && s.application_name == state.source_application_name
+        })
+        .collect();
+    match candidates.len() {
+        1 => candidates.pop(),
+        _ => None,
+    }
+}
+
+/// Link a restarted source into the split's loopbacks
+///
+/// Returns `false` if the original source is still there. The new node is
+/// disconnected from wherever it autoconnected to, so it's only heard through
+/// the split. The saved original links refer to ports by node name, so they
+/// still restore correctly on teardown.
+pub fn reattach_split(state: &mut SplitState) -> Result<bool> {
+    let objects = pipewire::get_pw_objects()?;
+    if source_alive(state, &objects) {
+        return Ok(false);
+    }
+
+    let source = find_restarted_source(state, &objects).ok_or_else(|| {
+        PwSplitterError::NodeNotFound(format!(
+            "a single restarted '{}' ({})",
+            state.source_application_name, state.source_node_name
+        ))
+    })?;
+    tracing::info!(
+        "reattaching {} from node {} to node {}",
+        state.name,
+        state.source_node_id,
+        source.node_id
+    );
+
+    for connection in pipewire::find_source_connections(source.node_id, &objects) {
+        disconnect_source_from_target(&source, &connection, &objects);
+    }
+    for loopback_name in state.loopback_names() {
+        connect_source_to_loopback(
+            source.node_id,
+            loopback_name,
+            state.channel_map(loopback_name),
+        )?;
+    }
+
+    state.source_node_id = source.node_id;
+    state.source_serial = source.serial;
+    record_created_links(state);
+    state.save()?;
+    Ok(true)
+}
+
+/// Whether a recording's destination node is still the one it was linked to
+pub fn dest_alive(recording: &RecordingLoopback, dests: &[RecordingDest]) -> bool {
+    dests.iter().any(|d| {
+        d.node_id == recording.dest_node_id
+            && is_same_dest(recording, d)
+            && pipewire::serial_matches(recording.dest_serial, d.serial)
+    })
+}
+
+/// Whether `dest` looks like the recording's destination
+///
+/// OBS names every input node "OBS", so the media name (the input's name in
+/// OBS) is what tells scene inputs apart.
+fn is_same_dest(recording: &RecordingLoopback, dest: &RecordingDest) -> bool {
+    dest.kind == recording.dest_kind
+        && dest.application_name == recording.dest_application_name
+        && dest.media_name == recording.dest_media_name
+}
+
+/// The node that replaced a recording's vanished destination, if there's exactly one
+pub fn find_restarted_dest(
+    recording: &RecordingLoopback,
+    dests: &[RecordingDest],
+) -> Option<RecordingDest> {
+    if dest_alive(recording, dests) {
+        return None;
+    }
+
+    let mut candidates: Vec<&RecordingDest> = dests
+        .iter()
+        .filter(|d| is_same_dest(recording, d))
+        .collect();
+    match candidates.len() {
+        1 => candidates.pop().cloned(),
+        _ => None,
+    }
+}
+
+/// Link recording loopbacks whose destination restarted to its replacement
+///
+/// Returns how many were relinked; a destination that is gone with no single
+/// replacement is left for a later call.
+pub fn relink_recording_dests(state: &mut SplitState) -> Result<usize> {
+    let objects = pipewire::get_pw_objects()?;
+    let dests = pipewire::extract_recording_dests(&objects);
+
+    let mut relinked = 0;
+    for recording in &mut state.recordings {
+        let Some(dest) = find_restarted_dest(recording, &dests) else {
+            continue;
+        };
+        tracing::info!(
+            "relinking {} from destination node {} to node {}",
+            recording.loopback_name,
+            recording.dest_node_id,
+            dest.node_id
+        );
+        pipewire::connect_loopback_to_recording_dest(&recording.loopback_name, dest.node_id)?;
+        recording.dest_node_id = dest.node_id;
+        recording.dest_serial = dest.serial;
+        relinked += 1;
+    }
+
+    if relinked > 0 {
+        record_created_links(state);
+        state.save()?;
+    }
+    Ok(relinked)
+}
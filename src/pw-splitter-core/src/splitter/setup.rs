@@ -0,0 +1,622 @@
+use crate::error::{PwSplitterError, Result};
+use crate::pipewire::{
+    self, AudioSink, AudioSource, CommandRunner, LoopbackRole, LoopbackTuning, PwObject,
+    RecordingDest, SourceConnection,
+};
+use crate::splitter::channel_map::ChannelMapping;
+use crate::splitter::state::SplitState;
+use crate::splitter::stepped::{SetupProgress, SetupStep, setup_split_stepped};
+use std::process::Child;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// Information needed to set up a split
+pub struct SplitConfig {
+    pub source: AudioSource,
+    /// Destinations to record to; each gets its own loopback
+    pub recording_dests: Vec<RecordingDest>,
+    pub original_connections: Vec<SourceConnection>,
+    /// Initial local volume (1.0 = 100%)
+    pub local_volume: f32,
+    /// Volume of the recording loopbacks (1.0 = 100%)
+    pub recording_volume: f32,
+    /// Sinks the local copy plays on; empty picks the source's current output
+    pub local_sinks: Vec<AudioSink>,
+    /// Source channels feeding the recording loopbacks
+    pub recording_channel_map: ChannelMapping,
+    /// Source channels feeding the local loopback
+    pub local_channel_map: ChannelMapping,
+    /// Latency and rate of the spawned loopbacks
+    pub tuning: LoopbackTuning,
+    /// `node.description` of the local loopback; `None` uses `<app> -> Local`
+    pub local_description: Option<String>,
+    /// Split that already routes the source, for a clone of it: the source's
+    /// links to that split's loopbacks are left alone
+    pub shares_source_with: Option<String>,
+    /// Loopback name prefix shown beforehand, see [`LinkDiff::loopback_prefix`];
+    /// `None` derives it from the source's name. Suffixed if taken by then.
+    ///
+    /// [`LinkDiff::loopback_prefix`]: crate::splitter::LinkDiff::loopback_prefix
+    pub loopback_prefix: Option<String>,
+}
+
+/// Result of setting up a split
+pub struct SplitResult {
+    pub state: SplitState,
+    pub loopbacks_to_recording: Vec<Child>,
+    pub loopback_to_local: Child,
+    /// Non-fatal problems, e.g. channels dropped between mismatched layouts
+    pub warnings: Vec<String>,
+}
+
+/// What [`setup_split`] did
+pub enum SetupOutcome {
+    /// The split is running
+    Created(Box<SplitResult>),
+    /// A dry run stopped before changing anything; see [`SetupProgress::DryRun`]
+    DryRun(Vec<String>),
+}
+
+/// Set up the audio split
+///
+/// This creates loopback streams that all capture from the source:
+/// - One per recording destination (e.g. OBS), at full volume
+/// - One to the local speakers with adjustable volume
+///
+/// Every command goes through `runner`, normally [`pipewire::runner()`].
+///
+/// Blocks until the split is ready; use [`setup_split_stepped`] to drive the
+/// setup incrementally from an event loop instead.
+pub fn setup_split(
+    config: SplitConfig,
+    port_timeout: Duration,
+    runner: Arc<dyn CommandRunner>,
+) -> Result<SetupOutcome> {
+    let mut setup = setup_split_stepped(config)
+        .with_port_timeout(port_timeout)
+        .with_runner(runner);
+    loop {
+        match setup.advance()? {
+            SetupProgress::InProgress(SetupStep::WaitingForPorts) => {
+                thread::sleep(pipewire::PORT_POLL_INTERVAL)
+            }
+            SetupProgress::InProgress(_) => {}
+            SetupProgress::Complete(result) => return Ok(SetupOutcome::Created(result)),
+            SetupProgress::DryRun(planned_links) => {
+                return Ok(SetupOutcome::DryRun(planned_links));
+            }
+        }
+    }
+}
+
+/// Fail if an active split other than `except` already routes `source`
+///
+/// Splitting a source twice would disconnect its original links twice and
+/// leave the routing inconsistent once either split is torn down. Node IDs
+/// are reused, so the saved node name and serial must match too. A clone
+/// (`except` set) may also join the other clones of `except`, which leave
+/// the links alone.
+pub fn check_not_already_split(source: &AudioSource, except: Option<&str>) -> Result<()> {
+    match SplitState::list_all()?.into_iter().find(|s| {
+        except != Some(s.name.as_str())
+            && !(except.is_some() && s.clone_of.as_deref() == except)
+            && s.source_node_id == source.node_id
+            && s.source_node_name == source.node_name
+            && pipewire::serial_matches(s.source_serial, source.serial)
+    }) {
+        Some(existing) => Err(PwSplitterError::SplitAlreadyExists(
+            source.application_name.clone(),
+            existing.name,
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Stand-in connection to the default sink, or else the first sink, that
+/// isn't a recording destination
+///
+/// Used for sources that aren't connected anywhere yet, so the local copy
+/// still has somewhere to go.
+pub fn default_output_connection(
+    source: &AudioSource,
+    objects: &[PwObject],
+    recording_dest_ids: &[u32],
+) -> Option<SourceConnection> {
+    let default_sink = pipewire::default_sink_name(objects);
+    // A sink split through its monitor can't also be its own local output
+    let sinks: Vec<AudioSink> = pipewire::extract_audio_sinks(objects)
+        .into_iter()
+        .filter(|s| !recording_dest_ids.contains(&s.node_id) && s.node_id != source.node_id)
+        .collect();
+    sinks
+        .iter()
+        .find(|s| default_sink.as_deref() == Some(s.node_name.as_str()))
+        .or(sinks.first())
+        .cloned()
+        .map(|sink| SourceConnection {
+            source_node_id: source.node_id,
+            target_node_id: sink.node_id,
+            target_node_name: sink.node_name,
+            links: Vec::new(),
+        })
+}
+
+/// Find the primary output connection (prefer Audio/Sink over recording inputs)
+///
+/// `recording_dest_ids` are skipped so a virtual sink chosen as a recording
+/// destination isn't also used as the local output.
+pub(super) fn find_primary_output<'a>(
+    connections: &'a [SourceConnection],
+    recording_dest_ids: &[u32],
+) -> Result<&'a SourceConnection> {
+    let objects = pipewire::get_pw_objects()?;
+    primary_output(connections, recording_dest_ids, &objects)
+        .ok_or(PwSplitterError::NoActiveConnection)
+}
+
+/// The connection [`find_primary_output`] picks, from `objects`
+///
+/// A source playing to several sinks keeps the default sink for the local
+/// copy, then any sink, then whatever it's connected to.
+pub(super) fn primary_output<'a>(
+    connections: &'a [SourceConnection],
+    recording_dest_ids: &[u32],
+    objects: &[PwObject],
+) -> Option<&'a SourceConnection> {
+    let connections: Vec<&SourceConnection> = connections
+        .iter()
+        .filter(|c| !recording_dest_ids.contains(&c.target_node_id))
+        .collect();
+    let default_sink = pipewire::default_sink_name(objects);
+    let sinks = pipewire::extract_audio_sinks(objects);
+    let is_sink = |conn: &SourceConnection| sinks.iter().any(|s| s.node_id == conn.target_node_id);
+
+    connections
+        .iter()
+        .find(|c| is_sink(c) && default_sink.as_deref() == Some(c.target_node_name.as_str()))
+        .or_else(|| connections.iter().find(|c| is_sink(c)))
+        .or(connections.first())
+        .copied()
+}
+
+/// Connect source output to a loopback's capture input, as `mapping` says
+pub(super) fn connect_source_to_loopback(
+    source_node_id: u32,
+    loopback_name: &str,
+    mapping: &ChannelMapping,
+) -> Result<()> {
+    let objects = pipewire::get_pw_objects()?;
+    let ports = pipewire::extract_ports(&objects);
+
+    // Find the loopback capture node (it has "input" in description and has input ports)
+    // The capture side of pw-loopback has input ports
+    let loopback_node_id = find_loopback_capture_node(&objects, loopback_name);
+
+    let loopback_node_id = loopback_node_id.ok_or_else(|| {
+        PwSplitterError::NodeNotFound(format!("loopback capture {}", loopback_name))
+    })?;
+
+    // Get source output ports (a sink's are its monitor ports); an app
+    // without a channel map may label none of them with a known position, in
+    // which case they're linked in port order
+    let mut source_ports =
+        pipewire::routable_ports(&ports, source_node_id, pipewire::PortDirection::Output);
+    let unlabeled = !pipewire::has_channel_positions(&source_ports);
+    if unlabeled {
+        source_ports =
+            pipewire::ports_in_order(&ports, source_node_id, pipewire::PortDirection::Output);
+    }
+
+    // Get loopback capture input ports
+    let loopback_ports =
+        pipewire::routable_ports(&ports, loopback_node_id, pipewire::PortDirection::Input);
+    tracing::debug!(
+        "source {} -> {}: {} source port(s), {} loopback port(s)",
+        source_node_id,
+        loopback_name,
+        source_ports.len(),
+        loopback_ports.len()
+    );
+
+    if source_ports.is_empty() {
+        return Err(PwSplitterError::SourceNotPlaying(format!(
+            "source node {}",
+            source_node_id
+        )));
+    }
+    if loopback_ports.is_empty() {
+        return Err(PwSplitterError::LinkCreationFailed(format!(
+            "Could not find ports: source={}, loopback={}",
+            source_ports.len(),
+            loopback_ports.len()
+        )));
+    }
+
+    let source_node_name = pipewire::get_node_name(&objects, source_node_id)
+        .ok_or_else(|| PwSplitterError::NodeNotFound(format!("source node {}", source_node_id)))?;
+
+    let loopback_node_name =
+        pipewire::get_node_name(&objects, loopback_node_id).ok_or_else(|| {
+            PwSplitterError::NodeNotFound(format!("loopback node {}", loopback_node_id))
+        })?;
+
+    // Create links for each mapped channel (by default, matching channels,
+    // with mono fanning out to stereo)
+    let pairs = if unlabeled {
+        let pairs = pipewire::pair_ports_by_position(&source_ports, &loopback_ports);
+        tracing::warn!(
+            "source {} has no channel positions, linking by port order: {}",
+            source_node_id,
+            pairs
+                .iter()
+                .map(|(src, lb)| format!("{} -> {}", src.port_name, lb.port_name))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        pairs
+    } else {
+        mapping.pair_ports(&source_ports, &loopback_ports)
+    };
+    for (src_port, lb_port) in pairs {
+        let output_port = pipewire::get_port_link_name(&source_node_name, &src_port.port_name);
+        let input_port = pipewire::get_port_link_name(&loopback_node_name, &lb_port.port_name);
+        pipewire::create_link(&output_port, &input_port, false)?;
+    }
+
+    Ok(())
+}
+
+/// Connect loopback playback output to a sink
+///
+/// Returns the loopback channels the sink has no port for.
+pub(super) fn connect_loopback_to_sink(
+    loopback_name: &str,
+    sink_name: &str,
+) -> Result<Vec<String>> {
+    let objects = pipewire::get_pw_objects()?;
+    let ports = pipewire::extract_ports(&objects);
+
+    // Find the loopback playback node (has output ports)
+    let loopback_node_id =
+        find_loopback_playback_node(&objects, loopback_name).ok_or_else(|| {
+            PwSplitterError::NodeNotFound(format!("loopback playback {}", loopback_name))
+        })?;
+
+    let sink_node_id = pipewire::find_node_by_name(&objects, sink_name)
+        .ok_or_else(|| PwSplitterError::NodeNotFound(sink_name.to_string()))?;
+
+    // Get loopback playback output ports
+    let loopback_ports =
+        pipewire::routable_ports(&ports, loopback_node_id, pipewire::PortDirection::Output);
+
+    // Get sink input ports; channel-less ones only matter if nothing else matches
+    let all_sink_ports =
+        pipewire::ports_in_order(&ports, sink_node_id, pipewire::PortDirection::Input);
+    let sink_ports = pipewire::routable_ports(&ports, sink_node_id, pipewire::PortDirection::Input);
+    tracing::debug!(
+        "{} -> sink {}: {} loopback port(s), {} sink port(s)",
+        loopback_name,
+        sink_name,
+        loopback_ports.len(),
+        sink_ports.len()
+    );
+
+    if loopback_ports.is_empty() {
+        return Err(PwSplitterError::LinkCreationFailed(format!(
+            "loopback {} has no output ports",
+            loopback_name
+        )));
+    }
+    if all_sink_ports.is_empty() {
+        return Err(PwSplitterError::LinkCreationFailed(format!(
+            "sink {} has no input ports; check its profile (e.g. in pavucontrol)",
+            sink_name
+        )));
+    }
+
+    let loopback_node_name =
+        pipewire::get_node_name(&objects, loopback_node_id).ok_or_else(|| {
+            PwSplitterError::NodeNotFound(format!("loopback node {}", loopback_node_id))
+        })?;
+
+    // Create links for each matching channel (mono fans out to stereo)
+    let mut pairs = pipewire::pair_ports(&loopback_ports, &sink_ports);
+    if pairs.is_empty() {
+        // The sink's profile uses other port names (Pro Audio, some HDMI setups)
+        let sink_port_names: Vec<&str> = all_sink_ports
+            .iter()
+            .map(|p| match p.channel.as_str() {
+                "" => p.port_name.as_str(),
+                channel => channel,
+            })
+            .collect();
+        tracing::warn!(
+            "no channels of {} match sink {} ({}), linking by port order",
+            loopback_name,
+            sink_name,
+            sink_port_names.join(", ")
+        );
+        pairs = pipewire::pair_ports_by_position(&loopback_ports, &all_sink_ports);
+    }
+
+    for (lb_port, sink_port) in &pairs {
+        let output_port = pipewire::get_port_link_name(&loopback_node_name, &lb_port.port_name);
+        let input_port = pipewire::get_port_link_name(sink_name, &sink_port.port_name);
+        // Devices are where formats can fail to negotiate, so check these links
+        pipewire::create_link(&output_port, &input_port, true)?;
+    }
+
+    Ok(pipewire::unpaired_channels(&loopback_ports, &pairs))
+}
+
+/// Channel map of a source's output ports, for spawning matching loopbacks
+pub(super) fn source_channels(source_node_id: u32) -> Result<Vec<String>> {
+    let objects = pipewire::get_pw_objects()?;
+    let ports = pipewire::extract_ports(&objects);
+    Ok(pipewire::channel_layout(
+        &ports,
+        source_node_id,
+        pipewire::PortDirection::Output,
+    ))
+}
+
+/// Describe channels dropped on the way to `target`, if any
+pub(super) fn dropped_channels_warning(target: &str, dropped: &[String]) -> Option<String> {
+    (!dropped.is_empty()).then(|| {
+        format!(
+            "{} has no {} channel(s); they are dropped",
+            target,
+            dropped.join("/")
+        )
+    })
+}
+
+/// Check whether both sides of a loopback have created their routable ports
+pub fn loopback_ports_ready(objects: &[PwObject], loopback_name: &str) -> bool {
+    let ports = pipewire::extract_ports(objects);
+    let has_ports = |node_id: Option<u32>, direction| {
+        node_id.is_some_and(|id| !pipewire::routable_ports(&ports, id, direction).is_empty())
+    };
+
+    has_ports(
+        find_loopback_capture_node(objects, loopback_name),
+        pipewire::PortDirection::Input,
+    ) && has_ports(
+        find_loopback_playback_node(objects, loopback_name),
+        pipewire::PortDirection::Output,
+    )
+}
+
+/// Block until a loopback's ports exist, or `timeout` elapses
+pub fn wait_for_loopback_ports(loopback_name: &str, timeout: Duration) -> Result<()> {
+    pipewire::wait_for_objects(
+        pipewire::get_fresh_pw_objects,
+        |objects| loopback_ports_ready(objects, loopback_name),
+        &format!("loopback {}", loopback_name),
+        timeout,
+    )
+    .map(|_| ())
+}
+
+/// Find the capture side of a loopback (the node with input ports)
+///
+/// Matches on the `pw_splitter.*` tags first; the description and port
+/// heuristics below only serve loopbacks spawned by older versions.
+pub fn find_loopback_capture_node(objects: &[PwObject], loopback_name: &str) -> Option<u32> {
+    if let Some(id) =
+        pipewire::find_tagged_loopback_node(objects, loopback_name, LoopbackRole::Capture)
+    {
+        return Some(id);
+    }
+
+    let ports = pipewire::extract_ports(objects);
+
+    for obj in objects {
+        if let pipewire::PwObject::Node(node) = obj
+            && let Some(info) = &node.info
+            && let Some(props) = &info.props
+            && let Some(desc) = &props.node_description
+            && desc.contains(loopback_name)
+            && desc.contains("input")
+        {
+            return Some(node.id);
+        }
+    }
+
+    // Fallback: find by checking which node has input ports
+    for obj in objects {
+        if let pipewire::PwObject::Node(node) = obj
+            && let Some(info) = &node.info
+            && let Some(props) = &info.props
+            && let Some(name) = &props.node_name
+            && name.contains(loopback_name)
+        {
+            // Check if this node has input ports
+            let has_input = ports
+                .iter()
+                .any(|p| p.node_id == node.id && p.direction == pipewire::PortDirection::Input);
+            if has_input {
+                return Some(node.id);
+            }
+        }
+    }
+
+    None
+}
+
+/// Find the playback side of a loopback (the node with output ports)
+///
+/// Like [`find_loopback_capture_node`], tags take precedence over heuristics.
+pub fn find_loopback_playback_node(objects: &[PwObject], loopback_name: &str) -> Option<u32> {
+    if let Some(id) =
+        pipewire::find_tagged_loopback_node(objects, loopback_name, LoopbackRole::Playback)
+    {
+        return Some(id);
+    }
+
+    let ports = pipewire::extract_ports(objects);
+
+    for obj in objects {
+        if let pipewire::PwObject::Node(node) = obj
+            && let Some(info) = &node.info
+            && let Some(props) = &info.props
+            && let Some(desc) = &props.node_description
+            && desc.contains(loopback_name)
+            && desc.contains("output")
+        {
+            return Some(node.id);
+        }
+    }
+
+    // Fallback: find by checking which node has output ports
+    for obj in objects {
+        if let pipewire::PwObject::Node(node) = obj
+            && let Some(info) = &node.info
+            && let Some(props) = &info.props
+            && let Some(name) = &props.node_name
+            && name.contains(loopback_name)
+        {
+            // Check if this node has output ports
+            let has_output = ports
+                .iter()
+                .any(|p| p.node_id == node.id && p.direction == pipewire::PortDirection::Output);
+            if has_output {
+                return Some(node.id);
+            }
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipewire::mock::{Desktop, MockRunner, TestEnv};
+    use crate::pipewire::{CommandRunner, DryRunRunner};
+    use std::io;
+    use std::process::Output;
+
+    /// Queries the mock's graph but only records changes, like `--dry-run`
+    struct DryRunMock {
+        mock: Arc<MockRunner>,
+        dry_run: DryRunRunner,
+    }
+
+    impl CommandRunner for DryRunMock {
+        fn query(&self, program: &str, args: &[&str]) -> io::Result<Output> {
+            self.mock.query(program, args)
+        }
+
+        fn run(&self, program: &str, args: &[&str]) -> io::Result<Output> {
+            self.dry_run.run(program, args)
+        }
+
+        fn spawn(&self, program: &str, args: &[&str]) -> io::Result<Option<Child>> {
+            self.dry_run.spawn(program, args)
+        }
+
+        fn is_dry_run(&self) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn setup_routes_source_through_loopbacks() {
+        let _env = TestEnv::new();
+        let desktop = Desktop::new();
+
+        let result = desktop.set_up(desktop.config()).unwrap();
+
+        let calls = desktop.mock.calls();
+        assert_eq!(
+            calls
+                .iter()
+                .filter(|c| c.starts_with("pw-loopback"))
+                .count(),
+            2
+        );
+        assert!(calls.contains(
+            &"pw-link -d Firefox:output_FL alsa_output.speakers:playback_FL".to_string()
+        ));
+        assert!(!desktop.source_on_speakers());
+
+        let state = &result.state;
+        assert_eq!(state.name, "Firefox_Split");
+        assert_eq!(state.recordings[0].loopback_name, "Firefox_to_Recording");
+        assert_eq!(state.original_output_node_name, "alsa_output.speakers");
+        assert_eq!(state.original_links.len(), 2);
+        // Each loopback has two links in and two out
+        assert_eq!(state.created_links.len(), 8);
+        assert!(
+            state
+                .created_links
+                .iter()
+                .any(|l| l.output_port == "Firefox_to_Local:output_FR"
+                    && l.input_port == "alsa_output.speakers:playback_FR")
+        );
+        assert_eq!(SplitState::load(&state.name).unwrap().name, state.name);
+    }
+
+    #[test]
+    fn source_can_be_cloned_more_than_once() {
+        let _env = TestEnv::new();
+        let desktop = Desktop::new();
+        let original = desktop.set_up(desktop.config()).unwrap();
+        let clone_config = || SplitConfig {
+            original_connections: Vec::new(),
+            local_volume: 0.0,
+            local_sinks: pipewire::extract_audio_sinks(&desktop.mock.objects()),
+            shares_source_with: Some(original.state.name.clone()),
+            ..desktop.config()
+        };
+
+        let first = desktop.set_up(clone_config());
+        let second = desktop.set_up(clone_config());
+
+        let (first, second) = (first.unwrap().state, second.unwrap().state);
+        assert_eq!(first.clone_of.as_deref(), Some("Firefox_Split"));
+        assert_eq!(second.clone_of.as_deref(), Some("Firefox_Split"));
+        assert_ne!(first.name, second.name);
+        assert!(first.original_links.is_empty());
+        // Another plain split of the source is still refused
+        assert!(matches!(
+            check_not_already_split(&desktop.audio_source(), None),
+            Err(PwSplitterError::SplitAlreadyExists(..))
+        ));
+    }
+
+    #[test]
+    fn dry_run_returns_the_plan_and_changes_nothing() {
+        let _env = TestEnv::new();
+        let desktop = Desktop::new();
+        let runner = Arc::new(DryRunMock {
+            mock: desktop.mock.clone(),
+            dry_run: DryRunRunner::default(),
+        });
+
+        let outcome = setup_split(desktop.config(), Duration::from_secs(1), runner.clone());
+
+        let Ok(SetupOutcome::DryRun(planned_links)) = outcome else {
+            panic!("expected a dry-run plan");
+        };
+        assert_eq!(planned_links.len(), 2);
+        assert!(
+            planned_links
+                .iter()
+                .any(|l| l.ends_with("Firefox_to_Local -> alsa_output.speakers"))
+        );
+        let commands = runner.dry_run.take_commands();
+        assert_eq!(
+            commands
+                .iter()
+                .filter(|c| c.starts_with("pw-loopback"))
+                .count(),
+            2
+        );
+        assert!(desktop.mock.calls().is_empty());
+        assert!(desktop.source_on_speakers());
+        assert!(SplitState::list_all().unwrap().is_empty());
+    }
+}
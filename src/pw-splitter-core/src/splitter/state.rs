@@ -0,0 +1,525 @@
+use crate::error::{PwSplitterError, Result};
+use crate::pipewire::{DestKind, LoopbackTuning};
+use crate::splitter::channel_map::ChannelMapping;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Fallback state directory, and the only one used by older versions
+const LEGACY_STATE_DIR: &str = "/tmp/pw-splitter";
+
+/// Environment variable overriding the state directory
+const STATE_DIR_ENV: &str = "PW_SPLITTER_STATE_DIR";
+
+/// Environment variable overriding the legacy state directory
+const LEGACY_STATE_DIR_ENV: &str = "PW_SPLITTER_LEGACY_STATE_DIR";
+
+/// Persistent state for an active split
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SplitState {
+    /// Unique name for this split (based on source name)
+    pub name: String,
+
+    /// Source node info
+    pub source_node_id: u32,
+    pub source_node_name: String,
+    pub source_application_name: String,
+    /// `object.serial` of the source node; missing in older state files
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_serial: Option<u64>,
+
+    /// One loopback per recording destination
+    #[serde(default)]
+    pub recordings: Vec<RecordingLoopback>,
+
+    /// Local loopback name (for reconnecting on restart)
+    pub local_loopback_name: String,
+
+    /// Original output (for restoration); the local copy's first sink
+    pub original_output_node_name: String,
+
+    /// Further sinks the local copy also plays on
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extra_output_node_names: Vec<String>,
+
+    /// Original links that were disconnected (for restoration)
+    pub original_links: Vec<SavedLink>,
+
+    /// PID of the local loopback process
+    pub loopback_to_local_pid: u32,
+
+    /// Timestamp when split was created
+    pub created_at: u64,
+
+    /// User-assigned tags for grouping splits (e.g. for bulk stop)
+    #[serde(default)]
+    pub tags: Vec<String>,
+
+    /// Volume of the local loopback (1.0 = 100%)
+    #[serde(default = "default_volume")]
+    pub local_volume: f32,
+
+    /// Volume of the recording loopbacks (1.0 = 100%), independent of the
+    /// local copy
+    #[serde(default = "default_volume")]
+    pub recording_volume: f32,
+
+    /// Local volume before the local copy was muted; `None` when not muted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub muted_volume: Option<f32>,
+
+    /// Per-channel gains of the local loopback on top of `local_volume`, in
+    /// its channel order; empty means all channels at full level
+    #[serde(default)]
+    pub local_channel_volumes: Vec<f32>,
+
+    /// Source channels feeding the recording loopbacks
+    #[serde(default)]
+    pub recording_channel_map: ChannelMapping,
+
+    /// Source channels feeding the local loopback
+    #[serde(default)]
+    pub local_channel_map: ChannelMapping,
+
+    /// Latency and rate the loopbacks were spawned with, reused on restart
+    #[serde(default)]
+    pub tuning: LoopbackTuning,
+
+    /// Custom `node.description` of the local loopback, reused on restart
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub local_description: Option<String>,
+
+    /// Links the split made through its loopbacks, destroyed on teardown
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub created_links: Vec<SavedLink>,
+
+    /// Split this one is a clone of; a clone leaves the source's links alone
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clone_of: Option<String>,
+}
+
+/// Description of the local loopback: the custom one, or `<app> -> Local`
+pub fn local_loopback_desc(custom: Option<&str>, application_name: &str) -> String {
+    custom.map_or_else(|| format!("{} -> Local", application_name), str::to_string)
+}
+
+fn default_volume() -> f32 {
+    1.0
+}
+
+/// A failed state write, with a hint for a read-only or full state directory
+fn write_error(what: &str, e: io::Error) -> PwSplitterError {
+    let hint = match e.kind() {
+        io::ErrorKind::ReadOnlyFilesystem | io::ErrorKind::PermissionDenied => {
+            format!("; set {} to a writable directory", STATE_DIR_ENV)
+        }
+        io::ErrorKind::StorageFull | io::ErrorKind::QuotaExceeded => {
+            format!("; free some space or set {} elsewhere", STATE_DIR_ENV)
+        }
+        _ => String::new(),
+    };
+    PwSplitterError::StateFileError(format!(
+        "{} in {}: {}{}",
+        what,
+        SplitState::state_dir().display(),
+        e,
+        hint
+    ))
+}
+
+/// A recording loopback and the destination it feeds
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordingLoopback {
+    pub loopback_name: String,
+    pub pid: u32,
+    pub dest_node_id: u32,
+    pub dest_media_name: String,
+    pub dest_application_name: String,
+    #[serde(default)]
+    pub dest_kind: DestKind,
+    /// `object.serial` of the destination node; missing in older state files
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dest_serial: Option<u64>,
+}
+
+/// Recording fields of state files written before multiple destinations
+#[derive(Deserialize)]
+struct LegacyRecording {
+    recording_loopback_name: String,
+    loopback_to_recording_pid: u32,
+    recording_dest_node_id: u32,
+    recording_dest_media_name: String,
+    recording_dest_application_name: String,
+    #[serde(default)]
+    recording_dest_kind: DestKind,
+}
+
+impl From<LegacyRecording> for RecordingLoopback {
+    fn from(legacy: LegacyRecording) -> Self {
+        Self {
+            loopback_name: legacy.recording_loopback_name,
+            pid: legacy.loopback_to_recording_pid,
+            dest_node_id: legacy.recording_dest_node_id,
+            dest_media_name: legacy.recording_dest_media_name,
+            dest_application_name: legacy.recording_dest_application_name,
+            dest_kind: legacy.recording_dest_kind,
+            dest_serial: None,
+        }
+    }
+}
+
+/// A link the split removed, to be recreated on teardown, or one it made
+///
+/// The ids and media names are missing from state files of older versions.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedLink {
+    /// Output as `node:port`, as pw-link takes it
+    pub output_port: String,
+    /// Input as `node:port`, as pw-link takes it
+    pub input_port: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_node_id: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_port_id: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_media_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_node_id: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_port_id: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_media_name: Option<String>,
+}
+
+impl SplitState {
+    /// Directory holding the state files
+    ///
+    /// Resolved from `$PW_SPLITTER_STATE_DIR`, then `$XDG_RUNTIME_DIR/pw-splitter`
+    /// (per-user, so splits of different users can't collide), then
+    /// the legacy directory.
+    pub fn state_dir() -> PathBuf {
+        if let Some(dir) = env::var_os(STATE_DIR_ENV).filter(|d| !d.is_empty()) {
+            return PathBuf::from(dir);
+        }
+        if let Some(runtime_dir) = env::var_os("XDG_RUNTIME_DIR").filter(|d| !d.is_empty()) {
+            return PathBuf::from(runtime_dir).join("pw-splitter");
+        }
+        Self::legacy_state_dir()
+    }
+
+    /// Directory older versions kept state files in, still read for their splits
+    ///
+    /// `/tmp/pw-splitter`, unless `$PW_SPLITTER_LEGACY_STATE_DIR` is set.
+    fn legacy_state_dir() -> PathBuf {
+        env::var_os(LEGACY_STATE_DIR_ENV)
+            .filter(|d| !d.is_empty())
+            .map_or_else(|| PathBuf::from(LEGACY_STATE_DIR), PathBuf::from)
+    }
+
+    /// Get the state file path for a split
+    pub fn state_file_path(name: &str) -> PathBuf {
+        Self::state_dir().join(format!("{}.json", name))
+    }
+
+    /// State file path in the legacy directory, for splits created by older versions
+    fn legacy_state_file_path(name: &str) -> PathBuf {
+        Self::legacy_state_dir().join(format!("{}.json", name))
+    }
+
+    /// Save state to file
+    ///
+    /// The JSON goes to `<name>.json.tmp` first and is renamed over the state
+    /// file, so a full disk or a crash mid-write leaves the previous state
+    /// intact rather than a truncated file that listing would skip.
+    pub fn save(&self) -> Result<()> {
+        // Ensure state directory exists
+        fs::create_dir_all(Self::state_dir())
+            .map_err(|e| write_error("Failed to create state dir", e))?;
+
+        let path = Self::state_file_path(&self.name);
+        let tmp_path = path.with_extension("json.tmp");
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(&tmp_path, json).map_err(|e| write_error("Failed to write state file", e))?;
+        fs::rename(&tmp_path, &path).map_err(|e| {
+            let _ = fs::remove_file(&tmp_path);
+            write_error("Failed to replace state file", e)
+        })?;
+
+        Ok(())
+    }
+
+    /// Load state from file, falling back to the legacy directory
+    pub fn load(name: &str) -> Result<Self> {
+        let mut path = Self::state_file_path(name);
+        if !path.exists() {
+            path = Self::legacy_state_file_path(name);
+            if !path.exists() {
+                return Err(PwSplitterError::SplitNotFound(name.to_string()));
+            }
+        }
+
+        let json = fs::read_to_string(&path).map_err(|e| {
+            PwSplitterError::StateFileError(format!("Failed to read state file: {}", e))
+        })?;
+        Self::from_json(&json)
+    }
+
+    /// Parse a state file, upgrading the single-destination format
+    fn from_json(json: &str) -> Result<Self> {
+        let value: serde_json::Value = serde_json::from_str(json)?;
+        let mut state: SplitState = serde_json::from_value(value.clone())?;
+        if state.recordings.is_empty()
+            && let Ok(legacy) = serde_json::from_value::<LegacyRecording>(value)
+        {
+            state.recordings.push(legacy.into());
+        }
+        Ok(state)
+    }
+
+    /// Delete state file (from both the current and legacy directory)
+    pub fn delete(&self) -> Result<()> {
+        for path in [
+            Self::state_file_path(&self.name),
+            Self::legacy_state_file_path(&self.name),
+        ] {
+            if path.exists() {
+                fs::remove_file(&path).map_err(|e| {
+                    PwSplitterError::StateFileError(format!("Failed to delete state file: {}", e))
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    /// List all active splits
+    ///
+    /// Splits left in the legacy directory by older versions are listed too.
+    pub fn list_all() -> Result<Vec<SplitState>> {
+        Self::list_merged(&Self::state_dir(), &Self::legacy_state_dir())
+    }
+
+    /// The splits in `state_dir`, then those only in `legacy_dir`
+    ///
+    /// A split in both is listed once, as stored in `state_dir`, which [`load`]
+    /// also prefers.
+    ///
+    /// [`load`]: Self::load
+    fn list_merged(state_dir: &Path, legacy_dir: &Path) -> Result<Vec<SplitState>> {
+        let mut states = Self::list_in(state_dir)?;
+        if state_dir != legacy_dir {
+            let legacy = Self::list_in(legacy_dir)?;
+            let names: HashSet<String> = states.iter().map(|s| s.name.clone()).collect();
+            states.extend(legacy.into_iter().filter(|s| !names.contains(&s.name)));
+        }
+        Ok(states)
+    }
+
+    /// List the splits stored in a single directory
+    fn list_in(state_dir: &Path) -> Result<Vec<SplitState>> {
+        if !state_dir.exists() {
+            return Ok(vec![]);
+        }
+
+        let mut states = Vec::new();
+        for entry in fs::read_dir(state_dir).map_err(|e| {
+            PwSplitterError::StateFileError(format!("Failed to read state dir: {}", e))
+        })? {
+            let entry = entry.map_err(|e| {
+                PwSplitterError::StateFileError(format!("Failed to read entry: {}", e))
+            })?;
+            let path = entry.path();
+            if path.extension().and_then(|s| s.to_str()) == Some("json")
+                && let Ok(json) = fs::read_to_string(&path)
+                && let Ok(state) = Self::from_json(&json)
+            {
+                states.push(state);
+            }
+        }
+
+        Ok(states)
+    }
+
+    /// Application names of all recording destinations, comma-separated
+    pub fn recording_dest_names(&self) -> String {
+        self.recordings
+            .iter()
+            .map(|r| r.dest_application_name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Node names of all loopbacks belonging to this split
+    pub fn loopback_names(&self) -> impl Iterator<Item = &str> {
+        self.recordings
+            .iter()
+            .map(|r| r.loopback_name.as_str())
+            .chain([self.local_loopback_name.as_str()])
+    }
+
+    /// Node names of every sink the local copy plays on, the first one first
+    pub fn local_output_names(&self) -> impl Iterator<Item = &str> {
+        [self.original_output_node_name.as_str()]
+            .into_iter()
+            .chain(self.extra_output_node_names.iter().map(String::as_str))
+    }
+
+    /// Channel mapping used by the loopback named `loopback_name`
+    pub fn channel_map(&self, loopback_name: &str) -> &ChannelMapping {
+        if loopback_name == self.local_loopback_name {
+            &self.local_channel_map
+        } else {
+            &self.recording_channel_map
+        }
+    }
+
+    /// Names of the other splits in `splits` also recording to `dest_node_id`
+    ///
+    /// Sharing a destination is allowed; the apps are mixed into one capture.
+    pub fn splits_sharing_dest<'a>(
+        &self,
+        splits: &'a [SplitState],
+        dest_node_id: u32,
+    ) -> Vec<&'a str> {
+        splits
+            .iter()
+            .filter(|s| s.name != self.name)
+            .filter(|s| s.recordings.iter().any(|r| r.dest_node_id == dest_node_id))
+            .map(|s| s.name.as_str())
+            .collect()
+    }
+
+    /// Time since the split was created
+    pub fn age(&self) -> Duration {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        Duration::from_secs(now.saturating_sub(self.created_at))
+    }
+
+    /// Check if this split carries the given tag
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+
+    /// Check if a split with this name already exists
+    pub fn exists(name: &str) -> bool {
+        Self::state_file_path(name).exists() || Self::legacy_state_file_path(name).exists()
+    }
+
+    /// Check a user-chosen name is usable as a state file name
+    pub fn validate_name(name: &str) -> Result<()> {
+        if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\']) {
+            return Err(PwSplitterError::InvalidSplitName(name.to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// Generate a unique name from `base_name`, suffixing `_1`, `_2`, ... while
+/// `is_taken` says the name is in use
+///
+/// Pass [`SplitState::exists`] for a split name.
+pub fn generate_unique_name(base_name: &str, is_taken: impl Fn(&str) -> bool) -> String {
+    let mut name = base_name.to_string();
+    let mut counter = 1;
+    while is_taken(&name) {
+        name = format!("{}_{}", base_name, counter);
+        counter += 1;
+    }
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipewire::mock::TestEnv;
+
+    /// A state file as written before volumes and multiple destinations
+    const OLD_STATE: &str = r#"{
+        "name": "Firefox_Split",
+        "source_node_id": 42,
+        "source_node_name": "Firefox",
+        "source_application_name": "Firefox",
+        "recording_loopback_name": "Firefox_to_Recording",
+        "loopback_to_recording_pid": 1234,
+        "recording_dest_node_id": 57,
+        "recording_dest_media_name": "Desktop",
+        "recording_dest_application_name": "OBS",
+        "local_loopback_name": "Firefox_to_Local",
+        "original_output_node_name": "alsa_output.speakers",
+        "original_links": [
+            { "output_port": "Firefox:output_FL", "input_port": "alsa_output.speakers:playback_FL" }
+        ],
+        "loopback_to_local_pid": 1235,
+        "created_at": 1700000000
+    }"#;
+
+    #[test]
+    fn old_state_file_loads_at_full_volume() {
+        let state = SplitState::from_json(OLD_STATE).unwrap();
+
+        assert_eq!(state.local_volume, 1.0);
+        assert_eq!(state.recording_volume, 1.0);
+        assert_eq!(state.muted_volume, None);
+        assert!(state.local_channel_volumes.is_empty());
+        assert_eq!(state.recordings.len(), 1);
+        assert_eq!(state.recordings[0].loopback_name, "Firefox_to_Recording");
+        assert_eq!(state.recordings[0].pid, 1234);
+        assert_eq!(state.original_links[0].output_node_id, None);
+    }
+
+    #[test]
+    fn legacy_splits_are_listed_alongside_new_ones() {
+        let env = TestEnv::new();
+        let (dir, legacy) = (
+            SplitState::state_dir(),
+            env.legacy_state_dir().to_path_buf(),
+        );
+        let write = |dir: &Path, name: &str, pid: u32| {
+            let mut state = SplitState::from_json(OLD_STATE).unwrap();
+            state.name = name.to_string();
+            state.loopback_to_local_pid = pid;
+            fs::create_dir_all(dir).unwrap();
+            fs::write(
+                dir.join(format!("{}.json", name)),
+                serde_json::to_string(&state).unwrap(),
+            )
+            .unwrap();
+        };
+        write(&dir, "Firefox_Split", 1);
+        write(&legacy, "Firefox_Split", 2);
+        write(&legacy, "Game_Split", 3);
+
+        let mut states = SplitState::list_all().unwrap();
+
+        states.sort_by(|a, b| a.name.cmp(&b.name));
+        let listed: Vec<(&str, u32)> = states
+            .iter()
+            .map(|s| (s.name.as_str(), s.loopback_to_local_pid))
+            .collect();
+        assert_eq!(listed, [("Firefox_Split", 1), ("Game_Split", 3)]);
+        let game = SplitState::load("Game_Split").unwrap();
+        assert!(SplitState::exists("Game_Split"));
+        game.delete().unwrap();
+        assert!(!legacy.join("Game_Split.json").exists());
+    }
+
+    #[test]
+    fn failed_save_keeps_the_previous_state() {
+        let env = TestEnv::new();
+        let mut state = SplitState::from_json(OLD_STATE).unwrap();
+        state.save().unwrap();
+
+        env.break_state_file(&state.name);
+        state.local_volume = 0.5;
+        assert!(state.save().is_err());
+
+        assert_eq!(SplitState::load(&state.name).unwrap().local_volume, 1.0);
+        assert_eq!(SplitState::list_all().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,568 @@
+//! Incremental split setup that can be advanced one step at a time.
+//!
+//! [`setup_split`](crate::splitter::setup_split) blocks for the whole setup,
+//! which would freeze an event loop. [`SplitSetup`] performs the same work,
+//! but each call to [`SplitSetup::advance`] runs at most one step and never
+//! sleeps, so a caller such as the TUI can render progress between steps.
+
+mod plan;
+mod verify;
+
+use crate::error::{PwSplitterError, Result};
+use crate::failure_log;
+use crate::pipewire::{self, CommandRunner, PwObject};
+use crate::splitter::graph::{self, PlannedSplit};
+use crate::splitter::links::{disconnect_source_from_target, restore_link};
+use crate::splitter::setup::{
+    SplitConfig, SplitResult, check_not_already_split, connect_loopback_to_sink,
+    connect_source_to_loopback, dropped_channels_warning, find_primary_output,
+    loopback_ports_ready, source_channels,
+};
+use crate::splitter::state::{SavedLink, SplitState, generate_unique_name, local_loopback_desc};
+use std::collections::HashSet;
+use std::process::Child;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// A step of the split setup
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetupStep {
+    /// Spawning the recording and local loopback processes
+    SpawningLoopbacks,
+    /// Waiting for the loopbacks to create their ports
+    WaitingForPorts,
+    /// Disconnecting the source and linking it through the loopbacks
+    Wiring,
+    /// Checking the loopbacks survived and persisting the split state
+    Verifying,
+}
+
+impl SetupStep {
+    /// All steps, in the order they run
+    pub const ALL: [SetupStep; 4] = [
+        SetupStep::SpawningLoopbacks,
+        SetupStep::WaitingForPorts,
+        SetupStep::Wiring,
+        SetupStep::Verifying,
+    ];
+
+    /// Human-readable description of the step
+    pub fn description(self) -> &'static str {
+        match self {
+            SetupStep::SpawningLoopbacks => "Spawning loopbacks…",
+            SetupStep::WaitingForPorts => "Waiting for loopback ports…",
+            SetupStep::Wiring => "Wiring…",
+            SetupStep::Verifying => "Verifying…",
+        }
+    }
+}
+
+/// Outcome of a single [`SplitSetup::advance`] call
+pub enum SetupProgress {
+    /// Setup is still running; the contained step runs on the next advance
+    InProgress(SetupStep),
+    /// Setup finished successfully
+    Complete(Box<SplitResult>),
+    /// A dry run stopped before changing anything, with the links a real run
+    /// would make through the loopbacks, e.g. `Firefox -> Firefox_to_Local -> speakers`
+    DryRun(Vec<String>),
+}
+
+/// A split setup in progress
+pub struct SplitSetup {
+    config: SplitConfig,
+    step: SetupStep,
+    split_name: String,
+    /// One per recording destination, in the same order
+    recording_loopback_names: Vec<String>,
+    local_loopback_name: String,
+    /// Sinks for the local copy, resolved when the loopbacks are spawned
+    local_output_names: Vec<String>,
+    loopbacks_to_recording: Vec<Child>,
+    loopback_to_local: Option<Child>,
+    spawned_at: Instant,
+    port_timeout: Duration,
+    runner: Arc<dyn CommandRunner>,
+    saved_links: Vec<SavedLink>,
+    warnings: Vec<String>,
+    /// Whether an advance completed the setup or its dry run
+    finished: bool,
+}
+
+/// Begin setting up a split without blocking
+///
+/// Nothing is spawned, and the split and its loopbacks aren't named, until
+/// the first call to [`SplitSetup::advance`].
+pub fn setup_split_stepped(config: SplitConfig) -> SplitSetup {
+    SplitSetup {
+        split_name: String::new(),
+        recording_loopback_names: Vec::new(),
+        local_loopback_name: String::new(),
+        config,
+        step: SetupStep::SpawningLoopbacks,
+        local_output_names: Vec::new(),
+        loopbacks_to_recording: Vec::new(),
+        loopback_to_local: None,
+        spawned_at: Instant::now(),
+        port_timeout: pipewire::DEFAULT_PORT_TIMEOUT,
+        runner: pipewire::runner(),
+        saved_links: Vec::new(),
+        warnings: Vec::new(),
+        finished: false,
+    }
+}
+
+/// `safe_name`, suffixed if needed so no loopback named after it would share
+/// its node name with another split's loopbacks, in `objects` or on record
+///
+/// Loopbacks of equal name would alias each other's ports, e.g. for two
+/// Firefox streams.
+pub(in crate::splitter) fn unique_loopback_prefix(
+    safe_name: &str,
+    recording_count: usize,
+    objects: &[PwObject],
+) -> String {
+    let mut taken: HashSet<String> = pipewire::node_names(objects);
+    for state in SplitState::list_all().unwrap_or_default() {
+        taken.extend(state.loopback_names().map(str::to_string));
+    }
+
+    generate_unique_name(safe_name, |prefix| {
+        recording_loopback_names(prefix, recording_count)
+            .into_iter()
+            .chain([format!("{}_to_Local", prefix)])
+            .any(|name| taken.contains(&name))
+    })
+}
+
+/// Name the recording loopbacks; the first keeps the single-destination name
+pub(in crate::splitter) fn recording_loopback_names(
+    source_safe_name: &str,
+    count: usize,
+) -> Vec<String> {
+    (0..count)
+        .map(|i| match i {
+            0 => format!("{}_to_Recording", source_safe_name),
+            _ => format!("{}_to_Recording_{}", source_safe_name, i + 1),
+        })
+        .collect()
+}
+
+impl SplitSetup {
+    /// Set how long to wait for the loopbacks' ports before failing
+    pub fn with_port_timeout(mut self, timeout: Duration) -> Self {
+        self.port_timeout = timeout;
+        self
+    }
+
+    /// Run every command of the setup through `runner` (by default, the
+    /// runner in effect when the setup was created)
+    pub fn with_runner(mut self, runner: Arc<dyn CommandRunner>) -> Self {
+        self.runner = runner;
+        self
+    }
+
+    /// The step that will run on the next [`advance`](Self::advance)
+    pub fn current_step(&self) -> SetupStep {
+        self.step
+    }
+
+    /// Run the current step and move on to the next one
+    ///
+    /// A failure rolls back everything done so far, and is recorded in the
+    /// error log with the failing step. Once the setup has finished, this
+    /// fails with [`PwSplitterError::SetupFinished`] and leaves the split be.
+    pub fn advance(&mut self) -> Result<SetupProgress> {
+        if self.finished {
+            return Err(PwSplitterError::SetupFinished);
+        }
+        let step = self.step;
+        tracing::trace!("split {}: {:?}", self.split_name, step);
+
+        let result = pipewire::with_runner(self.runner.clone(), || self.run_step());
+        self.finished = matches!(
+            result,
+            Ok(SetupProgress::Complete(_) | SetupProgress::DryRun(_))
+        );
+        if let Err(e) = &result {
+            pipewire::with_runner(self.runner.clone(), || self.roll_back());
+
+            let dests: Vec<String> = self
+                .config
+                .recording_dests
+                .iter()
+                .map(|d| format!("{} (node {})", d.display_name(), d.node_id))
+                .collect();
+            failure_log::record_setup_failure(
+                &format!(
+                    "{} (node {})",
+                    self.config.source.display_name(),
+                    self.config.source.node_id
+                ),
+                &dests,
+                step.description(),
+                e,
+            );
+        }
+        result
+    }
+
+    /// Abandon the setup, undoing everything done so far
+    ///
+    /// For a caller that stops advancing it midway, e.g. on exit; dropping it
+    /// instead would leave its loopbacks running and the source disconnected.
+    pub fn cancel(&mut self) {
+        pipewire::with_runner(self.runner.clone(), || self.roll_back());
+    }
+
+    /// Undo whatever a failed step left behind, making the setup all or nothing
+    ///
+    /// Every link the setup made has a loopback port at one end, so killing
+    /// the loopbacks removes those links with their nodes; the source is then
+    /// relinked to the outputs it was disconnected from.
+    fn roll_back(&mut self) {
+        let children = self
+            .loopbacks_to_recording
+            .drain(..)
+            .chain(self.loopback_to_local.take());
+        for mut child in children {
+            tracing::debug!("rolling back: killing pid {}", child.id());
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+        for link in self.saved_links.drain(..) {
+            if let Err(e) = restore_link(&link) {
+                tracing::warn!(
+                    "could not restore {} -> {}: {}",
+                    link.output_port,
+                    link.input_port,
+                    e
+                );
+            }
+        }
+    }
+
+    fn run_step(&mut self) -> Result<SetupProgress> {
+        match self.step {
+            SetupStep::SpawningLoopbacks => {
+                if let Some(planned_links) = self.spawn_loopbacks()? {
+                    return Ok(SetupProgress::DryRun(planned_links));
+                }
+            }
+            SetupStep::WaitingForPorts => self.check_ports()?,
+            SetupStep::Wiring => self.wire()?,
+            SetupStep::Verifying => return self.verify().map(SetupProgress::Complete),
+        }
+
+        Ok(SetupProgress::InProgress(self.step))
+    }
+
+    /// Pick the names of the split and its loopbacks, against the live graph
+    fn assign_names(&mut self, objects: &[PwObject]) {
+        let count = self.config.recording_dests.len();
+        let source_safe_name = unique_loopback_prefix(
+            self.config
+                .loopback_prefix
+                .as_deref()
+                .unwrap_or(&self.config.source.safe_name()),
+            count,
+            objects,
+        );
+        self.split_name =
+            generate_unique_name(&format!("{}_Split", source_safe_name), SplitState::exists);
+        // A clone's loopbacks would share their names with the original's, so
+        // they are named after the split instead
+        let loopback_prefix = match self.config.shares_source_with {
+            Some(_) => &self.split_name,
+            None => &source_safe_name,
+        };
+        self.recording_loopback_names = recording_loopback_names(loopback_prefix, count);
+        self.local_loopback_name = format!("{}_to_Local", loopback_prefix);
+    }
+
+    /// Spawn the loopbacks; in a dry run, stop there and return the links
+    /// a real run would make
+    fn spawn_loopbacks(&mut self) -> Result<Option<Vec<String>>> {
+        check_not_already_split(
+            &self.config.source,
+            self.config.shares_source_with.as_deref(),
+        )?;
+        self.config.tuning.validate()?;
+        self.warn_about_shared_dests();
+
+        let dest_ids: Vec<u32> = self
+            .config
+            .recording_dests
+            .iter()
+            .map(|d| d.node_id)
+            .collect();
+        if dest_ids.is_empty() {
+            return Err(PwSplitterError::NodeNotFound(
+                "no recording destination given".to_string(),
+            ));
+        }
+
+        // Local outputs: the user's picks, else the primary output connection (usually a sink)
+        let local_sink_node_ids: Vec<u32> = if self.config.local_sinks.is_empty() {
+            let primary_output = find_primary_output(&self.config.original_connections, &dest_ids)?;
+            self.local_output_names = vec![primary_output.target_node_name.clone()];
+            vec![primary_output.target_node_id]
+        } else {
+            self.local_output_names = self
+                .config
+                .local_sinks
+                .iter()
+                .map(|s| s.node_name.clone())
+                .collect();
+            self.config.local_sinks.iter().map(|s| s.node_id).collect()
+        };
+
+        // Check the planned routing before anything exists that would need cleaning up
+        let disconnected_target_ids: Vec<u32> = self
+            .config
+            .original_connections
+            .iter()
+            .map(|c| c.target_node_id)
+            .collect();
+        let objects = pipewire::get_pw_objects()?;
+        self.assign_names(&objects);
+        self.check_dests_have_inputs(&objects)?;
+        graph::check_for_feedback(
+            &objects,
+            &PlannedSplit {
+                source_node_id: self.config.source.node_id,
+                disconnected_target_ids: &disconnected_target_ids,
+                recording_dest_node_ids: &dest_ids,
+                local_sink_node_ids: &local_sink_node_ids,
+            },
+        )?;
+
+        // Loopbacks mirror the source's channel map so surround isn't downmixed
+        let channels = source_channels(self.config.source.node_id)?;
+        // A dry run records each spawn and has no child to keep
+        let spawn = |name: &str, desc: &str| {
+            pipewire::spawn_loopback_no_target(
+                &self.split_name,
+                name,
+                desc,
+                &channels,
+                &self.config.tuning,
+            )
+        };
+
+        // Recording loopbacks (full volume); no autoconnect on either side,
+        // everything is linked manually
+        for (dest, name) in self
+            .config
+            .recording_dests
+            .iter()
+            .zip(&self.recording_loopback_names)
+        {
+            let recording_loopback_desc = format!(
+                "{} -> {}",
+                self.config.source.application_name, dest.application_name
+            );
+            self.loopbacks_to_recording
+                .extend(spawn(name, &recording_loopback_desc)?);
+        }
+
+        // Local/original output loopback (adjustable volume)
+        let local_loopback_desc = local_loopback_desc(
+            self.config.local_description.as_deref(),
+            &self.config.source.application_name,
+        );
+        self.loopback_to_local = spawn(&self.local_loopback_name, &local_loopback_desc)?;
+
+        if pipewire::is_dry_run() {
+            return self.plan_wiring().map(Some);
+        }
+
+        self.spawned_at = Instant::now();
+        self.step = SetupStep::WaitingForPorts;
+        Ok(None)
+    }
+
+    /// Check once for the loopback ports; the caller decides how often to poll
+    fn check_ports(&mut self) -> Result<()> {
+        let objects = pipewire::get_fresh_pw_objects()?;
+        let names = || {
+            self.recording_loopback_names
+                .iter()
+                .chain([&self.local_loopback_name])
+        };
+
+        // A freshly opened app may create its ports only once it plays
+        let source_ready = !pipewire::ports_in_order(
+            &pipewire::extract_ports(&objects),
+            self.config.source.node_id,
+            pipewire::PortDirection::Output,
+        )
+        .is_empty();
+
+        if source_ready && names().all(|name| loopback_ports_ready(&objects, name)) {
+            tracing::debug!(
+                "loopback ports of {} ready after {:?}",
+                self.split_name,
+                self.spawned_at.elapsed()
+            );
+            self.step = SetupStep::Wiring;
+        } else if self.spawned_at.elapsed() >= self.port_timeout {
+            if !source_ready {
+                return Err(PwSplitterError::SourceNotPlaying(
+                    self.config.source.display_name(),
+                ));
+            }
+            tracing::warn!(
+                "loopback ports of {} missing after {:?}",
+                self.split_name,
+                self.port_timeout
+            );
+            return Err(PwSplitterError::PortsTimeout(format!(
+                "loopbacks {}",
+                names().cloned().collect::<Vec<_>>().join(", ")
+            )));
+        }
+        Ok(())
+    }
+
+    fn wire(&mut self) -> Result<()> {
+        // Disconnect source from all current outputs; re-read them, as the
+        // source may have gained targets (e.g. another recorder) since the
+        // config was built. A clone leaves them to the split it shares with.
+        let objects = pipewire::get_pw_objects()?;
+        let connections = match self.config.shares_source_with {
+            Some(_) => Vec::new(),
+            None => pipewire::find_source_connections(self.config.source.node_id, &objects),
+        };
+        for conn in &connections {
+            if let Some(links) = disconnect_source_from_target(&self.config.source, conn, &objects)
+            {
+                self.saved_links.extend(links);
+            }
+        }
+
+        // Each recording loopback: source -> loopback -> destination (by port ID
+        // to avoid ambiguity)
+        for (dest, name) in self
+            .config
+            .recording_dests
+            .iter()
+            .zip(&self.recording_loopback_names)
+        {
+            connect_source_to_loopback(
+                self.config.source.node_id,
+                name,
+                &self.config.recording_channel_map,
+            )?;
+            let dropped = pipewire::connect_loopback_to_recording_dest(name, dest.node_id)?;
+            self.warnings
+                .extend(dropped_channels_warning(&dest.application_name, &dropped));
+        }
+
+        connect_source_to_loopback(
+            self.config.source.node_id,
+            &self.local_loopback_name,
+            &self.config.local_channel_map,
+        )?;
+
+        // Local loopback -> speakers (and any further local outputs)
+        for sink_name in &self.local_output_names {
+            let dropped = connect_loopback_to_sink(&self.local_loopback_name, sink_name)?;
+            self.warnings
+                .extend(dropped_channels_warning(sink_name, &dropped));
+        }
+
+        self.step = SetupStep::Verifying;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipewire::mock::{Desktop, TestEnv, is_alive};
+    use serde_json::json;
+
+    /// Advance `setup` until it finishes or fails
+    fn run(setup: &mut SplitSetup) -> Result<Box<SplitResult>> {
+        loop {
+            if let SetupProgress::Complete(result) = setup.advance()? {
+                return Ok(result);
+            }
+        }
+    }
+
+    #[test]
+    fn failure_partway_through_rolls_back() {
+        let _env = TestEnv::new();
+        let desktop = Desktop::new();
+        // The source is already linked into the loopbacks by then
+        desktop.mock.fail("pw-link Firefox_to_Recording:output_FL");
+
+        let mut setup = setup_split_stepped(desktop.config()).with_runner(desktop.mock.clone());
+        let error = run(&mut setup).err().unwrap();
+
+        assert!(matches!(error, PwSplitterError::LinkCreationFailed(_)));
+        assert_eq!(setup.current_step(), SetupStep::Wiring);
+        let spawned = desktop.mock.spawned();
+        assert_eq!(spawned.len(), 2);
+        assert!(spawned.iter().all(|&pid| !is_alive(pid)));
+        assert!(desktop.source_on_speakers());
+        assert!(setup.saved_links.is_empty());
+        assert!(SplitState::list_all().unwrap().is_empty());
+    }
+
+    #[test]
+    fn source_without_ports_is_not_playing() {
+        let _env = TestEnv::new();
+        let desktop = Desktop::new();
+        // Opened, but hasn't played anything yet
+        let game = desktop.mock.add_node(
+            "Game",
+            "Stream/Output/Audio",
+            json!({ "application.name": "Game" }),
+        );
+        let objects = desktop.mock.objects();
+        let mut config = desktop.config();
+        config.source = pipewire::extract_audio_sources(&objects, Default::default())
+            .into_iter()
+            .find(|s| s.node_id == game)
+            .unwrap();
+        config.original_connections = Vec::new();
+        config.local_sinks = pipewire::extract_audio_sinks(&objects);
+        assert_eq!(config.source.channel_count, 0);
+
+        let mut setup = setup_split_stepped(config)
+            .with_port_timeout(Duration::ZERO)
+            .with_runner(desktop.mock.clone());
+        let error = run(&mut setup).err().unwrap();
+
+        assert!(matches!(error, PwSplitterError::SourceNotPlaying(name) if name == "Game [Audio]"));
+        assert_eq!(setup.current_step(), SetupStep::WaitingForPorts);
+        assert!(desktop.mock.spawned().iter().all(|&pid| !is_alive(pid)));
+        assert!(
+            !desktop
+                .mock
+                .calls()
+                .iter()
+                .any(|c| c.starts_with("pw-link"))
+        );
+    }
+
+    #[test]
+    fn cancel_midway_rolls_back() {
+        let _env = TestEnv::new();
+        let desktop = Desktop::new();
+        let mut setup = setup_split_stepped(desktop.config()).with_runner(desktop.mock.clone());
+        while setup.current_step() != SetupStep::Verifying {
+            setup.advance().unwrap();
+        }
+        assert!(!desktop.source_on_speakers());
+
+        setup.cancel();
+
+        assert!(desktop.source_on_speakers());
+        assert!(desktop.mock.spawned().iter().all(|&pid| !is_alive(pid)));
+        assert!(SplitState::list_all().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,84 @@
+//! Describing a setup before it changes anything: checks and warnings about
+//! the planned routing and, in a dry run, the wiring a real run would make.
+
+use super::SplitSetup;
+use crate::error::{PwSplitterError, Result};
+use crate::pipewire::{self, PortDirection, PwObject};
+use crate::splitter::links::disconnect_source_from_target;
+use crate::splitter::state::SplitState;
+
+impl SplitSetup {
+    /// Warn when another split already records to one of our destinations,
+    /// as both apps will then be mixed into one capture
+    pub(super) fn warn_about_shared_dests(&mut self) {
+        let splits = SplitState::list_all().unwrap_or_default();
+        for dest in &self.config.recording_dests {
+            let sharing: Vec<&str> = splits
+                .iter()
+                .filter(|s| s.recordings.iter().any(|r| r.dest_node_id == dest.node_id))
+                .map(|s| s.name.as_str())
+                .collect();
+            if !sharing.is_empty() {
+                self.warnings.push(format!(
+                    "{} already records {}; their audio will be mixed",
+                    dest.display_name(),
+                    sharing.join(", ")
+                ));
+            }
+        }
+    }
+
+    /// Fail unless every destination has input ports to link the loopbacks to
+    ///
+    /// An idle capture stream can lack them; finding out only when wiring
+    /// would mean spawning loopbacks for a setup that can't succeed.
+    pub(super) fn check_dests_have_inputs(&self, objects: &[PwObject]) -> Result<()> {
+        let ports = pipewire::extract_ports(objects);
+        for dest in &self.config.recording_dests {
+            if pipewire::routable_ports(&ports, dest.node_id, PortDirection::Input).is_empty() {
+                return Err(PwSplitterError::DestHasNoInputs(dest.display_name()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Run the disconnections, which a dry run only prints, and describe the
+    /// links a real run would make
+    ///
+    /// The loopbacks have no ports in a dry run, so links through them can't
+    /// be shown as pw-link commands.
+    pub(super) fn plan_wiring(&self) -> Result<Vec<String>> {
+        let source = &self.config.source;
+        // A clone leaves the source's links to the split it shares with
+        if self.config.shares_source_with.is_none() {
+            let objects = pipewire::get_pw_objects()?;
+            for conn in &pipewire::find_source_connections(source.node_id, &objects) {
+                disconnect_source_from_target(source, conn, &objects);
+            }
+        }
+
+        let recordings = self
+            .config
+            .recording_dests
+            .iter()
+            .zip(&self.recording_loopback_names)
+            .map(|(dest, name)| {
+                format!(
+                    "{} -> {} -> {} (node {})",
+                    source.display_name(),
+                    name,
+                    dest.display_name(),
+                    dest.node_id
+                )
+            });
+        let local = self.local_output_names.iter().map(|sink_name| {
+            format!(
+                "{} -> {} -> {}",
+                source.display_name(),
+                self.local_loopback_name,
+                sink_name
+            )
+        });
+        Ok(recordings.chain(local).collect())
+    }
+}
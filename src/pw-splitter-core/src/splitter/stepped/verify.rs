@@ -0,0 +1,206 @@
+//! The last setup step: checking the split works, then saving its state.
+//!
+//! pw-link can succeed without the link surviving, e.g. when the destination
+//! rejects it or goes away mid-setup. Without checking each recording link the
+//! split would be reported as active while the recording stays silent.
+
+use super::SplitSetup;
+use crate::error::{PwSplitterError, Result};
+use crate::pipewire;
+use crate::splitter::links::record_created_links;
+use crate::splitter::setup::{SplitResult, find_loopback_playback_node};
+use crate::splitter::state::{RecordingLoopback, SplitState};
+use crate::splitter::volume;
+use std::process::Child;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+impl SplitSetup {
+    /// Check the split works and save its state
+    ///
+    /// On failure the children are handed back, so [`advance`](Self::advance)
+    /// rolls this step back like any other.
+    pub(super) fn verify(&mut self) -> Result<Box<SplitResult>> {
+        let mut loopback_to_local = self
+            .loopback_to_local
+            .take()
+            .expect("the local loopback is spawned before verifying");
+        let mut loopbacks_to_recording = std::mem::take(&mut self.loopbacks_to_recording);
+
+        match self.finish(&mut loopbacks_to_recording, &mut loopback_to_local) {
+            Ok(state) => Ok(Box::new(SplitResult {
+                state,
+                loopbacks_to_recording,
+                loopback_to_local,
+                warnings: std::mem::take(&mut self.warnings),
+            })),
+            Err(e) => {
+                self.loopbacks_to_recording = loopbacks_to_recording;
+                self.loopback_to_local = Some(loopback_to_local);
+                Err(e)
+            }
+        }
+    }
+
+    fn finish(
+        &mut self,
+        loopbacks_to_recording: &mut [Child],
+        loopback_to_local: &mut Child,
+    ) -> Result<SplitState> {
+        let children = loopbacks_to_recording
+            .iter_mut()
+            .zip(&self.recording_loopback_names)
+            .chain([(&mut *loopback_to_local, &self.local_loopback_name)]);
+        for (child, name) in children {
+            if let Ok(Some(status)) = child.try_wait() {
+                return Err(PwSplitterError::LoopbackSpawnFailed(format!(
+                    "{} exited during setup ({})",
+                    name, status
+                )));
+            }
+        }
+
+        self.check_recordings_linked()?;
+
+        let config = &self.config;
+        let recordings = config
+            .recording_dests
+            .iter()
+            .zip(&self.recording_loopback_names)
+            .zip(loopbacks_to_recording.iter())
+            .map(|((dest, name), child)| RecordingLoopback {
+                loopback_name: name.clone(),
+                pid: child.id(),
+                dest_node_id: dest.node_id,
+                dest_media_name: dest.media_name.clone(),
+                dest_application_name: dest.application_name.clone(),
+                dest_kind: dest.kind,
+                dest_serial: dest.serial,
+            })
+            .collect();
+        let mut state = SplitState {
+            name: self.split_name.clone(),
+            source_node_id: config.source.node_id,
+            source_node_name: config.source.node_name.clone(),
+            source_application_name: config.source.application_name.clone(),
+            source_serial: config.source.serial,
+            recordings,
+            local_loopback_name: self.local_loopback_name.clone(),
+            original_output_node_name: self.local_output_names[0].clone(),
+            extra_output_node_names: self.local_output_names[1..].to_vec(),
+            original_links: self.saved_links.clone(),
+            loopback_to_local_pid: loopback_to_local.id(),
+            created_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            tags: Vec::new(),
+            local_volume: config.local_volume,
+            recording_volume: config.recording_volume,
+            muted_volume: None,
+            local_channel_volumes: Vec::new(),
+            recording_channel_map: config.recording_channel_map.clone(),
+            local_channel_map: config.local_channel_map.clone(),
+            tuning: config.tuning.clone(),
+            local_description: config.local_description.clone(),
+            created_links: Vec::new(),
+            clone_of: config.shares_source_with.clone(),
+        };
+        record_created_links(&mut state);
+
+        // Loopbacks start at 100%
+        volume::reapply_local_volume(&state)?;
+        for recording in &state.recordings {
+            volume::reapply_recording_volume(&recording.loopback_name, state.recording_volume)?;
+        }
+
+        // Without a state file nothing could stop these loopbacks or restore
+        // the source's links later, so failing here undoes the split rather
+        // than orphan it
+        state.save()?;
+
+        // The split now owns the original links; nothing is left to roll back
+        self.saved_links.clear();
+        Ok(state)
+    }
+
+    /// Confirm every recording loopback is linked to its destination,
+    /// relinking a missing one once before giving up
+    fn check_recordings_linked(&self) -> Result<()> {
+        for (dest, name) in self
+            .config
+            .recording_dests
+            .iter()
+            .zip(&self.recording_loopback_names)
+        {
+            if recording_linked(name, dest.node_id)? {
+                continue;
+            }
+            tracing::warn!(
+                "{} isn't linked to node {} after setup, relinking",
+                name,
+                dest.node_id
+            );
+            pipewire::connect_loopback_to_recording_dest(name, dest.node_id)?;
+            if !recording_linked(name, dest.node_id)? {
+                return Err(PwSplitterError::RecordingNotLinked(
+                    name.clone(),
+                    dest.display_name(),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether any port of the loopback's playback side is linked into `dest_node_id`
+fn recording_linked(loopback_name: &str, dest_node_id: u32) -> Result<bool> {
+    let objects = pipewire::get_fresh_pw_objects()?;
+    let Some(playback) = find_loopback_playback_node(&objects, loopback_name) else {
+        return Ok(false);
+    };
+    Ok(pipewire::extract_links(&objects)
+        .iter()
+        .any(|l| l.output_node_id == playback && l.input_node_id == dest_node_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::PwSplitterError;
+    use crate::pipewire::mock::{Desktop, TestEnv, is_alive};
+    use crate::splitter::state::SplitState;
+    use crate::splitter::stepped::{SetupProgress, setup_split_stepped};
+
+    #[test]
+    fn failed_save_kills_children_and_restores_links() {
+        let env = TestEnv::new();
+        let desktop = Desktop::new();
+        env.break_state_file("Firefox_Split");
+
+        let result = desktop.set_up(desktop.config());
+
+        assert!(matches!(result, Err(PwSplitterError::StateFileError(_))));
+        let spawned = desktop.mock.spawned();
+        assert_eq!(spawned.len(), 2);
+        assert!(spawned.iter().all(|&pid| !is_alive(pid)));
+        assert!(desktop.source_on_speakers());
+    }
+
+    #[test]
+    fn advancing_a_finished_setup_leaves_the_split_be() {
+        let _env = TestEnv::new();
+        let desktop = Desktop::new();
+        let mut setup = setup_split_stepped(desktop.config()).with_runner(desktop.mock.clone());
+        let result = loop {
+            if let SetupProgress::Complete(result) = setup.advance().unwrap() {
+                break result;
+            }
+        };
+
+        let again = setup.advance();
+
+        assert!(matches!(again, Err(PwSplitterError::SetupFinished)));
+        assert!(desktop.mock.spawned().iter().all(|&pid| is_alive(pid)));
+        assert!(!desktop.source_on_speakers());
+        assert!(SplitState::exists(&result.state.name));
+    }
+}
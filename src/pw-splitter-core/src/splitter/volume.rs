@@ -0,0 +1,318 @@
+use crate::error::{PwSplitterError, Result};
+use crate::pipewire;
+use crate::splitter::setup::find_loopback_playback_node;
+use crate::splitter::state::SplitState;
+
+/// Highest accepted volume; above 100% PipeWire amplifies in software
+pub const MAX_VOLUME: f32 = 1.5;
+
+/// Volume change per keypress in the TUI
+pub const VOLUME_STEP: f32 = 0.05;
+
+/// Balance change per keypress in the TUI
+pub const BALANCE_STEP: f32 = 0.1;
+
+/// Reject volumes outside `0.0..=MAX_VOLUME`
+pub fn validate_volume(volume: f32) -> Result<f32> {
+    if (0.0..=MAX_VOLUME).contains(&volume) {
+        Ok(volume)
+    } else {
+        Err(PwSplitterError::InvalidVolume(volume))
+    }
+}
+
+/// Set the volume of a loopback's playback side
+pub fn set_loopback_volume(loopback_name: &str, volume: f32) -> Result<()> {
+    let volume = validate_volume(volume)?;
+    let objects = pipewire::get_pw_objects()?;
+    let node_id = find_loopback_playback_node(&objects, loopback_name).ok_or_else(|| {
+        PwSplitterError::NodeNotFound(format!("loopback playback {}", loopback_name))
+    })?;
+
+    pipewire::set_node_volume(node_id, volume)
+}
+
+/// Set the local (monitor) volume of a split; the recording side is untouched
+///
+/// The new volume is persisted so it can be re-applied after a restart.
+/// Setting a volume while muted unmutes.
+pub fn set_local_volume(state: &mut SplitState, volume: f32) -> Result<()> {
+    let volume = validate_volume(volume)?;
+    let previous = state.local_volume;
+    state.local_volume = volume;
+    if let Err(e) = write_local_volume(state) {
+        state.local_volume = previous;
+        return Err(e);
+    }
+    state.muted_volume = None;
+    state.save()
+}
+
+/// Set the volume of what a split sends to its recording destinations; the
+/// local copy is untouched
+///
+/// The new volume is persisted so it can be re-applied after a restart.
+pub fn set_recording_volume(state: &mut SplitState, volume: f32) -> Result<()> {
+    let volume = validate_volume(volume)?;
+    for recording in &state.recordings {
+        set_loopback_volume(&recording.loopback_name, volume)?;
+    }
+    state.recording_volume = volume;
+    state.save()
+}
+
+/// Re-apply a split's recording volume to the recording loopback
+/// `loopback_name`, e.g. a respawned one
+///
+/// Loopbacks start at 100%, so nothing is done for that volume.
+pub fn reapply_recording_volume(loopback_name: &str, volume: f32) -> Result<()> {
+    if volume == 1.0 {
+        return Ok(());
+    }
+    set_loopback_volume(loopback_name, volume)
+}
+
+/// Silence the local copy, keeping the recording feed
+///
+/// The volume is remembered for [`unmute_local`]. Returns `false` if the
+/// split was already muted.
+pub fn mute_local(state: &mut SplitState) -> Result<bool> {
+    if state.muted_volume.is_some() {
+        return Ok(false);
+    }
+    let volume = state.local_volume;
+    state.local_volume = 0.0;
+    if let Err(e) = write_local_volume(state) {
+        state.local_volume = volume;
+        return Err(e);
+    }
+    state.muted_volume = Some(volume);
+    state.save()?;
+    Ok(true)
+}
+
+/// Restore the local volume saved by [`mute_local`]
+///
+/// Returns `false` if the split wasn't muted.
+pub fn unmute_local(state: &mut SplitState) -> Result<bool> {
+    let Some(volume) = state.muted_volume else {
+        return Ok(false);
+    };
+    state.local_volume = volume;
+    if let Err(e) = write_local_volume(state) {
+        state.local_volume = 0.0;
+        return Err(e);
+    }
+    state.muted_volume = None;
+    state.save()?;
+    Ok(true)
+}
+
+/// Shift the local copy's balance by `delta`, returning the new balance
+///
+/// Balance runs from -1.0 (left only) to 1.0 (right only); the far side is
+/// attenuated while the near side stays at full level. Channels that are
+/// neither left nor right (center, LFE, mono) are left alone.
+pub fn shift_local_balance(state: &mut SplitState, delta: f32) -> Result<f32> {
+    let channels = local_channels(state)?;
+    let current =
+        channel_gain(state, &channels, Side::Right) - channel_gain(state, &channels, Side::Left);
+
+    // Snap to whole steps so repeated presses don't accumulate float drift
+    let steps = ((current + delta) / BALANCE_STEP).round();
+    let balance = (steps * BALANCE_STEP).clamp(-1.0, 1.0);
+    let gains = balance_gains(&channels, balance);
+    let previous = std::mem::replace(&mut state.local_channel_volumes, gains);
+    if let Err(e) = write_local_volume(state) {
+        state.local_channel_volumes = previous;
+        return Err(e);
+    }
+    state.save()?;
+    Ok(balance)
+}
+
+/// Re-apply a split's saved local volume and balance, e.g. to a respawned loopback
+///
+/// Loopbacks start at 100% and centered, so nothing is done for that setting.
+pub fn reapply_local_volume(state: &SplitState) -> Result<()> {
+    if state.local_volume == 1.0 && state.local_channel_volumes.is_empty() {
+        return Ok(());
+    }
+    write_local_volume(state)
+}
+
+/// Push the local volume and channel gains to the local loopback
+fn write_local_volume(state: &SplitState) -> Result<()> {
+    if state.local_channel_volumes.is_empty() {
+        return set_loopback_volume(&state.local_loopback_name, state.local_volume);
+    }
+
+    // wpctl volumes are cubic, channelVolumes are linear; keep both consistent
+    let master = state.local_volume.powi(3);
+    let volumes: Vec<f32> = state
+        .local_channel_volumes
+        .iter()
+        .map(|gain| master * gain)
+        .collect();
+    let objects = pipewire::get_pw_objects()?;
+    pipewire::set_node_channel_volumes(local_playback_node(state, &objects)?, &volumes)
+}
+
+/// Side of the stereo image a channel belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Side {
+    Left,
+    Right,
+}
+
+/// Per-channel gains for `balance`, in the order of `channels`; none when centered
+fn balance_gains(channels: &[String], balance: f32) -> Vec<f32> {
+    if balance == 0.0 {
+        return Vec::new();
+    }
+    let (left, right) = ((1.0 - balance).min(1.0), (1.0 + balance).min(1.0));
+    channels
+        .iter()
+        .map(|c| match channel_side(c) {
+            Some(Side::Left) => left,
+            Some(Side::Right) => right,
+            None => 1.0,
+        })
+        .collect()
+}
+
+/// Side of a PipeWire channel position, e.g. FL, SL and FLC are left;
+/// center, LFE, mono and AUX channels have none
+fn channel_side(channel: &str) -> Option<Side> {
+    match channel {
+        "FL" | "SL" | "RL" | "FLC" | "RLC" | "FLW" | "FLH" | "TFL" | "TFLC" | "TSL" | "TRL"
+        | "BLC" | "LLFE" => Some(Side::Left),
+        "FR" | "SR" | "RR" | "FRC" | "RRC" | "FRW" | "FRH" | "TFR" | "TFRC" | "TSR" | "TRR"
+        | "BRC" | "RLFE" => Some(Side::Right),
+        _ => None,
+    }
+}
+
+/// Gain of the first channel on `side`, or full level if unset
+fn channel_gain(state: &SplitState, channels: &[String], side: Side) -> f32 {
+    channels
+        .iter()
+        .zip(&state.local_channel_volumes)
+        .find(|(c, _)| channel_side(c) == Some(side))
+        .map_or(1.0, |(_, gain)| *gain)
+}
+
+/// Channel layout of the local loopback's playback side
+fn local_channels(state: &SplitState) -> Result<Vec<String>> {
+    let objects = pipewire::get_pw_objects()?;
+    let node_id = local_playback_node(state, &objects)?;
+    Ok(pipewire::channel_layout(
+        &pipewire::extract_ports(&objects),
+        node_id,
+        pipewire::PortDirection::Output,
+    ))
+}
+
+fn local_playback_node(state: &SplitState, objects: &[pipewire::PwObject]) -> Result<u32> {
+    find_loopback_playback_node(objects, &state.local_loopback_name).ok_or_else(|| {
+        PwSplitterError::NodeNotFound(format!("loopback playback {}", state.local_loopback_name))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipewire::mock::{Desktop, TestEnv};
+
+    #[test]
+    fn failed_volume_change_keeps_the_state() {
+        let _env = TestEnv::new();
+        let desktop = Desktop::new();
+        let mut state = desktop.set_up(desktop.config()).unwrap().state;
+        pipewire::with_runner(desktop.mock.clone(), || mute_local(&mut state)).unwrap();
+        desktop.mock.fail("wpctl set-volume");
+
+        let result =
+            pipewire::with_runner(desktop.mock.clone(), || set_local_volume(&mut state, 0.5));
+
+        assert!(matches!(result, Err(PwSplitterError::CommandFailed(_))));
+        assert_eq!(state.local_volume, 0.0);
+        assert_eq!(state.muted_volume, Some(1.0));
+        let saved = SplitState::load(&state.name).unwrap();
+        assert_eq!(saved.local_volume, 0.0);
+        assert_eq!(saved.muted_volume, Some(1.0));
+    }
+
+    fn layout(channels: &[&str]) -> Vec<String> {
+        channels.iter().map(|c| c.to_string()).collect()
+    }
+
+    #[test]
+    fn balance_pans_every_side_channel_of_7_1() {
+        let channels = layout(&["FL", "FR", "FC", "LFE", "RL", "RR", "SL", "SR"]);
+
+        let gains = balance_gains(&channels, 0.5);
+
+        assert_eq!(gains, [0.5, 1.0, 1.0, 1.0, 0.5, 1.0, 0.5, 1.0]);
+    }
+
+    #[test]
+    fn balance_pans_every_side_channel_of_5_1_side() {
+        let channels = layout(&["FL", "FR", "FC", "LFE", "SL", "SR"]);
+
+        let gains = balance_gains(&channels, -0.5);
+
+        assert_eq!(gains, [1.0, 0.5, 1.0, 1.0, 1.0, 0.5]);
+    }
+
+    #[test]
+    fn inner_and_wide_channels_have_a_side() {
+        for (channel, side) in [
+            ("FLC", Side::Left),
+            ("FRC", Side::Right),
+            ("RLC", Side::Left),
+            ("RRC", Side::Right),
+            ("FLW", Side::Left),
+            ("FRW", Side::Right),
+        ] {
+            assert_eq!(channel_side(channel), Some(side), "{}", channel);
+        }
+        for channel in ["FC", "RC", "LFE", "MONO", "AUX0"] {
+            assert_eq!(channel_side(channel), None, "{}", channel);
+        }
+    }
+
+    #[test]
+    fn balance_shifts_in_steps_and_recenters() {
+        let _env = TestEnv::new();
+        let desktop = Desktop::new();
+        let mut state = desktop.set_up(desktop.config()).unwrap().state;
+        let shift = |state: &mut SplitState, delta| {
+            pipewire::with_runner(desktop.mock.clone(), || shift_local_balance(state, delta))
+                .unwrap()
+        };
+
+        assert_eq!(shift(&mut state, -BALANCE_STEP), -0.1);
+        assert_eq!(shift(&mut state, -BALANCE_STEP), -0.2);
+        assert_eq!(state.local_channel_volumes, [1.0, 0.8]);
+        assert!(
+            desktop
+                .mock
+                .calls()
+                .last()
+                .unwrap()
+                .ends_with("'{ channelVolumes: [ 1.0000, 0.8000 ] }'")
+        );
+        assert_eq!(shift(&mut state, 5.0), 1.0);
+        assert_eq!(state.local_channel_volumes, [0.0, 1.0]);
+
+        assert_eq!(shift(&mut state, -1.0), 0.0);
+        assert!(state.local_channel_volumes.is_empty());
+        assert!(
+            SplitState::load(&state.name)
+                .unwrap()
+                .local_channel_volumes
+                .is_empty()
+        );
+    }
+}
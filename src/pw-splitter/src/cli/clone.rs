@@ -0,0 +1,105 @@
+//! The `clone` subcommand: record an already split source to one more destination.
+//!
+//! The clone is a split of its own, so it can be stopped separately, but it
+//! leaves the original's links alone. Its local copy starts at 0%, as the
+//! original already plays the source locally.
+
+use crate::cli::{self, start};
+use crate::config::Config;
+use crate::error::{CliError, PwSplitterError, Result};
+use crate::pipewire::{self, AudioSink, SourceClasses};
+use crate::splitter::{self, SetupOutcome, SplitConfig, SplitState};
+
+/// Clone the split `existing` onto the destination `dest_query`, printing
+/// the new split's name
+pub fn run(existing: &str, dest_query: &str) -> Result<()> {
+    let config = Config::load()?;
+    let original = SplitState::load(existing)?;
+    let objects = pipewire::get_pw_objects()?;
+
+    // Whatever kind of node the original split, it's a valid source now
+    let any_class = SourceClasses {
+        include_devices: true,
+        include_sink_monitors: true,
+    };
+    let source = pipewire::extract_audio_sources(&objects, any_class)
+        .into_iter()
+        .find(|s| {
+            s.node_id == original.source_node_id
+                && s.node_name == original.source_node_name
+                && pipewire::serial_matches(original.source_serial, s.serial)
+        })
+        .ok_or_else(|| {
+            PwSplitterError::NodeNotFound(format!(
+                "source of {} ({}); try 'reattach {}' first",
+                existing, original.source_application_name, existing
+            ))
+        })?;
+
+    let destinations = pipewire::extract_recording_dests(&objects);
+    let dest = start::resolve(dest_query, "recording destination", &destinations)?.clone();
+    if original
+        .recordings
+        .iter()
+        .any(|r| r.dest_node_id == dest.node_id)
+    {
+        return Err(CliError::AlreadyRecorded(
+            existing.to_string(),
+            dest.display_name(),
+        ));
+    }
+
+    // The same outputs as the original, where they still exist
+    let sinks = pipewire::extract_audio_sinks(&objects);
+    let local_sinks: Vec<AudioSink> = original
+        .local_output_names()
+        .filter_map(|name| sinks.iter().find(|s| s.node_name == name).cloned())
+        .collect();
+    if local_sinks.is_empty() {
+        return Err(PwSplitterError::NodeNotFound(format!(
+            "local output {} of {}",
+            original.original_output_node_name, existing
+        ))
+        .into());
+    }
+
+    let result = match splitter::setup_split(
+        SplitConfig {
+            source,
+            recording_dests: vec![dest],
+            original_connections: Vec::new(),
+            local_volume: 0.0,
+            recording_volume: original.recording_volume,
+            local_sinks,
+            recording_channel_map: original.recording_channel_map.clone(),
+            local_channel_map: original.local_channel_map.clone(),
+            tuning: original.tuning.clone(),
+            local_description: original.local_description.clone(),
+            // A clone of a clone shares the source with the first split
+            shares_source_with: Some(original.clone_of.clone().unwrap_or(original.name)),
+            loopback_prefix: None,
+        },
+        config.port_timeout(),
+        pipewire::runner(),
+    )? {
+        SetupOutcome::Created(result) => *result,
+        SetupOutcome::DryRun(planned_links) => {
+            cli::print_planned_links(&planned_links);
+            return Ok(());
+        }
+    };
+
+    for warning in &result.warnings {
+        eprintln!("Warning: {}", warning);
+    }
+    if !cli::is_quiet() {
+        println!("{}", result.state.name);
+    }
+
+    // Forget the child processes so they keep running
+    for child in result.loopbacks_to_recording {
+        std::mem::forget(child);
+    }
+    std::mem::forget(result.loopback_to_local);
+    Ok(())
+}
@@ -0,0 +1,314 @@
+//! The `daemon` subcommand: keep every split healthy without the TUI.
+//!
+//! Every few seconds it restarts crashed loopbacks, relinks restarted
+//! recording destinations and, with `auto_reattach`, follows a restarted
+//! source. It detaches from the terminal unless run with `--foreground` (e.g.
+//! under systemd), and records its pid in `daemon.pid` in the state
+//! directory, locked while it runs; `daemon --stop` and `stop-all` use it
+//! to shut the daemon down.
+
+use crate::cli;
+use crate::config::Config;
+use crate::error::{CliError, Result};
+use crate::pipewire;
+use crate::splitter::{self, MAX_RESTART_ATTEMPTS, RestartTracker, SplitState};
+use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM};
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::{Read, Write};
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Pid file of the running daemon, in the state directory
+const PID_FILE: &str = "daemon.pid";
+
+/// Log of a detached daemon, in the state directory
+const LOG_FILE: &str = "pw-splitter-daemon.log";
+
+/// How often every split is checked
+const CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How long to sleep between looks at the exit flag
+const SIGNAL_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Start the daemon, detached unless `foreground`
+///
+/// `verbose` is passed on to the detached process, whose log goes to
+/// [`LOG_FILE`].
+pub fn run(foreground: bool, verbose: bool) -> Result<()> {
+    if let Some(pid) = running_pid() {
+        return Err(CliError::DaemonAlreadyRunning(pid));
+    }
+    if foreground {
+        supervise()
+    } else {
+        detach(verbose)
+    }
+}
+
+/// `daemon --stop`: stop the daemon, saying whether one was running
+pub fn stop() -> Result<()> {
+    let stopped = shut_down()?;
+    if cli::is_quiet() {
+        // Nothing to say either way
+    } else if let Some(pid) = stopped {
+        println!("Stopped the daemon (pid {})", pid);
+    } else {
+        println!("The daemon isn't running.");
+    }
+    Ok(())
+}
+
+/// Stop the daemon if it's running, returning its pid
+pub fn shut_down() -> Result<Option<u32>> {
+    let Some(pid) = running_pid() else {
+        // Left behind by a daemon that was killed outright
+        let _ = fs::remove_file(pid_file());
+        return Ok(None);
+    };
+    pipewire::runner().run("kill", &["-TERM", &pid.to_string()])?;
+    if pipewire::is_dry_run() {
+        return Ok(Some(pid));
+    }
+
+    // It finishes its current check first; wait, so a split stopped right
+    // after this isn't restarted by it
+    let deadline = Instant::now() + pipewire::DEFAULT_PORT_TIMEOUT;
+    while is_daemon(pid) && Instant::now() < deadline {
+        thread::sleep(pipewire::PORT_POLL_INTERVAL);
+    }
+    Ok(Some(pid))
+}
+
+/// Pid of the running daemon, if there is one
+pub fn running_pid() -> Option<u32> {
+    let pid = fs::read_to_string(pid_file()).ok()?.trim().parse().ok()?;
+    is_daemon(pid).then_some(pid)
+}
+
+fn pid_file() -> PathBuf {
+    SplitState::state_dir().join(PID_FILE)
+}
+
+/// Whether `pid` is a pw-splitter daemon; a stale pid file's pid may have
+/// been reused by an unrelated process
+fn is_daemon(pid: u32) -> bool {
+    fs::read(format!("/proc/{}/cmdline", pid)).is_ok_and(|cmdline| {
+        let args: Vec<&[u8]> = cmdline.split(|&b| b == 0).collect();
+        args.contains(&b"daemon".as_slice()) && args.contains(&b"--foreground".as_slice())
+    })
+}
+
+/// Run this executable again as `daemon --foreground`, in its own process
+/// group so the terminal's Ctrl-C and hangup don't reach it
+fn detach(verbose: bool) -> Result<()> {
+    let dir = SplitState::state_dir();
+    fs::create_dir_all(&dir)?;
+    let log_path = dir.join(LOG_FILE);
+    let log = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&log_path)?;
+
+    let mut command = Command::new(env::current_exe()?);
+    command.args(["daemon", "--foreground"]);
+    if verbose {
+        command.arg("--verbose");
+    }
+    let child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(log)
+        .process_group(0)
+        .spawn()?;
+
+    if !cli::is_quiet() {
+        println!(
+            "Started the daemon (pid {}), logging to {}",
+            child.id(),
+            log_path.display()
+        );
+    }
+    Ok(())
+}
+
+/// Check every split until SIGTERM, SIGINT or SIGHUP
+fn supervise() -> Result<()> {
+    let config = Config::load()?;
+    let terminate = Arc::new(AtomicBool::new(false));
+    for signal in [SIGTERM, SIGINT, SIGHUP] {
+        signal_hook::flag::register(signal, Arc::clone(&terminate))?;
+    }
+
+    let pid_file = pid_file();
+    fs::create_dir_all(SplitState::state_dir())?;
+    let _lock = lock_pid_file(&pid_file)?;
+    tracing::info!("daemon started (pid {})", std::process::id());
+
+    let mut supervisor = Supervisor::default();
+    while !terminate.load(Ordering::Relaxed) {
+        match SplitState::list_all() {
+            Ok(states) => supervisor.check(states, &config),
+            Err(e) => tracing::warn!("listing splits: {}", e),
+        }
+
+        let next_check = Instant::now() + CHECK_INTERVAL;
+        while !terminate.load(Ordering::Relaxed) && Instant::now() < next_check {
+            thread::sleep(SIGNAL_POLL_INTERVAL);
+        }
+    }
+
+    tracing::info!("daemon stopping");
+    let _ = fs::remove_file(&pid_file);
+    Ok(())
+}
+
+/// Take the pid file for this process, held until the returned file is dropped
+///
+/// The exclusive lock, rather than [`running_pid`], decides which of two
+/// daemons started at once gets to run.
+fn lock_pid_file(path: &Path) -> Result<File> {
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)?;
+    match file.try_lock() {
+        Ok(()) => {}
+        Err(TryLockError::WouldBlock) => {
+            let mut pid = String::new();
+            file.read_to_string(&mut pid)?;
+            return Err(CliError::DaemonAlreadyRunning(
+                pid.trim().parse().unwrap_or_default(),
+            ));
+        }
+        Err(TryLockError::Error(e)) => return Err(e.into()),
+    }
+    file.set_len(0)?;
+    write!(file, "{}", std::process::id())?;
+    Ok(file)
+}
+
+/// What the daemon remembers between checks
+#[derive(Default)]
+struct Supervisor {
+    /// Restart attempts per crashed loopback, by loopback name
+    trackers: HashMap<String, RestartTracker>,
+    /// Loopbacks found stopped at the last check, by name
+    stopped: HashSet<String>,
+    /// Loopbacks restarted by the daemon, reaped once they exit
+    children: Vec<Child>,
+}
+
+impl Supervisor {
+    fn check(&mut self, states: Vec<SplitState>, config: &Config) {
+        // Until reaped, an exited loopback would still look like it's running
+        self.children
+            .retain_mut(|child| matches!(child.try_wait(), Ok(None)));
+        let mut stopped = HashSet::new();
+        for mut state in states {
+            self.check_split(&mut state, config, &mut stopped);
+        }
+        self.stopped = stopped;
+    }
+
+    /// Relink and reattach `state`, then restart its crashed loopbacks
+    ///
+    /// `stop` kills the loopbacks before it deletes the state file, so a
+    /// loopback is only restarted once it has been down for a whole check.
+    fn check_split(
+        &mut self,
+        state: &mut SplitState,
+        config: &Config,
+        stopped: &mut HashSet<String>,
+    ) {
+        match splitter::relink_recording_dests(state) {
+            Ok(0) => {}
+            Ok(n) => tracing::warn!("{}: relinked {} restarted destination(s)", state.name, n),
+            Err(e) => tracing::debug!("{}: relink: {}", state.name, e),
+        }
+        if config.auto_reattach.unwrap_or(false) {
+            match splitter::reattach_split(state) {
+                Ok(true) => tracing::warn!("{}: source restarted, reattached", state.name),
+                Ok(false) => {}
+                Err(e) => tracing::debug!("{}: reattach: {}", state.name, e),
+            }
+        }
+
+        let (recording_running, local_running) = splitter::check_loopbacks_running(state);
+        let crashed: Vec<Option<usize>> = recording_running
+            .into_iter()
+            .enumerate()
+            .filter(|(_, running)| !running)
+            .map(|(index, _)| Some(index))
+            .chain((!local_running).then_some(None))
+            .collect();
+
+        for index in crashed {
+            let name = match index {
+                Some(index) => state.recordings[index].loopback_name.clone(),
+                None => state.local_loopback_name.clone(),
+            };
+            stopped.insert(name.clone());
+            let tracker = self.trackers.entry(name.clone()).or_default();
+            if !self.stopped.contains(&name)
+                || !tracker.may_attempt()
+                || !SplitState::exists(&state.name)
+            {
+                continue;
+            }
+
+            let result = match index {
+                Some(index) => splitter::restart_loopback_to_recording(state, index),
+                None => splitter::restart_loopback_to_local(state),
+            };
+            tracker.record(result.is_ok());
+            match result {
+                Ok(child) => {
+                    self.children.push(child);
+                    tracing::warn!("{}: restarted crashed loopback {}", state.name, name);
+                }
+                Err(_) if tracker.gave_up() => tracing::error!(
+                    "{}: {} failed to restart {} times, giving up",
+                    state.name,
+                    name,
+                    MAX_RESTART_ATTEMPTS
+                ),
+                Err(e) => tracing::warn!("{}: restarting {}: {}", state.name, name, e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn second_daemon_cannot_take_the_pid_file() {
+        let path = env::temp_dir().join(format!("pw-splitter-lock-{}.pid", std::process::id()));
+        let held = lock_pid_file(&path).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            std::process::id().to_string()
+        );
+
+        match lock_pid_file(&path) {
+            Err(CliError::DaemonAlreadyRunning(pid)) => {
+                assert_eq!(pid, std::process::id())
+            }
+            other => panic!("expected DaemonAlreadyRunning, got {:?}", other.map(|_| ())),
+        }
+
+        drop(held);
+        assert!(lock_pid_file(&path).is_ok());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,183 @@
+//! The `doctor` subcommand: a self-test of everything a split depends on.
+
+use crate::error::{CliError, Result};
+use crate::pipewire;
+use crate::splitter::SplitState;
+use std::fs;
+use std::io::{self, IsTerminal};
+
+/// ANSI color codes for the report
+const GREEN: &str = "32";
+const RED: &str = "31";
+
+/// Outcome of a single check
+struct Check {
+    name: String,
+    /// Detail on success, remediation hint on failure
+    outcome: std::result::Result<String, String>,
+}
+
+/// Run all checks, print a report, and fail if any check failed
+pub fn run() -> Result<()> {
+    let mut checks: Vec<Check> = pipewire::REQUIRED_TOOLS
+        .iter()
+        .chain([&pipewire::link_backend().tool()])
+        .map(|tool| check_tool(tool))
+        .collect();
+    checks.extend(check_pipewire());
+    checks.push(check_state_dir());
+
+    let color = io::stdout().is_terminal();
+    for check in &checks {
+        match &check.outcome {
+            Ok(detail) => println!(
+                "{} {}: {}",
+                paint("[PASS]", GREEN, color),
+                check.name,
+                detail
+            ),
+            Err(hint) => {
+                println!("{} {}", paint("[FAIL]", RED, color), check.name);
+                println!("       hint: {}", hint);
+            }
+        }
+    }
+
+    let failed = checks.iter().filter(|c| c.outcome.is_err()).count();
+    if failed > 0 {
+        println!(
+            "{}",
+            paint(
+                &format!("{} of {} checks failed.", failed, checks.len()),
+                RED,
+                color
+            )
+        );
+        return Err(CliError::ChecksFailed(failed));
+    }
+
+    println!(
+        "{}",
+        paint(
+            &format!("All {} checks passed.", checks.len()),
+            GREEN,
+            color
+        )
+    );
+    Ok(())
+}
+
+/// Wrap `text` in an ANSI color, unless output isn't a terminal
+fn paint(text: &str, color_code: &str, enabled: bool) -> String {
+    if enabled {
+        format!("\x1b[{}m{}\x1b[0m", color_code, text)
+    } else {
+        text.to_string()
+    }
+}
+
+/// Check a tool is on PATH and runs (`--version` exits successfully)
+fn check_tool(tool: &str) -> Check {
+    let outcome = match pipewire::find_in_path(tool) {
+        None => Err(format!(
+            "'{}' is not on PATH; install your distro's {}",
+            tool,
+            pipewire::tool_package(tool)
+        )),
+        Some(path) => match pipewire::runner().query(&path.to_string_lossy(), &["--version"]) {
+            Ok(output) if output.status.success() => {
+                let stdout = String::from_utf8_lossy(&output.stdout);
+                let version = stdout.lines().find(|l| !l.trim().is_empty()).unwrap_or("");
+                Ok(format!("{} ({})", path.display(), version.trim()))
+            }
+            Ok(output) => Err(format!(
+                "'{} --version' failed ({}); the installation may be broken",
+                path.display(),
+                output.status
+            )),
+            Err(e) => Err(format!(
+                "cannot run {} ({}); check it is executable",
+                path.display(),
+                e
+            )),
+        },
+    };
+
+    Check {
+        name: format!("{} usable", tool),
+        outcome,
+    }
+}
+
+/// Checks that PipeWire answers queries and has at least one output sink
+fn check_pipewire() -> Vec<Check> {
+    let objects = match pipewire::get_pw_objects() {
+        Ok(objects) if !objects.is_empty() => objects,
+        Ok(_) => {
+            return vec![Check {
+                name: "PipeWire reachable".to_string(),
+                outcome: Err(
+                    "pw-dump returned no objects; is the PipeWire daemon running? \
+                     Try 'systemctl --user status pipewire'"
+                        .to_string(),
+                ),
+            }];
+        }
+        Err(e) => {
+            return vec![Check {
+                name: "PipeWire reachable".to_string(),
+                outcome: Err(format!(
+                    "{}; start it with 'systemctl --user start pipewire'",
+                    e
+                )),
+            }];
+        }
+    };
+
+    let sinks = pipewire::extract_audio_sinks(&objects);
+    vec![
+        Check {
+            name: "PipeWire reachable".to_string(),
+            outcome: Ok(format!(
+                "{} objects, server version {}",
+                objects.len(),
+                pipewire::server_version(&objects).unwrap_or_else(|| "unknown".to_string())
+            )),
+        },
+        Check {
+            name: "Output sink available".to_string(),
+            outcome: match sinks.first() {
+                Some(sink) => Ok(format!(
+                    "{} sink(s), e.g. {}",
+                    sinks.len(),
+                    sink.description
+                )),
+                None => Err("no Audio/Sink found; connect an output device or check \
+                     that WirePlumber is running"
+                    .to_string()),
+            },
+        },
+    ]
+}
+
+fn check_state_dir() -> Check {
+    let dir = SplitState::state_dir();
+    let probe = dir.join(".doctor-probe");
+
+    let outcome = fs::create_dir_all(&dir)
+        .and_then(|_| fs::write(&probe, b""))
+        .and_then(|_| fs::remove_file(&probe))
+        .map(|_| dir.display().to_string())
+        .map_err(|e| {
+            format!(
+                "cannot write to {} ({}); check permissions and free space",
+                dir.display(),
+                e
+            )
+        });
+
+    Check {
+        name: "State directory writable".to_string(),
+        outcome,
+    }
+}
@@ -0,0 +1,201 @@
+//! The `list` subcommand.
+
+use crate::cli;
+use crate::error::Result;
+use crate::splitter::{self, SplitState};
+use serde::Serialize;
+use std::str::FromStr;
+
+/// Output format of `list`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ListFormat {
+    #[default]
+    Table,
+    Json,
+    Csv,
+}
+
+impl FromStr for ListFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "table" => Ok(ListFormat::Table),
+            "json" => Ok(ListFormat::Json),
+            "csv" => Ok(ListFormat::Csv),
+            _ => Err(format!(
+                "unknown format '{}': expected table, json or csv",
+                s
+            )),
+        }
+    }
+}
+
+/// Which splits `list` shows, by the health of their loopbacks
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HealthFilter {
+    #[default]
+    All,
+    /// `--dead-only`: no loopback running, as `prune` would remove
+    Dead,
+    /// `--running-only`: every loopback running
+    Running,
+}
+
+impl HealthFilter {
+    fn accepts(self, status: &SplitStatus) -> bool {
+        let recording = &status.recording_running;
+        match self {
+            HealthFilter::All => true,
+            HealthFilter::Dead => !status.local_running && !recording.contains(&true),
+            HealthFilter::Running => status.local_running && !recording.contains(&false),
+        }
+    }
+}
+
+/// A split together with the live health of its loopbacks
+#[derive(Serialize)]
+struct SplitStatus {
+    #[serde(flatten)]
+    state: SplitState,
+    /// One flag per entry of `recordings`
+    recording_running: Vec<bool>,
+    local_running: bool,
+    /// Per entry of `recordings`, the other splits recording to the same node
+    shared_with: Vec<Vec<String>>,
+    /// The local copy is muted (`muted_volume` holds its volume from before)
+    muted: bool,
+    /// Seconds since `created_at`; 0 if that's in the future
+    age_secs: u64,
+}
+
+/// List the active splits `filter` accepts, in the given format
+pub fn run(format: ListFormat, filter: HealthFilter) -> Result<()> {
+    let states = SplitState::list_all()?;
+    let statuses: Vec<SplitStatus> = states
+        .iter()
+        .map(|state| {
+            let (recording_running, local_running) = splitter::check_loopbacks_running(state);
+            let shared_with = state
+                .recordings
+                .iter()
+                .map(|r| {
+                    state
+                        .splits_sharing_dest(&states, r.dest_node_id)
+                        .into_iter()
+                        .map(str::to_string)
+                        .collect()
+                })
+                .collect();
+            SplitStatus {
+                state: state.clone(),
+                recording_running,
+                local_running,
+                shared_with,
+                muted: state.muted_volume.is_some(),
+                age_secs: state.age().as_secs(),
+            }
+        })
+        .filter(|status| filter.accepts(status))
+        .collect();
+
+    match format {
+        ListFormat::Table => print_table(&statuses, filter),
+        ListFormat::Json => println!("{}", serde_json::to_string_pretty(&statuses)?),
+        ListFormat::Csv => print_csv(&statuses),
+    }
+
+    Ok(())
+}
+
+fn print_table(statuses: &[SplitStatus], filter: HealthFilter) {
+    if statuses.is_empty() {
+        match filter {
+            HealthFilter::All => println!("No active splits."),
+            HealthFilter::Dead => println!("No dead splits."),
+            HealthFilter::Running => println!("No fully running splits."),
+        }
+        return;
+    }
+
+    println!("Active splits:");
+    println!("{:-<60}", "");
+
+    for status in statuses {
+        let split = &status.state;
+
+        println!("Name: {}", split.name);
+        println!("  Source: {}", split.source_application_name);
+        println!("  Age: {}", cli::format_age(split.age()));
+        for (recording, shared) in split.recordings.iter().zip(&status.shared_with) {
+            println!(
+                "  Recording to: {} [{}]",
+                recording.dest_application_name, recording.dest_media_name
+            );
+            if !shared.is_empty() {
+                println!("    (shared with {}; audio is mixed)", shared.join(", "));
+            }
+        }
+        println!(
+            "  Local output: {}",
+            split.local_output_names().collect::<Vec<_>>().join(", ")
+        );
+        if split.muted_volume.is_some() {
+            println!("  Local volume: muted");
+        }
+        if split.recording_volume != 1.0 {
+            println!("  Recording volume: {:.0}%", split.recording_volume * 100.0);
+        }
+        if !split.tags.is_empty() {
+            println!("  Tags: {}", split.tags.join(", "));
+        }
+        println!(
+            "  Loopbacks: recording={}, local={}",
+            status
+                .recording_running
+                .iter()
+                .map(|&running| running_label(running))
+                .collect::<Vec<_>>()
+                .join("/"),
+            running_label(status.local_running)
+        );
+        println!("{:-<60}", "");
+    }
+}
+
+/// One row per split; several recording destinations or local outputs are joined with `; `
+fn print_csv(statuses: &[SplitStatus]) {
+    println!("name,source,recording_dest,local_output,recording_running,local_running,muted");
+    for status in statuses {
+        let split = &status.state;
+        let dests = split
+            .recordings
+            .iter()
+            .map(|r| r.dest_application_name.as_str())
+            .collect::<Vec<_>>()
+            .join("; ");
+        println!(
+            "{},{},{},{},{},{},{}",
+            csv_field(&split.name),
+            csv_field(&split.source_application_name),
+            csv_field(&dests),
+            csv_field(&split.local_output_names().collect::<Vec<_>>().join("; ")),
+            status.recording_running.iter().all(|&running| running),
+            status.local_running,
+            status.muted
+        );
+    }
+}
+
+/// Quote a field if it contains a comma, quote or newline (RFC 4180)
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn running_label(running: bool) -> &'static str {
+    if running { "running" } else { "stopped" }
+}
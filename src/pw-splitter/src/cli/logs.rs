@@ -0,0 +1,35 @@
+//! The `logs` subcommand: show the most recent failed setups.
+
+use crate::error::{PwSplitterError, Result};
+use pw_splitter_core::failure_log;
+use std::fs;
+use std::io;
+
+/// Print the last `lines` lines of the error log
+pub fn run(lines: usize) -> Result<()> {
+    let path = failure_log::error_log_path();
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            println!(
+                "No failed setups logged ({} does not exist).",
+                path.display()
+            );
+            return Ok(());
+        }
+        Err(e) => {
+            return Err(PwSplitterError::StateFileError(format!(
+                "cannot read {}: {}",
+                path.display(),
+                e
+            ))
+            .into());
+        }
+    };
+
+    let all: Vec<&str> = text.lines().collect();
+    for line in &all[all.len().saturating_sub(lines)..] {
+        println!("{}", line);
+    }
+    Ok(())
+}
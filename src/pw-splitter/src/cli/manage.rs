@@ -0,0 +1,190 @@
+//! The subcommands that manage existing splits: `stop`, `stop-all`,
+//! `restart`, `reattach`, `volume`, `mute`/`unmute`, `prune`, `rename` and
+//! `tag`.
+
+use crate::cli;
+use crate::error::{CliError, Result};
+use crate::pipewire;
+use crate::splitter::{self, SplitState};
+use std::time::Duration;
+
+/// `stop <name>`
+pub fn stop_split(name: &str, restore_links: bool) -> Result<()> {
+    if !cli::is_quiet() {
+        println!("Stopping split: {}", name);
+    }
+    splitter::stop_split(name, restore_links)?;
+    // A dry run says so once its commands are listed
+    if !cli::is_quiet() && !pipewire::is_dry_run() {
+        println!("Split stopped successfully.");
+    }
+    Ok(())
+}
+
+/// `restart <name>`: respawn the loopbacks of a split
+pub fn restart_split(name: &str) -> Result<()> {
+    println!("Restarting split: {}", name);
+    let state = splitter::restart_split(name)?;
+    for recording in &state.recordings {
+        println!(
+            "  {} -> {}: pid {}",
+            recording.loopback_name, recording.dest_application_name, recording.pid
+        );
+    }
+    println!(
+        "  {} -> {}: pid {}",
+        state.local_loopback_name,
+        state.local_output_names().collect::<Vec<_>>().join(", "),
+        state.loopback_to_local_pid
+    );
+    println!("Split restarted successfully.");
+    Ok(())
+}
+
+/// `reattach <name>`: follow a restarted source or recording destination
+pub fn reattach_split(name: &str) -> Result<()> {
+    let mut state = SplitState::load(name)?;
+    let relinked = splitter::relink_recording_dests(&mut state)?;
+    if relinked > 0 {
+        println!("Relinked {} restarted recording destination(s)", relinked);
+    }
+    if splitter::reattach_split(&mut state)? {
+        println!(
+            "Reattached {} to source node {}",
+            name, state.source_node_id
+        );
+    } else if relinked == 0 {
+        println!(
+            "Source and destinations of {} are still running; nothing to do.",
+            name
+        );
+    }
+    Ok(())
+}
+
+/// `volume <name> <level>`: set the local copy's volume
+pub fn set_volume(name: &str, level: f32) -> Result<()> {
+    splitter::validate_volume(level)?;
+    let mut state = SplitState::load(name)?;
+    splitter::set_local_volume(&mut state, level)?;
+    println!("Set local volume of {} to {:.0}%", name, level * 100.0);
+    Ok(())
+}
+
+/// `mute`/`unmute <name>`
+pub fn set_muted(name: &str, mute: bool) -> Result<()> {
+    let mut state = SplitState::load(name)?;
+    let changed = if mute {
+        splitter::mute_local(&mut state)?
+    } else {
+        splitter::unmute_local(&mut state)?
+    };
+    match (mute, changed) {
+        (true, true) => println!("Muted the local copy of {}", name),
+        (true, false) => println!("{} is already muted", name),
+        (false, true) => println!(
+            "Unmuted the local copy of {} ({:.0}%)",
+            name,
+            state.local_volume * 100.0
+        ),
+        (false, false) => println!("{} isn't muted", name),
+    }
+    Ok(())
+}
+
+/// `stop-all`, optionally only the splits tagged `tag`
+pub fn stop_all_splits(tag: Option<&str>) -> Result<()> {
+    // Otherwise it could restart loopbacks while they're being stopped
+    if tag.is_none()
+        && let Some(pid) = cli::daemon::shut_down()?
+        && !cli::is_quiet()
+    {
+        println!("Stopped the daemon (pid {})", pid);
+    }
+    let report = splitter::stop_all_splits(|s| tag.is_none_or(|t| s.has_tag(t)), true)?;
+    let filter = tag.map(|tag| format!(" tagged '{}'", tag));
+    report_stopped(report, filter.as_deref().unwrap_or_default())
+}
+
+/// Stop every split of the source application `source`, ignoring case
+pub fn stop_source_splits(source: &str, restore_links: bool) -> Result<()> {
+    let source_lower = source.to_lowercase();
+    let report = splitter::stop_all_splits(
+        |s| s.source_application_name.to_lowercase() == source_lower,
+        restore_links,
+    )?;
+    report_stopped(report, &format!(" of source '{}'", source))
+}
+
+/// Print which splits a bulk stop tore down, and fail if any couldn't be
+///
+/// `filter` describes which splits were asked for, e.g. ` tagged 'x'`.
+fn report_stopped(report: splitter::StopAllReport, filter: &str) -> Result<()> {
+    let stopped = report.stopped;
+
+    if cli::is_quiet() {
+        // Failures below are still reported
+    } else if stopped.is_empty() && report.failed.is_empty() {
+        println!("No active splits{} to stop.", filter);
+    } else if !stopped.is_empty() {
+        println!("Stopped {} split(s){}:", stopped.len(), filter);
+        for name in stopped {
+            println!("  - {}", name);
+        }
+    }
+
+    if !report.failed.is_empty() {
+        eprintln!("Failed to stop {} split(s):", report.failed.len());
+        for (name, e) in &report.failed {
+            eprintln!("  - {}: {}", name, e);
+        }
+        return Err(CliError::StopFailed(report.failed.len()));
+    }
+
+    Ok(())
+}
+
+/// `prune`: restore and remove splits whose loopbacks are all gone
+pub fn prune_splits(dry_run: bool, older_than: Option<Duration>) -> Result<()> {
+    let report = splitter::prune_splits(dry_run, older_than)?;
+    let pruned = report.pruned;
+
+    if pruned.is_empty() && report.failed.is_empty() {
+        println!("No stale splits found.");
+        return Ok(());
+    }
+
+    if pruned.is_empty() {
+        // Only failures, reported below
+    } else if dry_run {
+        println!("Would prune {} stale split(s):", pruned.len());
+    } else {
+        println!("Pruned {} stale split(s):", pruned.len());
+    }
+    for state in &pruned {
+        println!("  - {} ({})", state.name, state.source_application_name);
+    }
+
+    if !report.failed.is_empty() {
+        eprintln!("Failed to prune {} split(s):", report.failed.len());
+        for (name, e) in &report.failed {
+            eprintln!("  - {}: {}", name, e);
+        }
+        return Err(CliError::PruneFailed(report.failed.len()));
+    }
+    Ok(())
+}
+
+/// `rename <old> <new>`
+pub fn rename_split(old: &str, new: &str) -> Result<()> {
+    splitter::rename_split(old, new)?;
+    println!("Renamed {} to {}", old, new);
+    Ok(())
+}
+
+/// `tag <name> <tags>...`
+pub fn tag_split(name: &str, tags: &[String]) -> Result<()> {
+    splitter::tag_split(name, tags)?;
+    println!("Tagged {} with: {}", name, tags.join(", "));
+    Ok(())
+}
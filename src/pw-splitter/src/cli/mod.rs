@@ -0,0 +1,119 @@
+//! Implementations of the non-interactive subcommands.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+pub mod clone;
+pub mod daemon;
+pub mod doctor;
+pub mod list;
+pub mod logs;
+pub mod manage;
+pub mod profile;
+pub mod service;
+pub mod start;
+pub mod status;
+pub mod version;
+
+/// Whether `--quiet` was given
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Suppress success messages, for scripts that only check the exit code
+///
+/// Warnings and errors still go to stderr.
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+/// Whether success messages are suppressed
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// Print the links a dry run of a setup would have made through the loopbacks
+///
+/// They can't be shown as commands, as the loopbacks were never spawned.
+pub fn print_planned_links(planned_links: &[String]) {
+    for link in planned_links {
+        println!("# link {}", link);
+    }
+}
+
+/// Print the commands a dry run would have run, in order
+pub fn report_dry_run(commands: &[String]) {
+    for command in commands {
+        println!("{}", command);
+    }
+    if !is_quiet() {
+        println!("Dry run: nothing was changed.");
+    }
+}
+
+/// A split's age, compactly, e.g. `45s`, `1h 05m` or `2d 03h`
+///
+/// [`SplitState::age`](crate::splitter::SplitState::age) is zero for a
+/// creation time in the future (clock skew), which shows as `0s`.
+pub fn format_age(age: Duration) -> String {
+    let secs = age.as_secs();
+    match secs {
+        0..60 => format!("{}s", secs),
+        60..3600 => format!("{}m {:02}s", secs / 60, secs % 60),
+        3600..86400 => format!("{}h {:02}m", secs / 3600, secs % 3600 / 60),
+        _ => format!("{}d {:02}h", secs / 86400, secs % 86400 / 3600),
+    }
+}
+
+/// Parse a duration such as `90s`, `30m`, `2h`, `1d` or `1h30m`
+///
+/// A bare number is taken as seconds.
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let invalid = || format!("invalid duration '{}' (e.g. 30m, 2h, 1d)", s);
+    if s.is_empty() {
+        return Err(invalid());
+    }
+    if let Ok(secs) = s.parse::<u64>() {
+        return Ok(Duration::from_secs(secs));
+    }
+
+    let mut total = 0u64;
+    let mut rest = s;
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .ok_or_else(invalid)?;
+        let value: u64 = rest[..digits].parse().map_err(|_| invalid())?;
+        let unit = match rest[digits..].chars().next() {
+            Some('s') => 1,
+            Some('m') => 60,
+            Some('h') => 3600,
+            Some('d') => 86400,
+            _ => return Err(invalid()),
+        };
+        total = value
+            .checked_mul(unit)
+            .and_then(|secs| total.checked_add(secs))
+            .ok_or_else(invalid)?;
+        rest = &rest[digits + 1..];
+    }
+    Ok(Duration::from_secs(total))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("30m"), Ok(Duration::from_secs(1800)));
+        assert_eq!(parse_duration("1h30m"), Ok(Duration::from_secs(5400)));
+        assert_eq!(parse_duration("1d"), Ok(Duration::from_secs(86400)));
+    }
+
+    #[test]
+    fn rejects_invalid_durations() {
+        for s in ["", "m", "5x", "1h30", "-1s", "99999999999999999999d"] {
+            assert!(parse_duration(s).is_err(), "{:?} should be rejected", s);
+        }
+    }
+}
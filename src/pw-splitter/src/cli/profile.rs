@@ -0,0 +1,107 @@
+//! The `profile` subcommand: named, reusable `start` configurations.
+//!
+//! Profiles are stored as JSON in `<config_dir>/profiles/<name>.json` and keep
+//! the node names as given, so `apply` resolves whatever nodes are live then.
+
+use crate::cli::start::{self, StartOptions};
+use crate::config::Config;
+use crate::error::{CliError, PwSplitterError, Result};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// Directory holding the profiles, next to the config file
+fn profiles_dir() -> Result<PathBuf> {
+    Config::path()
+        .as_deref()
+        .and_then(|path| path.parent())
+        .map(|dir| dir.join("profiles"))
+        .ok_or_else(|| {
+            CliError::ConfigError("no config directory (set XDG_CONFIG_HOME or HOME)".to_string())
+        })
+}
+
+fn profile_path(name: &str) -> Result<PathBuf> {
+    if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+        return Err(CliError::InvalidProfileName(name.to_string()));
+    }
+    Ok(profiles_dir()?.join(format!("{}.json", name)))
+}
+
+fn load(name: &str) -> Result<StartOptions> {
+    let path = profile_path(name)?;
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Err(CliError::ProfileNotFound(name.to_string()));
+        }
+        Err(e) => return Err(e.into()),
+    };
+    serde_json::from_str(&text)
+        .map_err(|e| CliError::ConfigError(format!("{}: {}", path.display(), e)))
+}
+
+/// Save `options` as the profile `name`, replacing any existing one
+pub fn save(name: &str, options: &StartOptions) -> Result<()> {
+    if let Some(volume) = options.volume {
+        crate::splitter::validate_volume(volume)?;
+    }
+    let path = profile_path(name)?;
+    fs::create_dir_all(profiles_dir()?)?;
+    fs::write(&path, serde_json::to_string_pretty(options)?)?;
+    println!("Saved profile {} to {}", name, path.display());
+    Ok(())
+}
+
+/// Print the saved profiles with what they route
+pub fn list() -> Result<()> {
+    let dir = profiles_dir()?;
+    let mut names: Vec<String> = match fs::read_dir(&dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                (path.extension()? == "json")
+                    .then(|| path.file_stem()?.to_str().map(str::to_string))
+                    .flatten()
+            })
+            .collect(),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e.into()),
+    };
+    names.sort();
+
+    if names.is_empty() {
+        println!("No saved profiles.");
+        return Ok(());
+    }
+    for name in names {
+        match load(&name) {
+            Ok(options) => println!(
+                "{}: {} -> {}{}",
+                name,
+                options.source,
+                options.dests.join(", "),
+                if options.sinks.is_empty() {
+                    String::new()
+                } else {
+                    format!(" (local: {})", options.sinks.join(", "))
+                }
+            ),
+            Err(e) => println!("{}: unreadable ({})", name, e),
+        }
+    }
+    Ok(())
+}
+
+/// Start a split from the profile `name`
+pub fn apply(name: &str) -> Result<()> {
+    let options = load(name)?;
+    start::run(&options).map_err(|e| match e {
+        // Most likely the saved application isn't running right now
+        CliError::Core(PwSplitterError::NodeNotFound(what)) => {
+            PwSplitterError::NodeNotFound(format!("{}; profile '{}' needs it running", what, name))
+                .into()
+        }
+        e => e,
+    })
+}
@@ -0,0 +1,102 @@
+//! The `install-service` subcommand: a systemd user unit running the daemon.
+//!
+//! The unit is written to `<config_home>/systemd/user/pw-splitter.service`
+//! and runs `pw-splitter daemon --foreground`, so systemd tracks the process
+//! and restarts it if it fails. Enabling it is left to the user, as printed.
+
+use crate::config::Config;
+use crate::error::{CliError, Result};
+use crate::splitter::SplitState;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// File name of the unit
+const UNIT_NAME: &str = "pw-splitter.service";
+
+/// Environment variable whose value the daemon needs to find the splits
+const STATE_DIR_ENV: &str = "PW_SPLITTER_STATE_DIR";
+
+/// Write the unit, replacing an older one, and print how to enable it
+pub fn install() -> Result<()> {
+    let path = unit_dir()?.join(UNIT_NAME);
+    let unit = unit_file(&env::current_exe()?);
+    let existed = path.exists();
+
+    fs::create_dir_all(unit_dir()?)?;
+    fs::write(&path, unit)?;
+
+    println!(
+        "{} {}",
+        if existed { "Updated" } else { "Wrote" },
+        path.display()
+    );
+    println!("Enable and start it with:");
+    println!("  systemctl --user daemon-reload");
+    println!("  systemctl --user enable --now {}", UNIT_NAME);
+    Ok(())
+}
+
+/// Remove the unit (`install-service --uninstall`)
+///
+/// Refuses while the unit is enabled: disabling needs the unit file, and
+/// without it the enable symlink would be left dangling.
+pub fn uninstall() -> Result<()> {
+    let dir = unit_dir()?;
+    let path = dir.join(UNIT_NAME);
+    if !path.exists() {
+        println!("{} isn't installed.", UNIT_NAME);
+        return Ok(());
+    }
+    if dir.join("default.target.wants").join(UNIT_NAME).exists() {
+        return Err(CliError::ServiceEnabled(UNIT_NAME.to_string()));
+    }
+
+    fs::remove_file(&path)?;
+    println!("Removed {}", path.display());
+    println!("Run 'systemctl --user daemon-reload' so systemd forgets it.");
+    Ok(())
+}
+
+/// `systemd/user` in the config home, where user units go
+fn unit_dir() -> Result<PathBuf> {
+    Config::path()
+        .as_deref()
+        .and_then(|path| path.parent()?.parent())
+        .map(|config_home| config_home.join("systemd").join("user"))
+        .ok_or_else(|| {
+            CliError::ConfigError("no config directory (set XDG_CONFIG_HOME or HOME)".to_string())
+        })
+}
+
+/// The unit running `exe` as the daemon, after PipeWire is up
+fn unit_file(exe: &Path) -> String {
+    // A state directory chosen in the shell isn't seen by systemd's
+    // environment, and the daemon would look for the splits elsewhere
+    let environment = env::var_os(STATE_DIR_ENV)
+        .filter(|dir| !dir.is_empty())
+        .map(|_| {
+            format!(
+                "Environment=\"{}={}\"\n",
+                STATE_DIR_ENV,
+                SplitState::state_dir().display()
+            )
+        })
+        .unwrap_or_default();
+
+    format!(
+        "# Generated by 'pw-splitter install-service'\n\
+         [Unit]\n\
+         Description=pw-splitter: keep audio splits running\n\
+         After=pipewire.service wireplumber.service\n\
+         \n\
+         [Service]\n\
+         ExecStart=\"{}\" daemon --foreground\n\
+         {}Restart=on-failure\n\
+         \n\
+         [Install]\n\
+         WantedBy=default.target\n",
+        exe.display(),
+        environment
+    )
+}
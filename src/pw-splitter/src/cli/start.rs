@@ -0,0 +1,215 @@
+//! The `start` subcommand: create a split without the TUI, for scripts.
+
+use crate::cli;
+use crate::config::Config;
+use crate::error::{CliError, PwSplitterError, Result};
+use crate::pipewire::{self, ApplicationNode, AudioSink, LoopbackTuning, PwObject, RecordingDest};
+use crate::splitter::{self, SetupOutcome, SplitConfig};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Nodes to split between, each given as a node id or a name
+///
+/// Also the format of saved profiles.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StartOptions {
+    /// Source application name or node id
+    pub source: String,
+    /// Recording destinations (application names or node ids)
+    pub dests: Vec<String>,
+    /// Local output sinks (node names, descriptions or ids); empty keeps the current output
+    ///
+    /// Profiles saved before several sinks were supported have a single `sink`.
+    #[serde(
+        default,
+        alias = "sink",
+        deserialize_with = "one_or_many",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub sinks: Vec<String>,
+    /// Local volume (1.0 = 100%); defaults to the configured volume
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub volume: Option<f32>,
+    /// Loopback `node.latency` (`<quantum>/<rate>`); defaults to the configured one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency: Option<String>,
+    /// Loopback sample rate in Hz; defaults to the configured one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate: Option<u32>,
+    /// Local loopback description; defaults to the configured one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// How long to wait for the source to appear; `None` fails right away
+    #[serde(skip)]
+    pub wait: Option<Duration>,
+}
+
+/// Interval between checks for the source with `--wait`
+const WAIT_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Resolve the nodes, set up the split and print its name
+pub fn run(options: &StartOptions) -> Result<()> {
+    let config = Config::load()?;
+    let local_volume = match options.volume {
+        Some(volume) => splitter::validate_volume(volume)?,
+        None => config.default_local_volume.unwrap_or(1.0),
+    };
+
+    let objects = match options.wait {
+        Some(timeout) => wait_for_source(&options.source, &config, timeout)?,
+        None => pipewire::get_pw_objects()?,
+    };
+    let sources = pipewire::extract_audio_sources(&objects, config.source_classes());
+    let source = resolve(&options.source, "source", &sources)?.clone();
+
+    let destinations = pipewire::extract_recording_dests(&objects);
+    let recording_dests: Vec<RecordingDest> = options
+        .dests
+        .iter()
+        .map(|query| resolve(query, "recording destination", &destinations).cloned())
+        .collect::<Result<_>>()?;
+    let dest_ids: Vec<u32> = recording_dests.iter().map(|d| d.node_id).collect();
+
+    let sinks = pipewire::extract_audio_sinks(&objects);
+    let local_sinks: Vec<AudioSink> = options
+        .sinks
+        .iter()
+        .map(|query| resolve_sink(query, &sinks).cloned())
+        .collect::<Result<_>>()?;
+
+    let mut original_connections = pipewire::find_source_connections(source.node_id, &objects);
+    if original_connections.is_empty() && local_sinks.is_empty() {
+        original_connections.extend(splitter::default_output_connection(
+            &source, &objects, &dest_ids,
+        ));
+    }
+
+    let result = match splitter::setup_split(
+        SplitConfig {
+            source,
+            recording_dests,
+            original_connections,
+            local_volume,
+            recording_volume: config.default_recording_volume.unwrap_or(1.0),
+            local_sinks,
+            recording_channel_map: config.recording_channel_map.clone().unwrap_or_default(),
+            local_channel_map: config.local_channel_map.clone().unwrap_or_default(),
+            tuning: LoopbackTuning {
+                latency: options.latency.clone().or_else(|| config.latency.clone()),
+                rate: options.rate.or(config.sample_rate),
+            },
+            local_description: options
+                .description
+                .clone()
+                .or_else(|| config.local_description.clone()),
+            shares_source_with: None,
+            loopback_prefix: None,
+        },
+        config.port_timeout(),
+        pipewire::runner(),
+    )? {
+        SetupOutcome::Created(result) => *result,
+        SetupOutcome::DryRun(planned_links) => {
+            cli::print_planned_links(&planned_links);
+            return Ok(());
+        }
+    };
+
+    for warning in &result.warnings {
+        eprintln!("Warning: {}", warning);
+    }
+    if !cli::is_quiet() {
+        println!("{}", result.state.name);
+    }
+
+    // Forget the child processes so they keep running
+    for child in result.loopbacks_to_recording {
+        std::mem::forget(child);
+    }
+    std::mem::forget(result.loopback_to_local);
+    Ok(())
+}
+
+/// Poll the graph until a source matching `query` exists, returning the graph
+///
+/// Lets startup scripts run before the application has produced any audio.
+fn wait_for_source(query: &str, config: &Config, timeout: Duration) -> Result<Vec<PwObject>> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        let objects = pipewire::get_fresh_pw_objects()?;
+        let sources = pipewire::extract_audio_sources(&objects, config.source_classes());
+        // An ambiguous name counts as present; resolving it reports the problem
+        if !matches!(
+            resolve(query, "source", &sources),
+            Err(CliError::Core(PwSplitterError::NodeNotFound(_)))
+        ) {
+            return Ok(objects);
+        }
+
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(PwSplitterError::NodeNotFound(format!(
+                "no source matching '{}' appeared within {}s",
+                query,
+                timeout.as_secs()
+            ))
+            .into());
+        }
+        tracing::info!(
+            "waiting for source '{}' ({}s left)",
+            query,
+            remaining.as_secs()
+        );
+        thread::sleep(WAIT_POLL_INTERVAL.min(remaining));
+    }
+}
+
+/// Find the node a query names: a node id, else a unique application name
+pub(super) fn resolve<'a, T: ApplicationNode>(
+    query: &str,
+    kind: &str,
+    nodes: &'a [T],
+) -> Result<&'a T> {
+    if let Some(node) = nodes.iter().find(|n| query.parse() == Ok(n.node_id())) {
+        return Ok(node);
+    }
+
+    pipewire::find_unique_by_name(nodes, query)?
+        .map(|i| &nodes[i])
+        .ok_or_else(|| not_found(kind, query).into())
+}
+
+/// Find a sink by node id, node name or description
+fn resolve_sink<'a>(query: &str, sinks: &'a [AudioSink]) -> Result<&'a AudioSink> {
+    sinks
+        .iter()
+        .find(|s| query.parse() == Ok(s.node_id))
+        .or_else(|| {
+            sinks
+                .iter()
+                .find(|s| s.node_name == query || s.description.eq_ignore_ascii_case(query))
+        })
+        .ok_or_else(|| not_found("sink", query).into())
+}
+
+/// Accept a single string as well as a list of them
+fn one_or_many<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(one) => vec![one],
+        OneOrMany::Many(many) => many,
+    })
+}
+
+fn not_found(kind: &str, query: &str) -> PwSplitterError {
+    PwSplitterError::NodeNotFound(format!("no {} matching '{}'", kind, query))
+}
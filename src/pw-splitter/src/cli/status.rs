@@ -0,0 +1,193 @@
+//! The `status` subcommand: link-level health of a single split.
+//!
+//! `--json` prints the same checks as a report for status bars and health
+//! scripts. Its layout is versioned by `schema_version`: fields may be added
+//! within a version, but are only removed or changed in a new one.
+
+use crate::error::Result;
+use crate::pipewire;
+use crate::splitter::{self, LoopbackHealth, SplitState};
+use serde::Serialize;
+
+/// Version of the `status --json` layout
+const SCHEMA_VERSION: u32 = 1;
+
+/// A node the split was wired to that is no longer the one it expects
+#[derive(Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum Drift {
+    /// The source node is gone, or its id now belongs to another node
+    Source {
+        node_id: u32,
+        node_name: String,
+        /// Node of the restarted source, if exactly one matches
+        restarted_as: Option<u32>,
+    },
+    /// A recording destination is gone, or its id now belongs to another node
+    RecordingDest {
+        loopback_name: String,
+        node_id: u32,
+        application_name: String,
+        media_name: String,
+        /// Node of the restarted destination, if exactly one matches
+        restarted_as: Option<u32>,
+    },
+}
+
+/// Everything `status` checks, as printed by `--json`
+#[derive(Serialize)]
+struct StatusReport<'a> {
+    schema_version: u32,
+    split: &'a SplitState,
+    /// Every loopback is running with its ports and links
+    healthy: bool,
+    /// The local copy is muted (`split.muted_volume` holds its volume from before)
+    muted: bool,
+    loopbacks: &'a [LoopbackHealth],
+    drift: Vec<Drift>,
+    /// Per entry of `split.recordings`, the other splits recording to the same node
+    shared_with: Vec<Vec<String>>,
+}
+
+/// Print each loopback of a split with its PID, ports and expected links
+pub fn run(name: &str, json: bool) -> Result<()> {
+    let state = SplitState::load(name)?;
+    let objects = pipewire::get_pw_objects()?;
+    let health = splitter::inspect_split(&state, &objects);
+
+    let mut drift = Vec::new();
+    if !splitter::source_alive(&state, &objects) {
+        drift.push(Drift::Source {
+            node_id: state.source_node_id,
+            node_name: state.source_node_name.clone(),
+            restarted_as: splitter::find_restarted_source(&state, &objects).map(|s| s.node_id),
+        });
+    }
+    let dests = pipewire::extract_recording_dests(&objects);
+    for recording in &state.recordings {
+        if !splitter::dest_alive(recording, &dests) {
+            drift.push(Drift::RecordingDest {
+                loopback_name: recording.loopback_name.clone(),
+                node_id: recording.dest_node_id,
+                application_name: recording.dest_application_name.clone(),
+                media_name: recording.dest_media_name.clone(),
+                restarted_as: splitter::find_restarted_dest(recording, &dests).map(|d| d.node_id),
+            });
+        }
+    }
+
+    let splits = SplitState::list_all()?;
+    let shared_with: Vec<Vec<String>> = state
+        .recordings
+        .iter()
+        .map(|r| {
+            state
+                .splits_sharing_dest(&splits, r.dest_node_id)
+                .into_iter()
+                .map(str::to_string)
+                .collect()
+        })
+        .collect();
+
+    let report = StatusReport {
+        schema_version: SCHEMA_VERSION,
+        split: &state,
+        healthy: health.iter().all(|l| l.is_healthy()),
+        muted: state.muted_volume.is_some(),
+        loopbacks: &health,
+        drift,
+        shared_with,
+    };
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print_report(&report);
+    }
+    Ok(())
+}
+
+fn print_report(report: &StatusReport) {
+    let state = report.split;
+    println!("Split: {}", state.name);
+    println!(
+        "  Source: {} (node {})",
+        state.source_application_name, state.source_node_id
+    );
+    if let Some(volume) = state.muted_volume {
+        println!("  Local copy: muted (unmutes to {:.0}%)", volume * 100.0);
+    }
+
+    for drift in &report.drift {
+        match drift {
+            Drift::Source { node_id, .. } => {
+                println!("  Warning: source node {} is gone or was replaced", node_id);
+            }
+            Drift::RecordingDest {
+                node_id,
+                application_name,
+                media_name,
+                restarted_as,
+                ..
+            } => {
+                let hint = if restarted_as.is_some() {
+                    format!("; it restarted, run 'pw-splitter reattach {}'", state.name)
+                } else {
+                    String::new()
+                };
+                println!(
+                    "  Warning: recording destination {} [{}] (node {}) is gone{}",
+                    application_name, media_name, node_id, hint
+                );
+            }
+        }
+    }
+
+    for (recording, shared) in state.recordings.iter().zip(&report.shared_with) {
+        if !shared.is_empty() {
+            println!(
+                "  Note: {} is shared with {}; audio is mixed",
+                recording.dest_application_name,
+                shared.join(", ")
+            );
+        }
+    }
+
+    for loopback in report.loopbacks {
+        println!();
+        println!("  Loopback {}", loopback.loopback_name);
+        println!(
+            "    pid {}: {}",
+            loopback.pid,
+            if loopback.running {
+                "running"
+            } else {
+                "not running"
+            }
+        );
+        println!(
+            "    ports: {}",
+            if loopback.ports_ready {
+                "present"
+            } else {
+                "missing"
+            }
+        );
+        for link in &loopback.links {
+            println!(
+                "    [{}] {}",
+                if link.present { "ok" } else { "MISSING" },
+                link.description
+            );
+        }
+    }
+
+    println!();
+    if report.healthy {
+        println!("All loopbacks and links are healthy.");
+    } else {
+        println!(
+            "Some links are broken; try 'pw-splitter restart {}'.",
+            state.name
+        );
+    }
+}
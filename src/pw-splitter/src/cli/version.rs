@@ -0,0 +1,45 @@
+//! `--version`: the crate version plus the PipeWire versions it runs against.
+//!
+//! Behaviour differs across PipeWire releases, so bug reports need both.
+
+use crate::pipewire;
+
+/// Print the crate version, then the PipeWire server and tool versions
+///
+/// Without a running PipeWire only the crate version is printed.
+pub fn print() {
+    println!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+
+    let Ok(objects) = pipewire::get_pw_objects() else {
+        return;
+    };
+    if let Some(version) = pipewire::server_version(&objects) {
+        println!("PipeWire server {}", version);
+    }
+    for tool in ["pw-loopback", pipewire::link_backend().tool()] {
+        if let Some(version) = tool_version(tool) {
+            println!("{} {}", tool, version);
+        }
+    }
+}
+
+/// Library version a PipeWire tool reports with `--version`
+///
+/// The tools print their name, then `Compiled with` and `Linked with`
+/// lines; the linked version is the one actually in use.
+fn tool_version(tool: &str) -> Option<String> {
+    let path = pipewire::find_in_path(tool)?;
+    let output = pipewire::runner()
+        .query(&path.to_string_lossy(), &["--version"])
+        .ok()
+        .filter(|output| output.status.success())?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let version = |prefix: &str| {
+        stdout
+            .lines()
+            .find(|line| line.trim_start().starts_with(prefix))
+            .and_then(|line| line.split_whitespace().last())
+            .map(str::to_string)
+    };
+    version("Linked with").or_else(|| version("Compiled with"))
+}
@@ -0,0 +1,162 @@
+//! User configuration, read from `$XDG_CONFIG_HOME/pw-splitter/config.toml`.
+//!
+//! ```toml
+//! default_recording_dest = "OBS"  # matched against application names
+//! default_local_volume = 0.5      # 1.0 = 100%
+//! default_recording_volume = 0.8  # recording feed of new splits; 1.0 = 100%
+//! port_timeout_ms = 3000          # how long to wait for loopback ports
+//! query_timeout_ms = 10000        # how long pw-dump may take before giving up
+//! include_device_sources = true   # also list microphones and other capture devices
+//! include_sink_monitors = true    # also list sinks, to split all they play
+//! auto_reattach = true            # follow a source app that restarts (TUI)
+//! recording_channel_map = "matching"  # source channels per loopback channel
+//! local_channel_map = "mono"          # ...or a table: { FL = ["FL", "FR"] }
+//! latency = "256/48000"           # loopback node.latency (quantum/rate)
+//! sample_rate = 48000             # loopback sample rate in Hz
+//! refresh_interval_ms = 500       # TUI input poll interval (50-5000)
+//! crash_notifications = true      # notify-send when a loopback crashes (TUI)
+//! auto_restart = false            # only report crashed loopbacks; `r` restarts (TUI)
+//! local_description = "Game Monitor"  # local loopback name in mixers
+//! link_backend = "pw-cli"         # link with pw-cli; default: pw-link if installed
+//! ```
+
+use crate::error::{CliError, Result};
+use crate::pipewire::{self, LinkBackend, LoopbackTuning, RecordingDest, SourceClasses};
+use crate::splitter::{self, ChannelMapping};
+use crate::tui;
+use serde::Deserialize;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Settings from the config file; every key is optional
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Application name of the destination to pre-select (case-insensitive)
+    pub default_recording_dest: Option<String>,
+    /// Local volume applied to new splits
+    pub default_local_volume: Option<f32>,
+    /// Recording volume applied to new splits; full volume if unset
+    pub default_recording_volume: Option<f32>,
+    /// Maximum wait for spawned loopbacks to create their ports
+    ///
+    /// Loopbacks used to get a fixed start-up delay; the old key name is
+    /// accepted as an alias.
+    #[serde(alias = "loopback_init_delay_ms")]
+    pub port_timeout_ms: Option<u64>,
+    /// Maximum time a pw-dump query may take before it's killed
+    pub query_timeout_ms: Option<u64>,
+    /// Offer capture devices (`Audio/Source`) as sources, not just applications
+    pub include_device_sources: Option<bool>,
+    /// Offer sinks (`Audio/Sink`) as sources, captured through their monitor
+    pub include_sink_monitors: Option<bool>,
+    /// Re-link the active split in the TUI when its source application
+    /// restarts; off by default, as the new node is matched by name only
+    pub auto_reattach: Option<bool>,
+    /// Source channels feeding the recording loopbacks
+    pub recording_channel_map: Option<ChannelMapping>,
+    /// Source channels feeding the local loopback
+    pub local_channel_map: Option<ChannelMapping>,
+    /// `node.latency` of spawned loopbacks, as `<quantum>/<rate>`
+    pub latency: Option<String>,
+    /// Sample rate of spawned loopbacks in Hz
+    pub sample_rate: Option<u32>,
+    /// How often the TUI polls for input, in milliseconds
+    pub refresh_interval_ms: Option<u64>,
+    /// Show a desktop notification when the TUI restarts a crashed loopback
+    pub crash_notifications: Option<bool>,
+    /// Restart crashed loopbacks in the TUI; when off they're only reported
+    pub auto_restart: Option<bool>,
+    /// `node.description` of the local loopback, shown in pwvucontrol
+    pub local_description: Option<String>,
+    /// Tool to link ports with; detected from the installed tools when unset
+    pub link_backend: Option<LinkBackend>,
+}
+
+impl Config {
+    /// Path of the config file, if a config directory can be determined
+    pub fn path() -> Option<PathBuf> {
+        let config_home = env::var_os("XDG_CONFIG_HOME")
+            .filter(|d| !d.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        Some(config_home.join("pw-splitter").join("config.toml"))
+    }
+
+    /// Load the config file; a missing file yields the defaults
+    pub fn load() -> Result<Self> {
+        let Some(path) = Self::path() else {
+            return Ok(Self::default());
+        };
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let text = fs::read_to_string(&path)
+            .map_err(|e| CliError::ConfigError(format!("cannot read {}: {}", path.display(), e)))?;
+        let config: Config = toml::from_str(&text)
+            .map_err(|e| CliError::ConfigError(format!("{}: {}", path.display(), e)))?;
+
+        for (key, volume) in [
+            ("default_local_volume", config.default_local_volume),
+            ("default_recording_volume", config.default_recording_volume),
+        ] {
+            if let Some(volume) = volume {
+                splitter::validate_volume(volume).map_err(|e| {
+                    CliError::ConfigError(format!("{}: {}: {}", path.display(), key, e))
+                })?;
+            }
+        }
+        if let Some(poll_ms) = config.refresh_interval_ms {
+            tui::poll::validate_refresh_interval(poll_ms)
+                .map_err(|e| CliError::ConfigError(format!("{}: {}", path.display(), e)))?;
+        }
+        config
+            .loopback_tuning()
+            .validate()
+            .map_err(|e| CliError::ConfigError(format!("{}: {}", path.display(), e)))?;
+        Ok(config)
+    }
+
+    /// Loopback latency and rate from the config
+    pub fn loopback_tuning(&self) -> LoopbackTuning {
+        LoopbackTuning {
+            latency: self.latency.clone(),
+            rate: self.sample_rate,
+        }
+    }
+
+    /// Index of the configured default destination within `destinations`
+    ///
+    /// Fails if several destinations share the configured name.
+    pub fn default_dest_index(&self, destinations: &[RecordingDest]) -> Result<Option<usize>> {
+        match self.default_recording_dest.as_deref() {
+            Some(wanted) => Ok(pipewire::find_unique_by_name(destinations, wanted)?),
+            None => Ok(None),
+        }
+    }
+
+    /// Media classes to offer as sources
+    pub fn source_classes(&self) -> SourceClasses {
+        SourceClasses {
+            include_devices: self.include_device_sources.unwrap_or(false),
+            include_sink_monitors: self.include_sink_monitors.unwrap_or(false),
+        }
+    }
+
+    /// Configured port timeout, or [`pipewire::DEFAULT_PORT_TIMEOUT`]
+    pub fn port_timeout(&self) -> Duration {
+        self.port_timeout_ms
+            .map(Duration::from_millis)
+            .unwrap_or(pipewire::DEFAULT_PORT_TIMEOUT)
+    }
+
+    /// Configured query timeout, or [`pipewire::DEFAULT_QUERY_TIMEOUT`]
+    pub fn query_timeout(&self) -> Duration {
+        self.query_timeout_ms
+            .map(Duration::from_millis)
+            .unwrap_or(pipewire::DEFAULT_QUERY_TIMEOUT)
+    }
+}
@@ -1,36 +1,55 @@
 use thiserror::Error;
 
+pub use pw_splitter_core::error::PwSplitterError;
+
+/// Errors of the command line and TUI: the library's, plus those only they raise
 #[derive(Error, Debug)]
-pub enum PwSplitterError {
-    #[error("Failed to execute PipeWire command: {0}")]
-    CommandFailed(String),
+pub enum CliError {
+    #[error(transparent)]
+    Core(#[from] PwSplitterError),
+
+    #[error("{0} doctor check(s) failed")]
+    ChecksFailed(usize),
+
+    #[error("Invalid refresh interval {0}ms: expected {1} to {2}ms")]
+    InvalidRefreshInterval(u64, u64, u64),
 
-    #[error("Failed to parse PipeWire output: {0}")]
-    ParseError(String),
+    #[error("'{0}' already records to {1}")]
+    AlreadyRecorded(String, String),
 
-    #[error("Node not found: {0}")]
-    NodeNotFound(String),
+    #[error("No profile named '{0}' (see 'pw-splitter profile list')")]
+    ProfileNotFound(String),
 
-    #[error("No active connection found for source")]
-    NoActiveConnection,
+    #[error("Invalid profile name '{0}': it must be non-empty and contain no path separators")]
+    InvalidProfileName(String),
 
-    #[error("Failed to spawn loopback: {0}")]
-    LoopbackSpawnFailed(String),
+    #[error("The daemon is already running (pid {0}); stop it with 'pw-splitter daemon --stop'")]
+    DaemonAlreadyRunning(u32),
 
-    #[error("Failed to create link: {0}")]
-    LinkCreationFailed(String),
+    #[error("{0} is still enabled; run 'systemctl --user disable --now {0}' first")]
+    ServiceEnabled(String),
 
-    #[error("Failed to destroy link: {0}")]
-    LinkDestroyFailed(String),
+    #[error("{0} split(s) could not be stopped")]
+    StopFailed(usize),
 
-    #[error("State file error: {0}")]
-    StateFileError(String),
+    #[error("{0} split(s) could not be pruned")]
+    PruneFailed(usize),
 
-    #[error("IO error: {0}")]
-    IoError(#[from] std::io::Error),
+    #[error("Config error: {0}")]
+    ConfigError(String),
+}
+
+impl From<std::io::Error> for CliError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Core(e.into())
+    }
+}
 
-    #[error("JSON error: {0}")]
-    JsonError(#[from] serde_json::Error),
+impl From<serde_json::Error> for CliError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Core(e.into())
+    }
 }
 
-pub type Result<T> = std::result::Result<T, PwSplitterError>;
+/// Result type used throughout the command line and TUI
+pub type Result<T> = std::result::Result<T, CliError>;
@@ -0,0 +1,41 @@
+//! Diagnostic logging through [`tracing`].
+//!
+//! Subcommands log to stderr. The TUI owns the terminal, so in TUI mode logs
+//! go to `pw-splitter.log` in the state directory instead.
+//!
+//! Failed split setups are additionally recorded by
+//! [`pw_splitter_core::failure_log`].
+
+use crate::splitter::SplitState;
+use std::fs::{self, File};
+use std::io;
+use std::sync::Mutex;
+use tracing::Level;
+
+/// Name of the log file written in TUI mode
+pub const LOG_FILE: &str = "pw-splitter.log";
+
+/// Install the global subscriber
+///
+/// Warnings and errors are always logged; `verbose` adds progress and debug
+/// detail such as every pw-link call and discovered port counts.
+pub fn init(verbose: bool, tui: bool) {
+    let level = if verbose { Level::DEBUG } else { Level::WARN };
+    let builder = tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_target(false);
+
+    if tui {
+        let dir = SplitState::state_dir();
+        match fs::create_dir_all(&dir).and_then(|_| File::create(dir.join(LOG_FILE))) {
+            Ok(file) => builder
+                .with_ansi(false)
+                .with_writer(Mutex::new(file))
+                .init(),
+            // Logging must never get in the way of the TUI itself
+            Err(_) => builder.with_writer(io::sink).init(),
+        }
+    } else {
+        builder.with_writer(io::stderr).init();
+    }
+}
@@ -1,10 +1,40 @@
+mod cli;
+mod config;
 mod error;
-mod pipewire;
-mod splitter;
+mod logging;
 mod tui;
 
+use error::{CliError, PwSplitterError};
 use pico_args::Arguments;
-use splitter::SplitState;
+use pw_splitter_core::{pipewire, splitter};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Non-interactive subcommands; anything else starts the TUI
+const SUBCOMMANDS: [&str; 19] = [
+    "list",
+    "status",
+    "start",
+    "stop",
+    "restart",
+    "volume",
+    "doctor",
+    "stop-all",
+    "prune",
+    "tag",
+    "rename",
+    "logs",
+    "reattach",
+    "profile",
+    "mute",
+    "unmute",
+    "clone",
+    "daemon",
+    "install-service",
+];
+
+/// Subcommands that honour `--dry-run`
+const DRY_RUN_SUBCOMMANDS: [&str; 6] = ["start", "stop", "stop-all", "prune", "profile", "clone"];
 
 fn main() {
     let mut args = Arguments::from_env();
@@ -12,89 +42,334 @@ fn main() {
     let version = args.contains(["-V", "--version"]);
 
     if version {
-        println!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+        cli::version::print();
         return;
     }
 
+    let yes = args.contains(["-y", "--yes"]);
+    let verbose = args.contains(["-v", "--verbose"]);
+    let dry_run = args.contains("--dry-run");
+    let no_auto_restart = args.contains("--no-auto-restart");
+    let refresh_interval_ms: Option<u64> = args
+        .opt_value_from_str("--refresh-interval")
+        .unwrap_or_else(|e| {
+            eprintln!("Error: '--refresh-interval' takes milliseconds: {}", e);
+            std::process::exit(1);
+        });
+    cli::set_quiet(args.contains(["-q", "--quiet"]));
+
     let subcommand: Option<String> = args.subcommand().ok().flatten();
+    let is_tui = subcommand
+        .as_deref()
+        .is_none_or(|s| !SUBCOMMANDS.contains(&s));
+    logging::init(verbose, is_tui);
+
+    // A broken config is reported by the commands that need the rest of it
+    if let Ok(config) = config::Config::load() {
+        pipewire::set_query_timeout(config.query_timeout());
+        pipewire::set_link_backend(config.link_backend);
+    }
+
+    if dry_run
+        && !subcommand
+            .as_deref()
+            .is_some_and(|s| DRY_RUN_SUBCOMMANDS.contains(&s))
+    {
+        eprintln!(
+            "Error: '--dry-run' is only supported by: {}",
+            DRY_RUN_SUBCOMMANDS.join(", ")
+        );
+        std::process::exit(1);
+    }
+    let dry_run_runner = dry_run.then(|| Arc::new(pipewire::DryRunRunner::default()));
+    if let Some(runner) = &dry_run_runner {
+        pipewire::set_runner(runner.clone());
+    }
 
     let result = match subcommand.as_deref() {
-        Some("list") => list_splits(),
+        Some("list") => {
+            // `--json` predates `--format` and is kept as a shorthand
+            let json = args.contains("--json");
+            let format: Option<cli::list::ListFormat> =
+                args.opt_value_from_str("--format").unwrap_or_else(|e| {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                });
+            let format = match (format, json) {
+                (Some(format), _) => format,
+                (None, true) => cli::list::ListFormat::Json,
+                (None, false) => cli::list::ListFormat::Table,
+            };
+            let filter = match (
+                args.contains("--dead-only"),
+                args.contains("--running-only"),
+            ) {
+                (true, true) => {
+                    eprintln!("Error: '--dead-only' and '--running-only' can't be combined");
+                    std::process::exit(1);
+                }
+                (true, false) => cli::list::HealthFilter::Dead,
+                (false, true) => cli::list::HealthFilter::Running,
+                (false, false) => cli::list::HealthFilter::All,
+            };
+            cli::list::run(format, filter)
+        }
+        Some("status") => {
+            let json = args.contains("--json");
+            let name: String = args.free_from_str().unwrap_or_else(|_| {
+                eprintln!("Error: missing split name for 'status' command");
+                std::process::exit(1);
+            });
+            cli::status::run(&name, json)
+        }
+        Some("start") => cli::start::run(&parse_start_options(&mut args, "start")),
+        Some("clone") => {
+            let dest: String = args.value_from_str("--dest").unwrap_or_else(|_| {
+                eprintln!("Error: 'clone' needs --dest <name or node id>");
+                std::process::exit(1);
+            });
+            let name: String = args.free_from_str().unwrap_or_else(|_| {
+                eprintln!("Error: missing split name for 'clone' command");
+                std::process::exit(1);
+            });
+            cli::clone::run(&name, &dest)
+        }
         Some("stop") => {
+            let no_restore = args.contains("--no-restore");
+            let source: Option<String> = args.opt_value_from_str("--source").unwrap_or_else(|_| {
+                eprintln!("Error: '--source' requires an application name");
+                std::process::exit(1);
+            });
+            if let Some(source) = source {
+                cli::manage::stop_source_splits(&source, !no_restore)
+            } else {
+                let name: String = args.free_from_str().unwrap_or_else(|_| {
+                    eprintln!("Error: missing split name for 'stop' command");
+                    std::process::exit(1);
+                });
+                cli::manage::stop_split(&name, !no_restore)
+            }
+        }
+        Some("restart") => {
+            let name: String = args.free_from_str().unwrap_or_else(|_| {
+                eprintln!("Error: missing split name for 'restart' command");
+                std::process::exit(1);
+            });
+            cli::manage::restart_split(&name)
+        }
+        Some("reattach") => {
+            let name: String = args.free_from_str().unwrap_or_else(|_| {
+                eprintln!("Error: missing split name for 'reattach' command");
+                std::process::exit(1);
+            });
+            cli::manage::reattach_split(&name)
+        }
+        Some("volume") => {
             let name: String = args.free_from_str().unwrap_or_else(|_| {
-                eprintln!("Error: missing split name for 'stop' command");
+                eprintln!("Error: missing split name for 'volume' command");
                 std::process::exit(1);
             });
-            stop_split(&name)
+            let level: f32 = args.free_from_str().unwrap_or_else(|_| {
+                eprintln!("Error: missing or invalid volume level (e.g. 0.5) for 'volume' command");
+                std::process::exit(1);
+            });
+            cli::manage::set_volume(&name, level)
+        }
+        Some(command @ ("mute" | "unmute")) => {
+            let name: String = args.free_from_str().unwrap_or_else(|_| {
+                eprintln!("Error: missing split name for '{}' command", command);
+                std::process::exit(1);
+            });
+            cli::manage::set_muted(&name, command == "mute")
         }
-        Some("stop-all") => stop_all_splits(),
-        None | Some(_) => run_tui(),
+        Some("doctor") => cli::doctor::run(),
+        Some("daemon") => {
+            if args.contains("--stop") {
+                cli::daemon::stop()
+            } else {
+                cli::daemon::run(args.contains("--foreground"), verbose)
+            }
+        }
+        Some("install-service") => {
+            if args.contains("--uninstall") {
+                cli::service::uninstall()
+            } else {
+                cli::service::install()
+            }
+        }
+        Some("stop-all") => {
+            let tag: Option<String> = args.opt_value_from_str("--tag").unwrap_or_else(|_| {
+                eprintln!("Error: '--tag' requires a value");
+                std::process::exit(1);
+            });
+            cli::manage::stop_all_splits(tag.as_deref())
+        }
+        Some("prune") => {
+            let older_than: Option<Duration> = args
+                .opt_value_from_fn("--older-than", cli::parse_duration)
+                .unwrap_or_else(|e| {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                });
+            cli::manage::prune_splits(dry_run, older_than)
+        }
+        Some("tag") => {
+            let name: String = args.free_from_str().unwrap_or_else(|_| {
+                eprintln!("Error: missing split name for 'tag' command");
+                std::process::exit(1);
+            });
+            let tags: Vec<String> = args
+                .finish()
+                .into_iter()
+                .map(|t| t.to_string_lossy().into_owned())
+                .collect();
+            if tags.is_empty() {
+                eprintln!("Error: missing tag(s) for 'tag' command");
+                std::process::exit(1);
+            }
+            cli::manage::tag_split(&name, &tags)
+        }
+        Some("rename") => {
+            let old: String = args.free_from_str().unwrap_or_else(|_| {
+                eprintln!("Error: missing split name for 'rename' command");
+                std::process::exit(1);
+            });
+            let new: String = args.free_from_str().unwrap_or_else(|_| {
+                eprintln!("Error: missing new name for 'rename' command");
+                std::process::exit(1);
+            });
+            cli::manage::rename_split(&old, &new)
+        }
+        Some("logs") => {
+            let lines: usize = args
+                .opt_value_from_str(["-n", "--lines"])
+                .unwrap_or_else(|_| {
+                    eprintln!("Error: '--lines' requires a number");
+                    std::process::exit(1);
+                })
+                .unwrap_or(40);
+            cli::logs::run(lines)
+        }
+        Some("profile") => {
+            let action: String = args.free_from_str().unwrap_or_else(|_| {
+                eprintln!("Error: 'profile' needs an action: save, list or apply");
+                std::process::exit(1);
+            });
+            let mut name = |action: &str| -> String {
+                args.free_from_str().unwrap_or_else(|_| {
+                    eprintln!("Error: missing profile name for 'profile {}'", action);
+                    std::process::exit(1);
+                })
+            };
+            match action.as_str() {
+                "save" => {
+                    let name = name("save");
+                    cli::profile::save(&name, &parse_start_options(&mut args, "profile save"))
+                }
+                "list" => cli::profile::list(),
+                "apply" => cli::profile::apply(&name("apply")),
+                _ => {
+                    eprintln!("Error: unknown profile action '{}'", action);
+                    std::process::exit(1);
+                }
+            }
+        }
+        None | Some(_) => run_tui(yes, refresh_interval_ms, no_auto_restart),
     };
 
+    if let Some(runner) = &dry_run_runner {
+        cli::report_dry_run(&runner.take_commands());
+    }
     if let Err(e) = result {
         eprintln!("Error: {}", e);
-        std::process::exit(1);
+        std::process::exit(exit_code(&e));
     }
 }
 
-fn run_tui() -> error::Result<()> {
-    tui::run()
-}
-
-fn list_splits() -> error::Result<()> {
-    let splits = SplitState::list_all()?;
-
-    if splits.is_empty() {
-        println!("No active splits.");
-        return Ok(());
+/// Process exit code for a failed command
+///
+/// | Code | Meaning               | Errors                                                     |
+/// | ---- | --------------------- | ---------------------------------------------------------- |
+/// | 0    | Success               |                                                            |
+/// | 1    | General error         | everything else                                            |
+/// | 2    | Not found             | `NodeNotFound`, `SplitNotFound`, `ProfileNotFound`         |
+/// | 3    | PipeWire unavailable  | `PipeWireNotRunning`, `CommandTimeout`, `ToolNotInstalled` |
+fn exit_code(error: &CliError) -> i32 {
+    match error {
+        CliError::ProfileNotFound(_)
+        | CliError::Core(PwSplitterError::NodeNotFound(_) | PwSplitterError::SplitNotFound(_)) => 2,
+        CliError::Core(
+            PwSplitterError::PipeWireNotRunning
+            | PwSplitterError::CommandTimeout(..)
+            | PwSplitterError::ToolNotInstalled(..),
+        ) => 3,
+        _ => 1,
     }
+}
 
-    println!("Active splits:");
-    println!("{:-<60}", "");
-
-    for split in splits {
-        let (recording_running, local_running) = splitter::check_loopbacks_running(&split);
-
-        println!("Name: {}", split.name);
-        println!("  Source: {}", split.source_application_name);
-        println!(
-            "  Recording to: {} [{}]",
-            split.recording_dest_application_name, split.recording_dest_media_name
-        );
-        println!("  Local output: {}", split.original_output_node_name);
-        println!(
-            "  Loopbacks: recording={}, local={}",
-            if recording_running {
-                "running"
-            } else {
-                "stopped"
-            },
-            if local_running { "running" } else { "stopped" }
+/// Parse the node options shared by `start` and `profile save`
+fn parse_start_options(args: &mut Arguments, command: &str) -> cli::start::StartOptions {
+    let source: String = args.value_from_str("--source").unwrap_or_else(|_| {
+        eprintln!("Error: '{}' needs --source <name or node id>", command);
+        std::process::exit(1);
+    });
+    let dests: Vec<String> = args.values_from_str("--dest").unwrap_or_default();
+    if dests.is_empty() {
+        eprintln!(
+            "Error: '{}' needs at least one --dest <name or node id>",
+            command
         );
-        println!("{:-<60}", "");
+        std::process::exit(1);
+    }
+    let sinks: Vec<String> = args.values_from_str("--sink").unwrap_or_else(|_| {
+        eprintln!("Error: '--sink' requires a value");
+        std::process::exit(1);
+    });
+    let volume: Option<f32> = args.opt_value_from_str("--volume").unwrap_or_else(|_| {
+        eprintln!("Error: '--volume' requires a level (e.g. 0.5)");
+        std::process::exit(1);
+    });
+    let latency: Option<String> = args.opt_value_from_str("--latency").unwrap_or_else(|_| {
+        eprintln!("Error: '--latency' requires a value (e.g. 1024/48000)");
+        std::process::exit(1);
+    });
+    let rate: Option<u32> = args.opt_value_from_str("--rate").unwrap_or_else(|_| {
+        eprintln!("Error: '--rate' requires a sample rate in Hz (e.g. 48000)");
+        std::process::exit(1);
+    });
+    let description: Option<String> =
+        args.opt_value_from_str("--description")
+            .unwrap_or_else(|_| {
+                eprintln!("Error: '--description' requires a name for the local loopback");
+                std::process::exit(1);
+            });
+    let wait: Option<u64> = args.opt_value_from_str("--wait").unwrap_or_else(|_| {
+        eprintln!("Error: '--wait' requires a number of seconds");
+        std::process::exit(1);
+    });
+    cli::start::StartOptions {
+        source,
+        dests,
+        sinks,
+        volume,
+        latency,
+        rate,
+        description,
+        wait: wait.map(Duration::from_secs),
     }
-
-    Ok(())
-}
-
-fn stop_split(name: &str) -> error::Result<()> {
-    println!("Stopping split: {}", name);
-    splitter::stop_split(name)?;
-    println!("Split stopped successfully.");
-    Ok(())
 }
 
-fn stop_all_splits() -> error::Result<()> {
-    let stopped = splitter::stop_all_splits()?;
-
-    if stopped.is_empty() {
-        println!("No active splits to stop.");
-    } else {
-        println!("Stopped {} split(s):", stopped.len());
-        for name in stopped {
-            println!("  - {}", name);
-        }
+fn run_tui(
+    auto_confirm: bool,
+    refresh_interval_ms: Option<u64>,
+    no_auto_restart: bool,
+) -> error::Result<()> {
+    let refresh_interval = refresh_interval_ms
+        .map(tui::poll::validate_refresh_interval)
+        .transpose()?;
+    let mut config = config::Config::load()?;
+    if no_auto_restart {
+        config.auto_restart = Some(false);
     }
-
-    Ok(())
+    tui::run(config, auto_confirm, refresh_interval)
 }
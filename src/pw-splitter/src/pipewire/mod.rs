@@ -1,7 +0,0 @@
-pub mod commands;
-pub mod parser;
-pub mod types;
-
-pub use commands::*;
-pub use parser::*;
-pub use types::*;
@@ -1,7 +0,0 @@
-pub mod cleanup;
-pub mod setup;
-pub mod state;
-
-pub use cleanup::*;
-pub use setup::*;
-pub use state::*;
@@ -0,0 +1,401 @@
+//! Volume control and loopback supervision while a split is active.
+
+use crate::cli::daemon;
+use crate::pipewire;
+use crate::splitter::{self, MAX_RESTART_ATTEMPTS};
+use crate::tui::app::{App, AppState};
+use crate::tui::children::LoopbackStatus;
+use crate::tui::selection::find_live_source;
+use std::io;
+use std::process::{Command, Stdio};
+use std::thread;
+
+/// Volume mixers to open with `v`, in order of preference
+const MIXERS: [&str; 2] = ["pwvucontrol", "pavucontrol"];
+
+/// Run a GUI helper next to the TUI, without waiting for it
+fn spawn_detached(program: &str, args: &[&str]) -> io::Result<()> {
+    // Its output would draw over the TUI
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    // Reap it when it exits, so it doesn't linger as a zombie
+    thread::spawn(move || child.wait());
+    Ok(())
+}
+
+/// Show a desktop notification through `notify-send`, if it's installed
+fn notify(summary: &str, body: &str) {
+    if let Err(e) = spawn_detached("notify-send", &["--app-name=pw-splitter", summary, body]) {
+        tracing::debug!("notify-send: {}", e);
+    }
+}
+
+impl App {
+    /// Current local volume of the active split (1.0 = 100%)
+    pub fn local_volume(&self) -> f32 {
+        self.active_split
+            .as_ref()
+            .map(|s| s.local_volume)
+            .unwrap_or(1.0)
+    }
+
+    /// Change the local volume of the active split by `delta`
+    pub fn adjust_volume(&mut self, delta: f32) {
+        let Some(state) = &mut self.active_split else {
+            return;
+        };
+
+        // Snap to whole steps so repeated presses don't accumulate float drift
+        let steps = ((state.local_volume + delta) / splitter::VOLUME_STEP).round();
+        let volume = (steps * splitter::VOLUME_STEP).clamp(0.0, splitter::MAX_VOLUME);
+
+        match splitter::set_local_volume(state, volume) {
+            Ok(()) => {
+                self.status_message = format!("Local volume: {:.0}%", volume * 100.0);
+            }
+            Err(e) => {
+                self.status_message = format!("Failed to set volume: {}", e);
+            }
+        }
+    }
+
+    /// Change the recording volume of the active split by `delta`
+    pub fn adjust_recording_volume(&mut self, delta: f32) {
+        let Some(state) = &mut self.active_split else {
+            return;
+        };
+
+        let steps = ((state.recording_volume + delta) / splitter::VOLUME_STEP).round();
+        let volume = (steps * splitter::VOLUME_STEP).clamp(0.0, splitter::MAX_VOLUME);
+
+        self.status_message = match splitter::set_recording_volume(state, volume) {
+            Ok(()) => format!("Recording volume: {:.0}%", volume * 100.0),
+            Err(e) => format!("Failed to set recording volume: {}", e),
+        };
+    }
+
+    /// Mute or unmute the local copy of the active split (`m`)
+    pub fn toggle_mute(&mut self) {
+        let Some(state) = &mut self.active_split else {
+            return;
+        };
+
+        let result = if state.muted_volume.is_some() {
+            splitter::unmute_local(state).map(|_| "Local copy unmuted")
+        } else {
+            splitter::mute_local(state).map(|_| "Local copy muted; recording continues")
+        };
+        self.status_message = match result {
+            Ok(message) => message.to_string(),
+            Err(e) => format!("Failed to mute: {}", e),
+        };
+    }
+
+    /// Ask before stopping the active split (Enter on the active screen)
+    pub fn request_stop(&mut self) {
+        if self.active_split.is_some() {
+            self.state = AppState::ConfirmStop;
+            self.status_message =
+                "Warning: stop this split? y/Enter: Stop | n/Esc: Keep".to_string();
+        }
+    }
+
+    /// Handle Ctrl-C: with a split active, ask whether to keep it running
+    ///
+    /// Splits outlive the TUI by design, so keeping it is the default; a
+    /// second Ctrl-C picks that.
+    pub fn interrupt(&mut self) {
+        match self.state {
+            AppState::Active | AppState::ConfirmStop => {
+                self.state = AppState::ConfirmQuit;
+                self.status_message =
+                    "Quit: Enter/k: Keep split running | s: Stop split | Esc: Cancel".to_string();
+            }
+            AppState::ConfirmQuit => self.should_quit = true,
+            // Quitting mid-setup would leave a half-wired split behind
+            AppState::Creating(_) => {}
+            _ => self.should_quit = true,
+        }
+    }
+
+    /// Stop the active split, then quit if that worked
+    pub fn stop_and_quit(&mut self) {
+        self.stop_active_split();
+        self.should_quit = self.state == AppState::Done;
+    }
+
+    /// Tear down the active split, keeping its state so the stop can be undone
+    pub fn stop_active_split(&mut self) {
+        let Some(state) = self.active_split.take() else {
+            return;
+        };
+
+        match splitter::teardown_split(&state, true, pipewire::runner()) {
+            Ok(()) => {
+                self.loopback_children.reap();
+                self.status_message = "Split stopped successfully".to_string();
+                self.last_stopped = Some(state);
+                self.state = AppState::Done;
+            }
+            Err(e) => {
+                self.status_message = format!("Failed to stop split: {}", e);
+                self.active_split = Some(state);
+                self.state = AppState::Active;
+            }
+        }
+    }
+
+    /// Recreate the split stopped last, with the same source, destinations,
+    /// output and volume (`u` on the done screen)
+    pub fn undo_stop(&mut self) {
+        if self.state != AppState::Done {
+            return;
+        }
+        let Some(stopped) = self.last_stopped.clone() else {
+            self.status_message = "Nothing to undo".to_string();
+            return;
+        };
+        let objects = match pipewire::get_pw_objects() {
+            Ok(objects) => objects,
+            Err(e) => {
+                self.status_message = format!("Failed to undo: {}", e);
+                return;
+            }
+        };
+
+        let Some(source) =
+            find_live_source(&objects, stopped.source_node_id, &stopped.source_node_name)
+        else {
+            self.status_message = "Failed to undo: source no longer available".to_string();
+            return;
+        };
+        let destinations = pipewire::extract_recording_dests(&objects);
+        let dests: Vec<_> = stopped
+            .recordings
+            .iter()
+            .filter_map(|r| destinations.iter().find(|d| d.node_id == r.dest_node_id))
+            .cloned()
+            .collect();
+        if dests.len() != stopped.recordings.len() {
+            self.status_message =
+                "Failed to undo: a recording destination is no longer available".to_string();
+            return;
+        }
+
+        self.source_connections = pipewire::find_source_connections(source.node_id, &objects);
+        self.local_sinks = pipewire::extract_audio_sinks(&objects)
+            .into_iter()
+            .filter(|s| stopped.local_output_names().any(|name| name == s.node_name))
+            .collect();
+        self.selected_source = Some(source);
+        self.selected_dests = dests;
+        self.last_stopped = None;
+        self.execute_split_with_volume(stopped.local_volume);
+    }
+
+    /// Shift the local copy's balance of the active split by `delta`
+    pub fn shift_balance(&mut self, delta: f32) {
+        let Some(state) = &mut self.active_split else {
+            return;
+        };
+
+        self.status_message = match splitter::shift_local_balance(state, delta) {
+            Ok(balance) if balance < 0.0 => format!("Balance: {:.0}% left", -balance * 100.0),
+            Ok(balance) if balance > 0.0 => format!("Balance: {:.0}% right", balance * 100.0),
+            Ok(_) => "Balance: centered".to_string(),
+            Err(e) => format!("Failed to set balance: {}", e),
+        };
+    }
+
+    /// Open a volume mixer next to the TUI, without waiting for it
+    pub fn open_mixer(&mut self) {
+        for mixer in MIXERS {
+            match spawn_detached(mixer, &[]) {
+                Ok(()) => {
+                    self.status_message = format!("Opened {}", mixer);
+                    return;
+                }
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => {
+                    self.status_message = format!("Failed to open {}: {}", mixer, e);
+                    return;
+                }
+            }
+        }
+        self.status_message = format!("No volume mixer found (install {})", MIXERS.join(" or "));
+    }
+
+    /// Report stopped loopbacks without restarting them, leaving that to `r`
+    ///
+    /// `why` says how they stopped, e.g. `crashed`. A crash is announced on
+    /// the desktop once, not on every health check.
+    fn report_crashes(&mut self, crashed: Vec<Option<usize>>, why: &str) {
+        let Some(state) = &self.active_split else {
+            return;
+        };
+        if self.config.crash_notifications.unwrap_or(false) {
+            for &index in crashed
+                .iter()
+                .filter(|c| !self.pending_restarts.contains(c))
+            {
+                let (label, name) = loopback_label(state, index);
+                notify(
+                    &format!("{} loopback of {} {}", label, state.name, why),
+                    &format!("{}: not restarted, press r to restart", name),
+                );
+            }
+        }
+        if !crashed.is_empty() {
+            let names: Vec<String> = crashed
+                .iter()
+                .map(|&index| loopback_label(state, index).1)
+                .collect();
+            self.status_message =
+                format!("Warning: {} {}; press r to restart", names.join(", "), why);
+        }
+        self.pending_restarts = crashed;
+    }
+
+    /// Restart the loopbacks reported as crashed or killed (`r`)
+    pub fn restart_crashed_loopbacks(&mut self) {
+        let Some(state) = &mut self.active_split else {
+            return;
+        };
+        let crashed = std::mem::take(&mut self.pending_restarts);
+        if crashed.is_empty() {
+            self.status_message = "No crashed loopbacks to restart".to_string();
+            return;
+        }
+
+        let failures: Vec<String> = crashed
+            .into_iter()
+            .filter_map(|index| {
+                let result = match index {
+                    Some(index) => splitter::restart_loopback_to_recording(state, index),
+                    None => splitter::restart_loopback_to_local(state),
+                };
+                let name = loopback_label(state, index).1;
+                match result {
+                    Ok(child) => {
+                        self.loopback_children.replace(index, child);
+                        None
+                    }
+                    Err(e) => Some(format!("{}: {}", name, e)),
+                }
+            })
+            .collect();
+        self.status_message = if failures.is_empty() {
+            "Crashed loopbacks restarted".to_string()
+        } else {
+            format!("Failed to restart {}", failures.join("; "))
+        };
+    }
+
+    /// Check if loopback processes are still running and restart if needed
+    ///
+    /// Each loopback is restarted at most once per [`splitter::RESTART_COOLDOWN`], and
+    /// given up on after [`MAX_RESTART_ATTEMPTS`] consecutive failures. With
+    /// `crash_notifications` set, each attempt is also announced on the desktop.
+    /// With `auto_restart = false`, crashes are only reported, as are
+    /// loopbacks killed on purpose (see [`LoopbackStatus::Killed`]). While the
+    /// daemon runs, all of this is left to it.
+    pub fn check_and_restart_loopbacks(&mut self) {
+        let Some(state) = &mut self.active_split else {
+            return;
+        };
+        // The daemon supervises every split; restarting here as well could
+        // spawn a loopback twice
+        if daemon::running_pid().is_some() {
+            self.loopbacks_running = Some(self.loopback_children.statuses(state));
+            return;
+        }
+
+        match splitter::relink_recording_dests(state) {
+            Ok(0) => {}
+            Ok(_) => self.status_message = "Recording destination restarted, relinked".to_string(),
+            Err(e) => tracing::debug!("relink: {}", e),
+        }
+        if self.config.auto_reattach.unwrap_or(false) {
+            match splitter::reattach_split(state) {
+                Ok(true) => self.status_message = "Source restarted, reattached".to_string(),
+                Ok(false) => {}
+                // Keep waiting; the app may still be starting up
+                Err(e) => tracing::debug!("reattach: {}", e),
+            }
+        }
+        let (recording_status, local_status) = self.loopback_children.statuses(state);
+        // Shown until the next check, so a restarted loopback flashes as stopped
+        self.loopbacks_running = Some((recording_status.clone(), local_status));
+
+        let stopped = recording_status
+            .into_iter()
+            .enumerate()
+            .map(|(index, status)| (Some(index), status))
+            .chain([(None, local_status)])
+            .filter(|(_, status)| !status.is_running());
+        let (killed, crashed): (Vec<_>, Vec<_>) =
+            stopped.partition(|(_, status)| matches!(status, LoopbackStatus::Killed(_)));
+        let killed: Vec<Option<usize>> = killed.into_iter().map(|(index, _)| index).collect();
+        let crashed: Vec<Option<usize>> = crashed.into_iter().map(|(index, _)| index).collect();
+
+        if !self.config.auto_restart.unwrap_or(true) {
+            let mut stopped = crashed;
+            stopped.extend(killed);
+            self.report_crashes(stopped, "crashed or was killed");
+            return;
+        }
+        self.report_crashes(killed, "was killed");
+        let Some(state) = &mut self.active_split else {
+            return;
+        };
+
+        for recording_index in crashed {
+            let (label, name) = loopback_label(state, recording_index);
+            let tracker = self.restart_trackers.entry(name.clone()).or_default();
+            if !tracker.may_attempt() {
+                continue;
+            }
+
+            let result = match recording_index {
+                Some(index) => splitter::restart_loopback_to_recording(state, index),
+                None => splitter::restart_loopback_to_local(state),
+            };
+            tracker.record(result.is_ok());
+            let result = result.map(|child| self.loopback_children.replace(recording_index, child));
+
+            if self.config.crash_notifications.unwrap_or(false) {
+                let outcome = match &result {
+                    Ok(_) => "restarted it".to_string(),
+                    Err(_) if tracker.gave_up() => "restarting keeps failing, gave up".to_string(),
+                    Err(e) => format!("restart failed: {}", e),
+                };
+                notify(
+                    &format!("{} loopback of {} crashed", label, state.name),
+                    &format!("{}: {}", name, outcome),
+                );
+            }
+
+            self.status_message = match result {
+                Ok(_) => format!("{} loopback restarted", label),
+                Err(_) if tracker.gave_up() => format!(
+                    "Error: {} failed to restart {} times, giving up; stop the split or run \
+                     'pw-splitter restart {}'",
+                    name, MAX_RESTART_ATTEMPTS, state.name
+                ),
+                Err(e) => format!("Failed to restart {} loopback: {}", label.to_lowercase(), e),
+            };
+        }
+    }
+}
+
+/// Kind and node name of a loopback: the recording one at `index`, or the local one
+fn loopback_label(state: &splitter::SplitState, index: Option<usize>) -> (&'static str, String) {
+    match index {
+        Some(index) => ("Recording", state.recordings[index].loopback_name.clone()),
+        None => ("Local", state.local_loopback_name.clone()),
+    }
+}
@@ -0,0 +1,176 @@
+//! Rendering of the Active screen: routing with loopback health, and volume.
+
+use crate::cli;
+use crate::splitter::{self, SplitState};
+use crate::tui::app::App;
+use crate::tui::children::LoopbackStatus;
+use ratatui::{
+    Frame,
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Paragraph},
+};
+
+pub(super) fn draw_active(frame: &mut Frame, area: Rect, app: &App) {
+    let state = match &app.active_split {
+        Some(s) => s,
+        None => {
+            let paragraph = Paragraph::new("No active split").block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(" Active Split "),
+            );
+            frame.render_widget(paragraph, area);
+            return;
+        }
+    };
+
+    let mut lines = vec![
+        Line::from(""),
+        Line::from(vec![Span::styled(
+            "  SPLIT ACTIVE",
+            Style::default()
+                .fg(Color::Green)
+                .add_modifier(Modifier::BOLD),
+        )]),
+        Line::from(""),
+        Line::from(format!("  Source: {}", state.source_application_name)),
+        Line::from(format!("  Recording to: {}", state.recording_dest_names())),
+        Line::from(format!(
+            "  Local output: {}",
+            state.local_output_names().collect::<Vec<_>>().join(", ")
+        )),
+        Line::from(format!("  Running for: {}", cli::format_age(state.age()))),
+        Line::from(""),
+        Line::from("  Routing:"),
+        Line::from(format!("    [{}]", state.source_application_name)),
+    ];
+    lines.extend(routing_lines(app, state));
+    lines.extend([
+        Line::from(""),
+        Line::from(vec![
+            Span::raw("  Local volume: "),
+            match state.muted_volume {
+                Some(volume) => Span::styled(
+                    format!("muted (m restores {:.0}%)", volume * 100.0),
+                    Style::default().fg(Color::Red),
+                ),
+                None => Span::styled(
+                    volume_bar(app.local_volume()),
+                    Style::default().fg(Color::Yellow),
+                ),
+            },
+        ]),
+        Line::from(vec![
+            Span::raw("  Recording volume: "),
+            Span::styled(
+                volume_bar(state.recording_volume),
+                Style::default().fg(Color::Yellow),
+            ),
+        ]),
+        Line::from(format!(
+            "  Channel levels: {}",
+            channel_levels(&state.local_channel_volumes)
+        )),
+        Line::from(format!(
+            "  Also adjustable in pwvucontrol, look for: \"{}\"",
+            state.local_loopback_name
+        )),
+    ]);
+
+    let paragraph = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(" Active Split "),
+    );
+
+    frame.render_widget(paragraph, area);
+}
+
+/// One branch per loopback, green while running and red while stopped,
+/// killed or restarting; grey until the first health check
+fn routing_lines(app: &App, state: &SplitState) -> Vec<Line<'static>> {
+    let (recording_running, local_running) = match &app.loopbacks_running {
+        Some((recording, local)) => (Some(recording.as_slice()), Some(*local)),
+        None => (None, None),
+    };
+    let restarts = |name: &str| app.restart_trackers.get(name).map_or(0, |t| t.restarts());
+    let recording_level = match state.recording_volume {
+        1.0 => "FULL VOLUME".to_string(),
+        volume => format!("{:.0}%", volume * 100.0),
+    };
+
+    let branches = state
+        .recordings
+        .iter()
+        .enumerate()
+        .map(|(i, r)| {
+            (
+                format!(
+                    "[To Recording] ---> [{}] ({})",
+                    r.dest_application_name, recording_level
+                ),
+                recording_running
+                    .map(|running| running.get(i).copied().unwrap_or(LoopbackStatus::Crashed)),
+                restarts(&r.loopback_name),
+            )
+        })
+        .chain(state.local_output_names().map(|sink_name| {
+            (
+                format!("[To Local] ---> [{}] (ADJUSTABLE)", sink_name),
+                local_running,
+                restarts(&state.local_loopback_name),
+            )
+        }))
+        .collect::<Vec<_>>();
+
+    let last = branches.len() - 1;
+    let mut lines = Vec::new();
+    for (i, (label, running, restarts)) in branches.into_iter().enumerate() {
+        let (color, health) = match running {
+            Some(LoopbackStatus::Running) => (Color::Green, "running"),
+            Some(LoopbackStatus::Crashed) => (Color::Red, "stopped"),
+            Some(LoopbackStatus::Killed(_)) => (Color::Red, "killed"),
+            None => (Color::DarkGray, "checking"),
+        };
+        let restarted = match restarts {
+            0 => String::new(),
+            n => format!(", restarted {}x", n),
+        };
+        lines.push(Line::from("        |"));
+        lines.push(Line::from(vec![
+            Span::raw(if i == last { "        '" } else { "        +" }),
+            Span::styled(format!("---> {}", label), Style::default().fg(color)),
+            Span::styled(
+                format!("  {}{}", health, restarted),
+                Style::default().fg(color),
+            ),
+        ]));
+    }
+    lines
+}
+
+/// Render the local loopback's per-channel gains, in channel order
+fn channel_levels(gains: &[f32]) -> String {
+    if gains.is_empty() {
+        return "all 100% (centered)".to_string();
+    }
+    gains
+        .iter()
+        .map(|g| format!("{:.0}%", g * 100.0))
+        .collect::<Vec<_>>()
+        .join(" / ")
+}
+
+/// Render a volume as a textual bar, one cell per 10%
+fn volume_bar(volume: f32) -> String {
+    let total = (splitter::MAX_VOLUME * 10.0).round() as usize;
+    let filled = ((volume * 10.0).round() as usize).min(total);
+    format!(
+        "[{}{}] {:.0}%",
+        "#".repeat(filled),
+        "-".repeat(total - filled),
+        volume * 100.0
+    )
+}
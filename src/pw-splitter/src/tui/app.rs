@@ -1,6 +1,16 @@
-use crate::error::Result;
-use crate::pipewire::{self, AudioSource, RecordingDest, SourceConnection};
-use crate::splitter::{self, SplitConfig, SplitState};
+use crate::config::Config;
+use crate::error::{PwSplitterError, Result};
+use crate::pipewire::{self, AudioSink, AudioSource, RecordingDest, SourceConnection};
+use crate::splitter::{
+    self, LinkDiff, RestartTracker, SetupProgress, SetupStep, SplitConfig, SplitSetup, SplitState,
+};
+use crate::tui::children::{LoopbackChildren, LoopbackStatus};
+use crate::tui::poll::{
+    DEFAULT_POLL_INTERVAL, HEALTH_CHECK_INTERVAL, MIN_POLL_INTERVAL, configured_poll_interval,
+};
+use crate::tui::selection::{ListFilter, selectable_nodes, source_exists};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
 
 /// Application state
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -8,7 +18,14 @@ pub enum AppState {
     SelectSource,
     SelectDestination,
     Confirm,
+    /// Choosing the local output from the confirm screen
+    SelectSink,
+    Creating(SetupStep),
     Active,
+    /// Waiting for the user to confirm stopping the active split
+    ConfirmStop,
+    /// Ctrl-C with a split active: keep it running or stop it before quitting
+    ConfirmQuit,
     Error(String),
     Done,
 }
@@ -21,39 +38,114 @@ pub struct App {
     pub selected_source_idx: usize,
     pub selected_dest_idx: usize,
     pub selected_source: Option<AudioSource>,
-    pub selected_dest: Option<RecordingDest>,
+    /// Destinations marked with space; empty means "just the highlighted one"
+    pub marked_dests: Vec<usize>,
+    pub selected_dests: Vec<RecordingDest>,
+    /// Sinks offered for the local output, loaded when the sink list opens
+    pub sinks: Vec<AudioSink>,
+    pub selected_sink_idx: usize,
+    /// Sinks marked with space; empty means "just the highlighted one"
+    pub marked_sinks: Vec<usize>,
+    /// Local outputs chosen by the user; empty keeps the source's current output
+    pub local_sinks: Vec<AudioSink>,
     pub source_connections: Vec<SourceConnection>,
+    /// Current connections of the highlighted source, for the detail pane
+    pub highlighted_connections: Vec<SourceConnection>,
+    /// Links the split would cut and make, shown on the confirm screen
+    pub link_diff: LinkDiff,
     pub active_split: Option<SplitState>,
+    /// The split stopped last, kept so the stop can be undone
+    pub last_stopped: Option<SplitState>,
     pub status_message: String,
     pub should_quit: bool,
+    /// Timeout for polling input events (never below [`MIN_POLL_INTERVAL`])
+    pub poll_interval: Duration,
+    /// When loopback health was last checked
+    pub last_health_check: Instant,
+    /// Restart attempts per crashed loopback, by loopback name
+    pub restart_trackers: HashMap<String, RestartTracker>,
+    /// Crashed loopbacks awaiting a manual restart (`auto_restart = false`):
+    /// recording loopback indices, `None` for the local one
+    pub pending_restarts: Vec<Option<usize>>,
+    /// Status of the active split's loopbacks at the last health check: one
+    /// per recording, then the local one
+    pub loopbacks_running: Option<(Vec<LoopbackStatus>, LoopbackStatus)>,
+    /// Handles of the active split's loopback processes
+    pub loopback_children: LoopbackChildren,
+    /// Split setup in progress, advanced one step per tick
+    pub pending_setup: Option<SplitSetup>,
+    /// Advanced on every tick; drives the spinner while creating a split
+    pub spinner_frame: usize,
+    pub config: Config,
+    /// Skip destination selection and confirmation when a default destination matches
+    pub auto_confirm: bool,
+    /// `/` filter over the source or destination list
+    pub filter: ListFilter,
 }
 
 impl App {
-    pub fn new() -> Result<Self> {
-        let objects = pipewire::get_pw_objects()?;
-        let sources = pipewire::extract_audio_sources(&objects);
-        let destinations = pipewire::extract_recording_dests(&objects);
+    pub fn new(config: Config, auto_confirm: bool) -> Result<Self> {
+        let objects = match pipewire::get_pw_objects() {
+            Ok(objects) => objects,
+            // Shown on the error screen, where `r` retries, instead of exiting
+            Err(e @ PwSplitterError::PipeWireNotRunning) => {
+                return Ok(Self {
+                    state: AppState::Error(e.to_string()),
+                    poll_interval: configured_poll_interval(&config),
+                    config,
+                    auto_confirm,
+                    ..Self::default()
+                });
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let (sources, destinations) = selectable_nodes(&objects, &config);
+        let selected_dest_idx = config
+            .default_dest_index(&destinations)
+            .ok()
+            .flatten()
+            .unwrap_or(0);
 
-        Ok(Self {
+        let mut app = Self {
             state: AppState::SelectSource,
             sources,
             destinations,
             selected_source_idx: 0,
-            selected_dest_idx: 0,
+            selected_dest_idx,
             selected_source: None,
-            selected_dest: None,
+            marked_dests: Vec::new(),
+            selected_dests: Vec::new(),
+            sinks: Vec::new(),
+            selected_sink_idx: 0,
+            marked_sinks: Vec::new(),
+            local_sinks: Vec::new(),
             source_connections: Vec::new(),
+            link_diff: LinkDiff::default(),
+            highlighted_connections: Vec::new(),
             active_split: None,
+            last_stopped: None,
             status_message: String::new(),
             should_quit: false,
-        })
+            poll_interval: configured_poll_interval(&config),
+            last_health_check: Instant::now(),
+            restart_trackers: HashMap::new(),
+            pending_restarts: Vec::new(),
+            loopbacks_running: None,
+            loopback_children: LoopbackChildren::default(),
+            pending_setup: None,
+            spinner_frame: 0,
+            config,
+            auto_confirm,
+            filter: ListFilter::default(),
+        };
+        app.update_source_details();
+        Ok(app)
     }
 
     /// Refresh the list of sources and destinations
     pub fn refresh(&mut self) -> Result<()> {
-        let objects = pipewire::get_pw_objects()?;
-        self.sources = pipewire::extract_audio_sources(&objects);
-        self.destinations = pipewire::extract_recording_dests(&objects);
+        let objects = pipewire::get_fresh_pw_objects()?;
+        (self.sources, self.destinations) = selectable_nodes(&objects, &self.config);
 
         // Reset indices if out of bounds
         if self.selected_source_idx >= self.sources.len() {
@@ -62,59 +154,34 @@ impl App {
         if self.selected_dest_idx >= self.destinations.len() {
             self.selected_dest_idx = self.destinations.len().saturating_sub(1);
         }
+        // Indices may now point at different destinations
+        self.marked_dests.clear();
+        self.snap_selection_to_filter();
 
         Ok(())
     }
 
-    /// Move selection up
-    pub fn select_prev(&mut self) {
-        match self.state {
-            AppState::SelectSource => {
-                if self.selected_source_idx > 0 {
-                    self.selected_source_idx -= 1;
-                }
-            }
-            AppState::SelectDestination => {
-                if self.selected_dest_idx > 0 {
-                    self.selected_dest_idx -= 1;
-                }
-            }
-            _ => {}
-        }
-    }
-
-    /// Move selection down
-    pub fn select_next(&mut self) {
-        match self.state {
-            AppState::SelectSource => {
-                if !self.sources.is_empty() && self.selected_source_idx < self.sources.len() - 1 {
-                    self.selected_source_idx += 1;
-                }
-            }
-            AppState::SelectDestination => {
-                if !self.destinations.is_empty()
-                    && self.selected_dest_idx < self.destinations.len() - 1
-                {
-                    self.selected_dest_idx += 1;
-                }
-            }
-            _ => {}
-        }
-    }
-
     /// Confirm current selection and move to next state
     pub fn confirm_selection(&mut self) {
         match self.state {
             AppState::SelectSource => {
-                if self.sources.is_empty() {
-                    self.status_message = "No audio sources available".to_string();
-                    return;
-                }
-
-                let source = self.sources[self.selected_source_idx].clone();
+                // The list may have shrunk since it was drawn
+                let source = match self.sources.get(self.selected_source_idx) {
+                    Some(source) if self.visible_indices().contains(&self.selected_source_idx) => {
+                        source.clone()
+                    }
+                    _ => {
+                        self.status_message = "No audio sources available".to_string();
+                        return;
+                    }
+                };
 
                 // Find current connections for this source
                 if let Ok(objects) = pipewire::get_pw_objects() {
+                    if !source_exists(&objects, &source) {
+                        self.source_vanished();
+                        return;
+                    }
                     self.source_connections =
                         pipewire::find_source_connections(source.node_id, &objects);
                 }
@@ -125,62 +192,113 @@ impl App {
 
                 self.selected_source = Some(source);
                 self.state = AppState::SelectDestination;
+                self.filter = ListFilter::default();
                 self.status_message.clear();
+
+                if self.auto_confirm {
+                    self.split_to_default_dest();
+                }
             }
             AppState::SelectDestination => {
-                if self.destinations.is_empty() {
+                if !self.visible_indices().contains(&self.selected_dest_idx) {
                     self.status_message = "No recording destinations available".to_string();
                     return;
                 }
 
-                self.selected_dest = Some(self.destinations[self.selected_dest_idx].clone());
-                self.state = AppState::Confirm;
+                self.selected_dests = if self.marked_dests.is_empty() {
+                    vec![self.destinations[self.selected_dest_idx].clone()]
+                } else {
+                    self.marked_dests
+                        .iter()
+                        .map(|&i| self.destinations[i].clone())
+                        .collect()
+                };
+                self.enter_confirm();
                 self.status_message.clear();
             }
             AppState::Confirm => {
                 self.execute_split();
             }
-            AppState::Active => {
-                // Stop the split
-                if let Some(state) = &self.active_split {
-                    match splitter::teardown_split(state) {
-                        Ok(()) => {
-                            self.status_message = "Split stopped successfully".to_string();
-                            self.active_split = None;
-                            self.state = AppState::Done;
-                        }
-                        Err(e) => {
-                            self.status_message = format!("Failed to stop split: {}", e);
-                        }
-                    }
-                }
-            }
+            AppState::SelectSink => self.choose_sink(),
+            AppState::Active => self.request_stop(),
+            AppState::ConfirmStop => self.stop_active_split(),
             _ => {}
         }
     }
 
+    /// Split the highlighted source straight to the default destination (`g`)
+    pub fn quick_split(&mut self) {
+        if self.state != AppState::SelectSource {
+            return;
+        }
+        if matches!(self.config.default_dest_index(&self.destinations), Ok(None)) {
+            self.status_message = "Error: no default destination configured or available \
+                 (set default_recording_dest in config.toml)"
+                .to_string();
+            return;
+        }
+
+        self.confirm_selection();
+        // With --yes, confirming the source already started the split
+        if self.state == AppState::SelectDestination {
+            self.split_to_default_dest();
+        }
+    }
+
+    /// Skip destination selection and confirmation, using the configured default
+    fn split_to_default_dest(&mut self) {
+        match self.config.default_dest_index(&self.destinations) {
+            Ok(Some(idx)) => {
+                self.selected_dests = vec![self.destinations[idx].clone()];
+                self.execute_split();
+            }
+            Ok(None) => {
+                self.status_message =
+                    "Warning: no default destination configured or available".to_string();
+            }
+            Err(e) => {
+                self.status_message = format!("Warning: default destination: {}", e);
+            }
+        }
+    }
+
     /// Go back to previous state
     pub fn go_back(&mut self) {
         match self.state {
             AppState::SelectDestination => {
                 self.selected_source = None;
                 self.source_connections.clear();
+                self.marked_dests.clear();
+                self.filter = ListFilter::default();
                 self.state = AppState::SelectSource;
             }
             AppState::Confirm => {
-                self.selected_dest = None;
+                self.selected_dests.clear();
+                self.local_sinks.clear();
                 self.state = AppState::SelectDestination;
             }
-            AppState::Active => {
-                // Don't go back from active state - must stop first
+            AppState::SelectSink => {
+                self.filter = ListFilter::default();
+                self.enter_confirm();
+            }
+            AppState::ConfirmStop | AppState::ConfirmQuit => {
+                self.state = AppState::Active;
+            }
+            AppState::Creating(_) | AppState::Active => {
+                // Don't go back while creating or active - must stop first
             }
             _ => {}
         }
         self.status_message.clear();
     }
 
-    /// Execute the split setup
+    /// Execute the split setup with the configured local volume
     fn execute_split(&mut self) {
+        self.execute_split_with_volume(self.config.default_local_volume.unwrap_or(1.0));
+    }
+
+    /// Execute the split setup
+    pub(super) fn execute_split_with_volume(&mut self, local_volume: f32) {
         let source = match &self.selected_source {
             Some(s) => s.clone(),
             None => {
@@ -189,83 +307,147 @@ impl App {
             }
         };
 
-        let dest = match &self.selected_dest {
-            Some(d) => d.clone(),
-            None => {
-                self.state = AppState::Error("No destination selected".to_string());
+        if self.selected_dests.is_empty() {
+            self.state = AppState::Error("No destination selected".to_string());
+            return;
+        }
+
+        // Stay on the confirm screen so the user can go back and pick another source
+        if let Err(e) = splitter::check_not_already_split(&source, None) {
+            self.state = AppState::Confirm;
+            self.status_message = format!("Warning: {}", e);
+            return;
+        }
+        let dests = self.selected_dests.clone();
+
+        let objects = match pipewire::get_pw_objects() {
+            Ok(objects) => objects,
+            Err(e) => {
+                self.state = AppState::Error(format!("Failed to query PipeWire: {}", e));
                 return;
             }
         };
 
+        // The source may have ended while the user was choosing destinations
+        if !source_exists(&objects, &source) {
+            self.source_vanished();
+            return;
+        }
+
         // If source has no connections, we still proceed but warn
-        let connections = if self.source_connections.is_empty() {
-            // Try to get default output
-            if let Ok(objects) = pipewire::get_pw_objects() {
-                let sinks = pipewire::extract_audio_sinks(&objects);
-                if let Some(default_sink) = sinks.first() {
-                    vec![SourceConnection {
-                        source_node_id: source.node_id,
-                        target_node_id: default_sink.node_id,
-                        target_node_name: default_sink.node_name.clone(),
-                        links: Vec::new(),
-                    }]
-                } else {
-                    self.state = AppState::Error("No output sinks available".to_string());
-                    return;
-                }
-            } else {
-                self.state = AppState::Error("Failed to query PipeWire".to_string());
-                return;
-            }
-        } else {
-            self.source_connections.clone()
+        let Some(connections) = self.planned_connections(&source, &objects) else {
+            self.state = AppState::Error("No output sinks available".to_string());
+            return;
         };
 
         let config = SplitConfig {
             source,
-            recording_dest: dest,
+            recording_dests: dests,
             original_connections: connections,
+            local_volume,
+            recording_volume: self.config.default_recording_volume.unwrap_or(1.0),
+            local_sinks: self.local_sinks.clone(),
+            recording_channel_map: self
+                .config
+                .recording_channel_map
+                .clone()
+                .unwrap_or_default(),
+            local_channel_map: self.config.local_channel_map.clone().unwrap_or_default(),
+            tuning: self.config.loopback_tuning(),
+            local_description: self.config.local_description.clone(),
+            shares_source_with: None,
+            loopback_prefix: self.link_diff.loopback_prefix.clone(),
+        };
+
+        let setup =
+            splitter::setup_split_stepped(config).with_port_timeout(self.config.port_timeout());
+        self.state = AppState::Creating(setup.current_step());
+        self.status_message = "Creating split...".to_string();
+        self.pending_setup = Some(setup);
+    }
+
+    /// Return to a refreshed source list after the chosen source ended
+    fn source_vanished(&mut self) {
+        self.selected_source = None;
+        self.source_connections.clear();
+        self.selected_dests.clear();
+        self.marked_dests.clear();
+        self.local_sinks.clear();
+        self.filter = ListFilter::default();
+        self.state = AppState::SelectSource;
+        // A failed refresh keeps the old list; the next confirm re-checks anyway
+        let _ = self.refresh();
+        self.status_message = "Warning: source no longer available".to_string();
+    }
+
+    /// Advance a pending split setup by one step
+    fn advance_setup(&mut self) {
+        let Some(setup) = &mut self.pending_setup else {
+            return;
         };
 
-        match splitter::setup_split(config) {
-            Ok(result) => {
+        match setup.advance() {
+            Ok(SetupProgress::InProgress(step)) => {
+                self.state = AppState::Creating(step);
+            }
+            // The TUI doesn't offer a dry run
+            Ok(SetupProgress::DryRun(_)) => {
+                self.pending_setup = None;
+                self.state = AppState::Error("Dry run: nothing was changed".to_string());
+            }
+            Ok(SetupProgress::Complete(result)) => {
+                self.pending_setup = None;
+                let result = *result;
                 self.active_split = Some(result.state);
+                self.restart_trackers.clear();
+                self.pending_restarts.clear();
+                self.loopbacks_running = None;
+                self.loopback_children =
+                    LoopbackChildren::new(result.loopbacks_to_recording, result.loopback_to_local);
                 self.state = AppState::Active;
-                self.status_message = "Split active! Adjust volume in pwvucontrol".to_string();
-
-                // Forget the child processes so they keep running
-                std::mem::forget(result.loopback_to_recording);
-                std::mem::forget(result.loopback_to_local);
+                self.status_message = if result.warnings.is_empty() {
+                    "Split active! Adjust volume in pwvucontrol".to_string()
+                } else {
+                    format!("Split active. Warning: {}", result.warnings.join("; "))
+                };
             }
             Err(e) => {
+                self.pending_setup = None;
                 self.state = AppState::Error(format!("Failed to create split: {}", e));
             }
         }
     }
 
-    /// Check if loopback processes are still running and restart if needed
-    pub fn check_and_restart_loopbacks(&mut self) {
-        if let Some(state) = &mut self.active_split {
-            let (recording_running, local_running) = splitter::check_loopbacks_running(state);
+    /// Poll timeout for the event loop; shortened while a setup is in progress
+    pub fn poll_timeout(&self) -> Duration {
+        if self.pending_setup.is_some() {
+            MIN_POLL_INTERVAL
+        } else {
+            self.poll_interval
+        }
+    }
 
-            if !recording_running {
-                self.status_message = "Recording loopback crashed, restarting...".to_string();
-                if let Err(e) = splitter::restart_loopback_to_recording(state) {
-                    self.status_message = format!("Failed to restart recording loopback: {}", e);
-                } else {
-                    self.status_message = "Recording loopback restarted".to_string();
-                }
-            }
+    /// Run periodic work: advance a pending setup, then check loopback health
+    ///
+    /// Loopback health is checked at most once per [`HEALTH_CHECK_INTERVAL`].
+    pub fn tick(&mut self) {
+        self.spinner_frame = self.spinner_frame.wrapping_add(1);
 
-            if !local_running {
-                self.status_message = "Local loopback crashed, restarting...".to_string();
-                if let Err(e) = splitter::restart_loopback_to_local(state) {
-                    self.status_message = format!("Failed to restart local loopback: {}", e);
-                } else {
-                    self.status_message = "Local loopback restarted".to_string();
-                }
-            }
+        if self.pending_setup.is_some() {
+            self.advance_setup();
+            return;
         }
+
+        if !matches!(
+            self.state,
+            AppState::Active | AppState::ConfirmStop | AppState::ConfirmQuit
+        ) || self.last_health_check.elapsed() < HEALTH_CHECK_INTERVAL
+        {
+            return;
+        }
+
+        self.last_health_check = Instant::now();
+        self.check_and_restart_loopbacks();
     }
 }
 
@@ -278,11 +460,30 @@ impl Default for App {
             selected_source_idx: 0,
             selected_dest_idx: 0,
             selected_source: None,
-            selected_dest: None,
+            marked_dests: Vec::new(),
+            selected_dests: Vec::new(),
+            sinks: Vec::new(),
+            selected_sink_idx: 0,
+            marked_sinks: Vec::new(),
+            local_sinks: Vec::new(),
             source_connections: Vec::new(),
+            link_diff: LinkDiff::default(),
+            highlighted_connections: Vec::new(),
             active_split: None,
+            last_stopped: None,
             status_message: String::new(),
             should_quit: false,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            last_health_check: Instant::now(),
+            restart_trackers: HashMap::new(),
+            pending_restarts: Vec::new(),
+            loopbacks_running: None,
+            loopback_children: LoopbackChildren::default(),
+            pending_setup: None,
+            spinner_frame: 0,
+            config: Config::default(),
+            auto_confirm: false,
+            filter: ListFilter::default(),
         }
     }
 }
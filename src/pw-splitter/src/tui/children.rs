@@ -0,0 +1,125 @@
+//! The active split's loopback processes, kept as `Child` handles.
+//!
+//! `/proc/<pid>` can't tell a dead loopback from an unrelated process that
+//! reused its PID, and says nothing about how it ended. Waiting on our own
+//! child gives the exact exit status (and reaps it), so a loopback someone
+//! killed on purpose isn't mistaken for a crash.
+
+use crate::splitter::{self, SplitState};
+use signal_hook::consts::{SIGINT, SIGKILL, SIGTERM};
+use std::os::unix::process::ExitStatusExt;
+use std::process::{Child, ExitStatus};
+use std::thread;
+
+/// Signals a loopback is stopped with on purpose (`kill`, `pw-splitter stop`)
+const STOP_SIGNALS: [i32; 3] = [SIGTERM, SIGINT, SIGKILL];
+
+/// How a loopback is doing at a health check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoopbackStatus {
+    Running,
+    /// Exited by itself, or died of a signal other than [`STOP_SIGNALS`]
+    Crashed,
+    /// Killed with one of [`STOP_SIGNALS`]; restarting it would undo that
+    Killed(i32),
+}
+
+impl LoopbackStatus {
+    fn from_exit(status: ExitStatus) -> Self {
+        match status.signal() {
+            Some(signal) if STOP_SIGNALS.contains(&signal) => LoopbackStatus::Killed(signal),
+            _ => LoopbackStatus::Crashed,
+        }
+    }
+
+    fn from_running(running: bool) -> Self {
+        if running {
+            LoopbackStatus::Running
+        } else {
+            LoopbackStatus::Crashed
+        }
+    }
+
+    pub fn is_running(self) -> bool {
+        self == LoopbackStatus::Running
+    }
+}
+
+/// Handles of the active split's loopbacks
+///
+/// Dropping them leaves the processes running, so the split outlives the TUI.
+#[derive(Debug, Default)]
+pub struct LoopbackChildren {
+    /// One per entry of `SplitState::recordings`
+    recordings: Vec<Option<Child>>,
+    local: Option<Child>,
+}
+
+impl LoopbackChildren {
+    pub fn new(recordings: Vec<Child>, local: Child) -> Self {
+        Self {
+            recordings: recordings.into_iter().map(Some).collect(),
+            local: Some(local),
+        }
+    }
+
+    /// Take over a restarted loopback: the recording one at `index`, or the local one
+    pub fn replace(&mut self, index: Option<usize>, child: Child) {
+        let slot = match index {
+            Some(index) => {
+                if self.recordings.len() <= index {
+                    self.recordings.resize_with(index + 1, || None);
+                }
+                &mut self.recordings[index]
+            }
+            None => &mut self.local,
+        };
+        *slot = Some(child);
+    }
+
+    /// Status of each loopback of `state`: one per recording, then the local one
+    ///
+    /// Loopbacks without a handle (e.g. a failed restart), or whose handle
+    /// is for another PID than the state's, fall back to checking `/proc`.
+    pub fn statuses(&mut self, state: &SplitState) -> (Vec<LoopbackStatus>, LoopbackStatus) {
+        let (recording_running, local_running) = splitter::check_loopbacks_running(state);
+        let recordings = state
+            .recordings
+            .iter()
+            .zip(recording_running)
+            .enumerate()
+            .map(|(i, (recording, running))| {
+                let child = self.recordings.get_mut(i).and_then(Option::as_mut);
+                status_of(child, recording.pid, running)
+            })
+            .collect();
+        let local = status_of(
+            self.local.as_mut(),
+            state.loopback_to_local_pid,
+            local_running,
+        );
+        (recordings, local)
+    }
+
+    /// Reap the loopbacks once they exit, after the split was torn down
+    pub fn reap(&mut self) {
+        let children = self.recordings.drain(..).chain([self.local.take()]);
+        for mut child in children.flatten() {
+            // Teardown already signalled them; don't block the UI on it
+            thread::spawn(move || child.wait());
+        }
+    }
+}
+
+/// Status of one loopback, from its handle if it's the one running as `pid`
+fn status_of(child: Option<&mut Child>, pid: u32, running: bool) -> LoopbackStatus {
+    match child.filter(|child| child.id() == pid).map(Child::try_wait) {
+        Some(Ok(None)) => LoopbackStatus::Running,
+        Some(Ok(Some(status))) => LoopbackStatus::from_exit(status),
+        Some(Err(e)) => {
+            tracing::debug!("try_wait pid {}: {}", pid, e);
+            LoopbackStatus::from_running(running)
+        }
+        None => LoopbackStatus::from_running(running),
+    }
+}
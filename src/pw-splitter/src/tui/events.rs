@@ -1,62 +1,143 @@
+use crate::splitter;
 use crate::tui::app::{App, AppState};
-use crossterm::event::{self, Event, KeyCode, KeyEventKind};
-use std::time::Duration;
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
 
 /// Handle input events
 /// Returns true if the app should continue running
 pub fn handle_events(app: &mut App) -> std::io::Result<bool> {
     // Poll for events with a timeout (allows for periodic checks like loopback monitoring)
-    if event::poll(Duration::from_millis(250))? {
-        if let Event::Key(key) = event::read()? {
-            // Only handle key press events (not release)
-            if key.kind != KeyEventKind::Press {
-                return Ok(!app.should_quit);
-            }
+    if event::poll(app.poll_timeout())?
+        && let Event::Key(key) = event::read()?
+    {
+        // Only handle key press events (not release)
+        if key.kind != KeyEventKind::Press {
+            return Ok(!app.should_quit);
+        }
 
+        // While typing a filter, keys go into the query instead of acting
+        if app.filter.editing {
             match key.code {
-                KeyCode::Char('q') => {
-                    app.should_quit = true;
-                }
-                KeyCode::Up | KeyCode::Char('k') => {
-                    app.select_prev();
-                }
-                KeyCode::Down | KeyCode::Char('j') => {
-                    app.select_next();
-                }
-                KeyCode::Enter => {
-                    app.confirm_selection();
-                }
-                KeyCode::Esc => {
-                    app.go_back();
-                }
-                KeyCode::Char('r') => {
-                    // Refresh or restart
-                    match &app.state {
-                        AppState::SelectSource | AppState::SelectDestination => {
-                            if let Err(e) = app.refresh() {
-                                app.status_message = format!("Refresh failed: {}", e);
-                            } else {
-                                app.status_message = "Refreshed".to_string();
-                            }
+                KeyCode::Char(c) => app.push_search_char(c),
+                KeyCode::Backspace => app.pop_search_char(),
+                KeyCode::Enter => app.finish_search(),
+                KeyCode::Esc => app.clear_search(),
+                _ => {}
+            }
+            app.tick();
+            return Ok(!app.should_quit);
+        }
+
+        match key.code {
+            // Raw mode turns Ctrl-C into a key press rather than SIGINT
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                app.interrupt();
+            }
+            KeyCode::Enter | KeyCode::Char('k') if app.state == AppState::ConfirmQuit => {
+                app.should_quit = true;
+            }
+            KeyCode::Char('s') if app.state == AppState::ConfirmQuit => {
+                app.stop_and_quit();
+            }
+            // Quitting mid-setup would leave a half-wired split behind
+            KeyCode::Char('q') if !matches!(app.state, AppState::Creating(_)) => {
+                app.should_quit = true;
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                app.select_prev();
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                app.select_next();
+            }
+            KeyCode::Char(' ') => {
+                app.toggle_mark();
+            }
+            KeyCode::Enter => {
+                app.confirm_selection();
+            }
+            KeyCode::Char('/') => {
+                app.start_search();
+            }
+            KeyCode::Char('o') => {
+                app.open_sink_selection();
+            }
+            KeyCode::Char('g') => {
+                app.quick_split();
+            }
+            KeyCode::Char('m') if app.state == AppState::Active => {
+                app.toggle_mute();
+            }
+            KeyCode::Char('m') => {
+                app.toggle_sink_monitors();
+            }
+            // An applied filter is cleared before Esc navigates back
+            KeyCode::Esc if app.filter.is_active() => {
+                app.clear_search();
+            }
+            KeyCode::Esc => {
+                app.go_back();
+            }
+            KeyCode::Char('+') | KeyCode::Char('=') | KeyCode::Right
+                if app.state == AppState::Active =>
+            {
+                app.adjust_volume(splitter::VOLUME_STEP);
+            }
+            KeyCode::Char('-') | KeyCode::Left if app.state == AppState::Active => {
+                app.adjust_volume(-splitter::VOLUME_STEP);
+            }
+            KeyCode::Char('>') | KeyCode::Char('.') if app.state == AppState::Active => {
+                app.adjust_recording_volume(splitter::VOLUME_STEP);
+            }
+            KeyCode::Char('<') | KeyCode::Char(',') if app.state == AppState::Active => {
+                app.adjust_recording_volume(-splitter::VOLUME_STEP);
+            }
+            KeyCode::Char('y') if app.state == AppState::ConfirmStop => {
+                app.stop_active_split();
+            }
+            KeyCode::Char('n') if app.state == AppState::ConfirmStop => {
+                app.go_back();
+            }
+            KeyCode::Char('u') if app.state == AppState::Done => {
+                app.undo_stop();
+            }
+            KeyCode::Char('v') if app.state == AppState::Active => {
+                app.open_mixer();
+            }
+            KeyCode::Char('[') if app.state == AppState::Active => {
+                app.shift_balance(-splitter::BALANCE_STEP);
+            }
+            KeyCode::Char(']') if app.state == AppState::Active => {
+                app.shift_balance(splitter::BALANCE_STEP);
+            }
+            KeyCode::Char('r') => {
+                // Refresh or restart
+                match &app.state {
+                    AppState::Active => app.restart_crashed_loopbacks(),
+                    AppState::SelectSource | AppState::SelectDestination => {
+                        if let Err(e) = app.refresh() {
+                            app.status_message = format!("Refresh failed: {}", e);
+                        } else {
+                            app.status_message = "Refreshed".to_string();
                         }
-                        AppState::Done | AppState::Error(_) => {
-                            // Reset to start a new split
-                            if let Ok(new_app) = App::new() {
-                                *app = new_app;
-                            }
+                    }
+                    AppState::Done | AppState::Error(_) => {
+                        // Reset to start a new split
+                        if let Ok(new_app) = App::new(app.config.clone(), app.auto_confirm) {
+                            // Keeps a --refresh-interval override
+                            *app = App {
+                                poll_interval: app.poll_interval,
+                                ..new_app
+                            };
                         }
-                        _ => {}
                     }
+                    _ => {}
                 }
-                _ => {}
             }
-        }
-    } else {
-        // No event - do periodic checks
-        if app.state == AppState::Active {
-            app.check_and_restart_loopbacks();
+            _ => {}
         }
     }
 
+    // Periodic checks are rate-limited by the app itself, so key spam can't starve them
+    app.tick();
+
     Ok(!app.should_quit)
 }
@@ -1,46 +1,105 @@
+pub mod active;
+pub mod active_view;
 pub mod app;
+pub mod children;
 pub mod events;
+pub mod poll;
+pub mod selection;
+pub mod truncate;
 pub mod ui;
 
+use crate::config::Config;
 use crate::error::Result;
 use app::App;
 use crossterm::{
+    cursor::Show,
     execute,
     terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
 };
 use ratatui::{Terminal, backend::CrosstermBackend};
+use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM};
 use std::io::{self, stdout};
+use std::panic;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// Signals that end the TUI; active splits are left running, as on `q`
+const EXIT_SIGNALS: [i32; 3] = [SIGTERM, SIGINT, SIGHUP];
 
 /// Run the TUI application
-pub fn run() -> Result<()> {
+///
+/// With `auto_confirm`, a split to the configured default destination is
+/// created as soon as a source is picked. `refresh_interval` overrides the
+/// configured poll interval.
+///
+/// The terminal is restored on every exit path: errors, panics and the
+/// [`EXIT_SIGNALS`].
+pub fn run(config: Config, auto_confirm: bool, refresh_interval: Option<Duration>) -> Result<()> {
+    let terminate = Arc::new(AtomicBool::new(false));
+    for signal in EXIT_SIGNALS {
+        signal_hook::flag::register(signal, Arc::clone(&terminate))?;
+    }
+    install_panic_hook();
+
     // Setup terminal
     enable_raw_mode()?;
-    let mut stdout = stdout();
-    execute!(stdout, EnterAlternateScreen)?;
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
+    let result = execute!(stdout(), EnterAlternateScreen)
+        .and_then(|_| Terminal::new(CrosstermBackend::new(stdout())))
+        .map_err(Into::into)
+        .and_then(|mut terminal| {
+            let mut app = App::new(config, auto_confirm)?;
+            if let Some(interval) = refresh_interval {
+                app.poll_interval = interval;
+            }
+            run_app(&mut terminal, &mut app, &terminate)
+        });
 
-    // Create app
-    let mut app = App::new()?;
-
-    // Main loop
-    let result = run_app(&mut terminal, &mut app);
+    restore_terminal()?;
+    result
+}
 
-    // Cleanup terminal
-    disable_raw_mode()?;
-    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
-    terminal.show_cursor()?;
+/// Leave raw mode and the alternate screen; harmless if already restored
+fn restore_terminal() -> io::Result<()> {
+    // Attempt both, even if the first fails
+    let raw_mode = disable_raw_mode();
+    let screen = execute!(stdout(), LeaveAlternateScreen, Show);
+    raw_mode.and(screen)
+}
 
-    result
+/// Restore the terminal before the panic message is printed
+fn install_panic_hook() {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let _ = restore_terminal();
+        default_hook(info);
+    }));
 }
 
-fn run_app(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, app: &mut App) -> Result<()> {
+fn run_app(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    app: &mut App,
+    terminate: &AtomicBool,
+) -> Result<()> {
     loop {
         terminal.draw(|frame| ui::draw(frame, app))?;
 
         if !events::handle_events(app)? {
             break;
         }
+
+        // Checked every poll, so a signal is acted on within one poll interval
+        if terminate.load(Ordering::Relaxed) {
+            // Its loopbacks and cut links would otherwise outlive us, with
+            // no state file for stop or prune to find them by
+            if let Some(setup) = &mut app.pending_setup {
+                tracing::warn!(
+                    "exiting on signal while a split was being created, rolling it back"
+                );
+                setup.cancel();
+            }
+            break;
+        }
     }
 
     Ok(())
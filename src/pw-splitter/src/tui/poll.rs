@@ -0,0 +1,57 @@
+//! How often the TUI polls for input, and how that interval is configured.
+//!
+//! The interval comes from `--refresh-interval`, then `PW_SPLITTER_POLL_MS`,
+//! then `refresh_interval_ms` in the config file. Periodic work tracks its own
+//! elapsed time, so a long interval delays it by at most one poll.
+
+use crate::config::Config;
+use crate::error::{CliError, Result};
+use std::time::Duration;
+
+/// Default timeout for polling input events
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Lower bound for the poll interval; anything smaller busy-loops the event loop
+pub const MIN_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Upper bound for the poll interval; beyond this the TUI feels frozen and
+/// crashed loopbacks go unnoticed too long
+pub const MAX_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How often loopback health is checked, independent of the poll interval
+pub const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Environment variable overriding the poll interval (in milliseconds)
+const POLL_INTERVAL_ENV: &str = "PW_SPLITTER_POLL_MS";
+
+/// Clamp a poll interval (in milliseconds) to the supported range
+pub fn clamp_poll_interval(poll_ms: u64) -> Duration {
+    Duration::from_millis(poll_ms).clamp(MIN_POLL_INTERVAL, MAX_POLL_INTERVAL)
+}
+
+/// Check an explicitly chosen poll interval (in milliseconds)
+pub fn validate_refresh_interval(poll_ms: u64) -> Result<Duration> {
+    let interval = Duration::from_millis(poll_ms);
+    if (MIN_POLL_INTERVAL..=MAX_POLL_INTERVAL).contains(&interval) {
+        Ok(interval)
+    } else {
+        Err(CliError::InvalidRefreshInterval(
+            poll_ms,
+            MIN_POLL_INTERVAL.as_millis() as u64,
+            MAX_POLL_INTERVAL.as_millis() as u64,
+        ))
+    }
+}
+
+/// Resolve the poll interval from `PW_SPLITTER_POLL_MS` or the config
+///
+/// The config value was validated when it was loaded; the environment
+/// variable is only clamped, as it always has been.
+pub fn configured_poll_interval(config: &Config) -> Duration {
+    std::env::var(POLL_INTERVAL_ENV)
+        .ok()
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .map(clamp_poll_interval)
+        .or(config.refresh_interval_ms.map(Duration::from_millis))
+        .unwrap_or(DEFAULT_POLL_INTERVAL)
+}
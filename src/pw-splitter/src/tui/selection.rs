@@ -0,0 +1,313 @@
+//! Navigation of the source, destination and sink lists, including the `/` filter.
+
+use crate::config::Config;
+use crate::pipewire::{
+    self, AudioSource, PwObject, RecordingDest, SourceClasses, SourceConnection,
+};
+use crate::splitter::{self, LinkDiff, SplitState};
+use crate::tui::app::{App, AppState};
+use std::collections::HashSet;
+
+/// Sources and destinations the user may pick, excluding our own loopbacks
+///
+/// Splitting a split's loopback would chain splits together, so the nodes of
+/// every active split are hidden from both lists.
+pub(super) fn selectable_nodes(
+    objects: &[PwObject],
+    config: &Config,
+) -> (Vec<AudioSource>, Vec<RecordingDest>) {
+    let splits = SplitState::list_all().unwrap_or_default();
+    let own: HashSet<&str> = splits.iter().flat_map(|s| s.loopback_names()).collect();
+
+    let mut sources = pipewire::extract_audio_sources(objects, config.source_classes());
+    sources.retain(|s| !own.contains(s.node_name.as_str()));
+    let mut destinations = pipewire::extract_recording_dests(objects);
+    destinations.retain(|d| !own.contains(d.node_name.as_str()));
+    (sources, destinations)
+}
+
+/// Whether `source` is still a live node
+pub(super) fn source_exists(objects: &[PwObject], source: &AudioSource) -> bool {
+    find_live_source(objects, source.node_id, &source.node_name).is_some()
+}
+
+/// The source with this id, if it's live (IDs are reused, so the name must match too)
+pub(super) fn find_live_source(
+    objects: &[PwObject],
+    node_id: u32,
+    node_name: &str,
+) -> Option<AudioSource> {
+    let any_class = SourceClasses {
+        include_devices: true,
+        include_sink_monitors: true,
+    };
+    pipewire::extract_audio_sources(objects, any_class)
+        .into_iter()
+        .find(|s| s.node_id == node_id && s.node_name == node_name)
+}
+
+/// Case-insensitive substring filter over the selection lists
+#[derive(Debug, Clone, Default)]
+pub struct ListFilter {
+    /// Lowercased query; empty matches everything
+    pub query: String,
+    /// Whether typed characters currently go into the query
+    pub editing: bool,
+}
+
+impl ListFilter {
+    /// Check whether a list entry's display name passes the filter
+    pub fn matches(&self, display_name: &str) -> bool {
+        self.query.is_empty() || display_name.to_lowercase().contains(&self.query)
+    }
+
+    /// Whether a filter is in effect (being typed or already applied)
+    pub fn is_active(&self) -> bool {
+        self.editing || !self.query.is_empty()
+    }
+}
+
+impl App {
+    /// Indices into the current list of the entries passing the filter
+    pub fn visible_indices(&self) -> Vec<usize> {
+        let names: Vec<String> = match self.state {
+            AppState::SelectSource => self.sources.iter().map(|s| s.display_name()).collect(),
+            AppState::SelectDestination => {
+                self.destinations.iter().map(|d| d.display_name()).collect()
+            }
+            AppState::SelectSink => self.sinks.iter().map(|s| s.description.clone()).collect(),
+            _ => return Vec::new(),
+        };
+
+        names
+            .iter()
+            .enumerate()
+            .filter(|(_, name)| self.filter.matches(name))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// The highlighted index of the current list
+    fn selected_idx_mut(&mut self) -> Option<&mut usize> {
+        match self.state {
+            AppState::SelectSource => Some(&mut self.selected_source_idx),
+            AppState::SelectDestination => Some(&mut self.selected_dest_idx),
+            AppState::SelectSink => Some(&mut self.selected_sink_idx),
+            _ => None,
+        }
+    }
+
+    /// Move selection up, skipping filtered-out entries
+    pub fn select_prev(&mut self) {
+        let visible = self.visible_indices();
+        if let Some(idx) = self.selected_idx_mut()
+            && let Some(pos) = visible.iter().position(|i| i == idx)
+            && pos > 0
+        {
+            *idx = visible[pos - 1];
+        }
+        self.update_source_details();
+    }
+
+    /// Move selection down, skipping filtered-out entries
+    pub fn select_next(&mut self) {
+        let visible = self.visible_indices();
+        if let Some(idx) = self.selected_idx_mut()
+            && let Some(pos) = visible.iter().position(|i| i == idx)
+            && pos + 1 < visible.len()
+        {
+            *idx = visible[pos + 1];
+        }
+        self.update_source_details();
+    }
+
+    /// Mark or unmark the highlighted destination or sink, to pick several
+    pub fn toggle_mark(&mut self) {
+        let visible = self.visible_indices();
+        let (idx, marked) = match self.state {
+            AppState::SelectDestination => (self.selected_dest_idx, &mut self.marked_dests),
+            AppState::SelectSink => (self.selected_sink_idx, &mut self.marked_sinks),
+            _ => return,
+        };
+        if !visible.contains(&idx) {
+            return;
+        }
+
+        match marked.iter().position(|&i| i == idx) {
+            Some(pos) => {
+                marked.remove(pos);
+            }
+            None => marked.push(idx),
+        }
+    }
+
+    /// Show or hide sinks in the source list (`m`), for splitting everything
+    /// playing to one through its monitor
+    pub fn toggle_sink_monitors(&mut self) {
+        if self.state != AppState::SelectSource {
+            return;
+        }
+        let shown = !self.config.include_sink_monitors.unwrap_or(false);
+        self.config.include_sink_monitors = Some(shown);
+        self.status_message = match self.refresh() {
+            Ok(()) if shown => "Showing sink monitors".to_string(),
+            Ok(()) => "Hiding sink monitors".to_string(),
+            Err(e) => format!("Refresh failed: {}", e),
+        };
+    }
+
+    /// Connections the split replaces: the source's current ones, or else a
+    /// stand-in to the first sink that isn't a chosen destination
+    pub(super) fn planned_connections(
+        &self,
+        source: &AudioSource,
+        objects: &[PwObject],
+    ) -> Option<Vec<SourceConnection>> {
+        if !self.source_connections.is_empty() {
+            return Some(self.source_connections.clone());
+        }
+        let dest_ids: Vec<u32> = self.selected_dests.iter().map(|d| d.node_id).collect();
+        splitter::default_output_connection(source, objects, &dest_ids).map(|c| vec![c])
+    }
+
+    /// Show the confirm screen, with the links to cut and make read from the
+    /// live graph, since the source may have moved since it was picked
+    pub(super) fn enter_confirm(&mut self) {
+        self.state = AppState::Confirm;
+        let Some(source) = self.selected_source.clone() else {
+            return;
+        };
+        self.link_diff = match pipewire::get_fresh_pw_objects() {
+            Ok(objects) => {
+                self.source_connections =
+                    pipewire::find_source_connections(source.node_id, &objects);
+                let connections = self
+                    .planned_connections(&source, &objects)
+                    .unwrap_or_default();
+                splitter::plan_link_diff(
+                    &source,
+                    &connections,
+                    &self.selected_dests,
+                    &self.local_sinks,
+                    &objects,
+                )
+            }
+            Err(_) => LinkDiff::default(),
+        };
+    }
+
+    /// Open the local output list from the confirm screen (`o`)
+    ///
+    /// Recording destinations are left out, since routing the local copy into
+    /// one would record the source twice.
+    pub fn open_sink_selection(&mut self) {
+        if self.state != AppState::Confirm {
+            return;
+        }
+
+        let objects = match pipewire::get_pw_objects() {
+            Ok(objects) => objects,
+            Err(e) => {
+                self.status_message = format!("Failed to list sinks: {}", e);
+                return;
+            }
+        };
+        self.sinks = pipewire::extract_audio_sinks(&objects);
+        self.sinks
+            .retain(|s| self.selected_dests.iter().all(|d| d.node_id != s.node_id));
+        if self.sinks.is_empty() {
+            self.status_message = "No other output sinks available".to_string();
+            return;
+        }
+
+        // Start on the current choice, if it's still around
+        self.marked_sinks = self
+            .local_sinks
+            .iter()
+            .filter_map(|chosen| self.sinks.iter().position(|s| s.node_id == chosen.node_id))
+            .collect();
+        self.selected_sink_idx = self.marked_sinks.first().copied().unwrap_or(0);
+        self.state = AppState::SelectSink;
+        self.status_message.clear();
+    }
+
+    /// Use the marked sinks (or else the highlighted one) as the local outputs
+    /// and return to the confirm screen
+    pub fn choose_sink(&mut self) {
+        if !self.visible_indices().contains(&self.selected_sink_idx) {
+            self.status_message = "No output sinks available".to_string();
+            return;
+        }
+
+        self.local_sinks = if self.marked_sinks.is_empty() {
+            vec![self.sinks[self.selected_sink_idx].clone()]
+        } else {
+            self.marked_sinks
+                .iter()
+                .map(|&i| self.sinks[i].clone())
+                .collect()
+        };
+        self.filter = ListFilter::default();
+        self.enter_confirm();
+        self.status_message.clear();
+    }
+
+    /// Start typing a filter query (`/`)
+    pub fn start_search(&mut self) {
+        if matches!(
+            self.state,
+            AppState::SelectSource | AppState::SelectDestination | AppState::SelectSink
+        ) {
+            self.filter.editing = true;
+        }
+    }
+
+    /// Append a typed character to the filter query
+    pub fn push_search_char(&mut self, c: char) {
+        self.filter.query.extend(c.to_lowercase());
+        self.snap_selection_to_filter();
+    }
+
+    /// Remove the last character of the filter query
+    pub fn pop_search_char(&mut self) {
+        self.filter.query.pop();
+        self.snap_selection_to_filter();
+    }
+
+    /// Stop typing, keeping the query applied
+    pub fn finish_search(&mut self) {
+        self.filter.editing = false;
+    }
+
+    /// Drop the filter and return to normal navigation
+    pub fn clear_search(&mut self) {
+        self.filter = ListFilter::default();
+    }
+
+    /// Keep the highlight on a visible entry after the filter changes
+    pub fn snap_selection_to_filter(&mut self) {
+        let visible = self.visible_indices();
+        if let Some(idx) = self.selected_idx_mut()
+            && !visible.contains(idx)
+            && let Some(&first) = visible.first()
+        {
+            *idx = first;
+        }
+        self.update_source_details();
+    }
+
+    /// Read where the highlighted source currently plays, for the detail pane
+    pub(super) fn update_source_details(&mut self) {
+        if self.state != AppState::SelectSource {
+            return;
+        }
+        let highlighted = self
+            .sources
+            .get(self.selected_source_idx)
+            .map(|s| s.node_id);
+        self.highlighted_connections = match (highlighted, pipewire::get_pw_objects()) {
+            (Some(node_id), Ok(objects)) => pipewire::find_source_connections(node_id, &objects),
+            _ => Vec::new(),
+        };
+    }
+}
@@ -0,0 +1,89 @@
+//! Fitting node names into list rows.
+//!
+//! Some media names (e.g. browser tab titles) are far longer than a row;
+//! left alone they wrap into the next entry and break the list's border.
+
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// Marks text that was cut short
+const ELLIPSIS: char = '…';
+
+/// Shorten `text` to at most `max_width` terminal columns, ending it with an
+/// ellipsis if anything was cut
+///
+/// Cuts fall on character boundaries and count wide characters (CJK, emoji)
+/// as the two columns they take up.
+pub(super) fn truncate_to_width(text: &str, max_width: usize) -> String {
+    if text.width() <= max_width {
+        return text.to_string();
+    }
+    if max_width == 0 {
+        return String::new();
+    }
+
+    // Leave a column for the ellipsis
+    let mut truncated = String::new();
+    let mut width = 0;
+    for c in text.chars() {
+        let char_width = c.width().unwrap_or(0);
+        if width + char_width > max_width - 1 {
+            break;
+        }
+        truncated.push(c);
+        width += char_width;
+    }
+    truncated.push(ELLIPSIS);
+    truncated
+}
+
+/// Columns left for a name in a bordered list row of `area_width`, after
+/// the fixed text around it (prefix, marks and suffixes)
+pub(super) fn name_width(area_width: u16, fixed: &str) -> usize {
+    // One column of border on either side
+    usize::from(area_width.saturating_sub(2)).saturating_sub(fixed.width())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_text_is_kept() {
+        assert_eq!(truncate_to_width("Firefox [Audio]", 15), "Firefox [Audio]");
+        assert_eq!(truncate_to_width("", 0), "");
+    }
+
+    #[test]
+    fn long_text_ends_in_an_ellipsis() {
+        let truncated = truncate_to_width("Firefox [YouTube - Some very long title]", 12);
+        assert_eq!(truncated, "Firefox [Yo…");
+        assert_eq!(truncated.width(), 12);
+    }
+
+    #[test]
+    fn no_room_leaves_nothing_or_the_ellipsis() {
+        assert_eq!(truncate_to_width("Firefox", 0), "");
+        assert_eq!(truncate_to_width("Firefox", 1), "…");
+    }
+
+    #[test]
+    fn multibyte_text_is_cut_on_char_boundaries() {
+        assert_eq!(truncate_to_width("Café Müller", 5), "Café…");
+        assert_eq!(truncate_to_width("Ж".repeat(10).as_str(), 4), "ЖЖЖ…");
+    }
+
+    #[test]
+    fn wide_characters_count_two_columns() {
+        // Each of these takes two columns; half of one can't be shown
+        assert_eq!(truncate_to_width("音楽プレイヤー", 6), "音楽…");
+        assert_eq!(truncate_to_width("🎵🎵🎵 Music", 6), "🎵🎵…");
+        assert!(truncate_to_width("音楽プレイヤー", 6).width() <= 6);
+    }
+
+    #[test]
+    fn name_width_leaves_room_for_borders_and_prefix() {
+        assert_eq!(name_width(40, "> "), 36);
+        assert_eq!(name_width(3, "> "), 0);
+        assert_eq!(name_width(0, ""), 0);
+    }
+}
@@ -1,10 +1,13 @@
+use crate::splitter::SetupStep;
+use crate::tui::active_view::draw_active;
 use crate::tui::app::{App, AppState};
+use crate::tui::truncate::{name_width, truncate_to_width};
 use ratatui::{
     Frame,
     layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, List, ListItem, Paragraph},
+    widgets::{Block, Borders, List, ListItem, Paragraph, Wrap},
 };
 
 pub fn draw(frame: &mut Frame, app: &App) {
@@ -29,7 +32,11 @@ fn draw_title(frame: &mut Frame, area: Rect, app: &App) {
         AppState::SelectSource => "Select Audio Source",
         AppState::SelectDestination => "Select Recording Destination",
         AppState::Confirm => "Confirm Split Configuration",
+        AppState::SelectSink => "Select Local Output",
+        AppState::Creating(_) => "Creating Split",
         AppState::Active => "Split Active",
+        AppState::ConfirmStop => "Stop Split?",
+        AppState::ConfirmQuit => "Quit?",
         AppState::Error(_) => "Error",
         AppState::Done => "Done",
     };
@@ -50,18 +57,39 @@ fn draw_main_content(frame: &mut Frame, area: Rect, app: &App) {
         AppState::SelectSource => draw_source_list(frame, area, app),
         AppState::SelectDestination => draw_destination_list(frame, area, app),
         AppState::Confirm => draw_confirm(frame, area, app),
-        AppState::Active => draw_active(frame, area, app),
+        AppState::SelectSink => draw_sink_list(frame, area, app),
+        AppState::Creating(step) => draw_creating(frame, area, app, *step),
+        AppState::Active | AppState::ConfirmStop | AppState::ConfirmQuit => {
+            draw_active(frame, area, app)
+        }
         AppState::Error(msg) => draw_error(frame, area, msg),
         AppState::Done => draw_done(frame, area),
     }
 }
 
 fn draw_source_list(frame: &mut Frame, area: Rect, app: &App) {
-    let items: Vec<ListItem> = app
+    // Beside the list, show where the highlighted source plays now
+    let highlighted = app
         .sources
-        .iter()
-        .enumerate()
-        .map(|(i, source)| {
+        .get(app.selected_source_idx)
+        .filter(|_| app.visible_indices().contains(&app.selected_source_idx));
+    let area = match highlighted {
+        Some(_) => {
+            let chunks = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+                .split(area);
+            draw_source_details(frame, chunks[1], app);
+            chunks[0]
+        }
+        None => area,
+    };
+
+    let items: Vec<ListItem> = app
+        .visible_indices()
+        .into_iter()
+        .map(|i| {
+            let source = &app.sources[i];
             let style = if i == app.selected_source_idx {
                 Style::default()
                     .fg(Color::Yellow)
@@ -76,25 +104,66 @@ fn draw_source_list(frame: &mut Frame, area: Rect, app: &App) {
                 "  "
             };
 
-            ListItem::new(format!("{}{}", prefix, source.display_name())).style(style)
+            // Shows mono sources up front, as they fan out to both channels,
+            // and sources without ports, which can't be split until they play
+            let layout = source
+                .channel_label()
+                .map(|label| format!(" [{}]", label))
+                .unwrap_or_else(|| " [not playing yet]".to_string());
+            let name = truncate_to_width(
+                &source.display_name(),
+                name_width(area.width, &format!("{}{}", prefix, layout)),
+            );
+            ListItem::new(format!("{}{}{}", prefix, name, layout)).style(style)
         })
         .collect();
 
-    let list = List::new(items).block(
+    let list = List::new(items).block(Block::default().borders(Borders::ALL).title(list_title(
+        "Audio Sources (applications producing audio)",
+        app,
+    )));
+
+    frame.render_widget(list, area);
+}
+
+/// The highlighted source's current targets, which the split would disconnect
+fn draw_source_details(frame: &mut Frame, area: Rect, app: &App) {
+    let lines: Vec<Line> = if app.highlighted_connections.is_empty() {
+        vec![Line::from(Span::styled(
+            "Not connected to anything",
+            Style::default().fg(Color::DarkGray),
+        ))]
+    } else {
+        app.highlighted_connections
+            .iter()
+            .map(|conn| {
+                let count = conn.links.len();
+                Line::from(vec![
+                    Span::styled("-> ", Style::default().fg(Color::Cyan)),
+                    Span::raw(conn.target_node_name.clone()),
+                    Span::styled(
+                        format!(" ({} link{})", count, if count == 1 { "" } else { "s" }),
+                        Style::default().fg(Color::DarkGray),
+                    ),
+                ])
+            })
+            .collect()
+    };
+
+    let paragraph = Paragraph::new(lines).wrap(Wrap { trim: false }).block(
         Block::default()
             .borders(Borders::ALL)
-            .title(" Audio Sources (applications producing audio) "),
+            .title(" Currently playing to "),
     );
-
-    frame.render_widget(list, area);
+    frame.render_widget(paragraph, area);
 }
 
 fn draw_destination_list(frame: &mut Frame, area: Rect, app: &App) {
     let items: Vec<ListItem> = app
-        .destinations
-        .iter()
-        .enumerate()
-        .map(|(i, dest)| {
+        .visible_indices()
+        .into_iter()
+        .map(|i| {
+            let dest = &app.destinations[i];
             let style = if i == app.selected_dest_idx {
                 Style::default()
                     .fg(Color::Yellow)
@@ -108,20 +177,80 @@ fn draw_destination_list(frame: &mut Frame, area: Rect, app: &App) {
             } else {
                 "  "
             };
+            let mark = if app.marked_dests.contains(&i) {
+                "[x] "
+            } else {
+                "[ ] "
+            };
+
+            let name = truncate_to_width(
+                &dest.display_name(),
+                name_width(area.width, &format!("{}{}", prefix, mark)),
+            );
+            ListItem::new(format!("{}{}{}", prefix, mark, name)).style(style)
+        })
+        .collect();
+
+    let list = List::new(items).block(Block::default().borders(Borders::ALL).title(list_title(
+        "Recording Destinations (capture streams and virtual sinks)",
+        app,
+    )));
+
+    frame.render_widget(list, area);
+}
+
+fn draw_sink_list(frame: &mut Frame, area: Rect, app: &App) {
+    let items: Vec<ListItem> = app
+        .visible_indices()
+        .into_iter()
+        .map(|i| {
+            let sink = &app.sinks[i];
+            let style = if i == app.selected_sink_idx {
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD)
+            } else {
+                Style::default()
+            };
+
+            let prefix = if i == app.selected_sink_idx {
+                "> "
+            } else {
+                "  "
+            };
+            let mark = if app.marked_sinks.contains(&i) {
+                "[x] "
+            } else {
+                "[ ] "
+            };
 
-            ListItem::new(format!("{}{}", prefix, dest.display_name())).style(style)
+            let name = truncate_to_width(
+                &sink.description,
+                name_width(area.width, &format!("{}{}", prefix, mark)),
+            );
+            ListItem::new(format!("{}{}{}", prefix, mark, name)).style(style)
         })
         .collect();
 
     let list = List::new(items).block(
         Block::default()
             .borders(Borders::ALL)
-            .title(" Recording Destinations (applications capturing audio) "),
+            .title(list_title("Output Sinks (where you hear the source)", app)),
     );
 
     frame.render_widget(list, area);
 }
 
+/// List title, with the filter query appended while one is active
+fn list_title(title: &str, app: &App) -> String {
+    if !app.filter.is_active() {
+        return format!(" {} ", title);
+    }
+
+    let cursor = if app.filter.editing { "_" } else { "" };
+    format!(" {} [/{}{}] ", title, app.filter.query, cursor)
+}
+
 fn draw_confirm(frame: &mut Frame, area: Rect, app: &App) {
     let source_name = app
         .selected_source
@@ -129,11 +258,15 @@ fn draw_confirm(frame: &mut Frame, area: Rect, app: &App) {
         .map(|s| s.display_name())
         .unwrap_or_else(|| "None".to_string());
 
-    let dest_name = app
-        .selected_dest
-        .as_ref()
-        .map(|d| d.display_name())
-        .unwrap_or_else(|| "None".to_string());
+    let dest_name = if app.selected_dests.is_empty() {
+        "None".to_string()
+    } else {
+        app.selected_dests
+            .iter()
+            .map(|d| d.display_name())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
 
     let original_output = if app.source_connections.is_empty() {
         "No active connection (will use default output)".to_string()
@@ -145,7 +278,17 @@ fn draw_confirm(frame: &mut Frame, area: Rect, app: &App) {
             .join(", ")
     };
 
-    let lines = vec![
+    let local_output = if app.local_sinks.is_empty() {
+        "Automatic (current output)".to_string()
+    } else {
+        app.local_sinks
+            .iter()
+            .map(|s| s.description.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    let mut lines = vec![
         Line::from(""),
         Line::from(vec![
             Span::raw("  Source: "),
@@ -153,7 +296,7 @@ fn draw_confirm(frame: &mut Frame, area: Rect, app: &App) {
         ]),
         Line::from(""),
         Line::from(vec![
-            Span::raw("  Recording Destination: "),
+            Span::raw("  Recording Destinations: "),
             Span::styled(&dest_name, Style::default().fg(Color::Green)),
         ]),
         Line::from(""),
@@ -162,6 +305,11 @@ fn draw_confirm(frame: &mut Frame, area: Rect, app: &App) {
             Span::styled(&original_output, Style::default().fg(Color::Blue)),
         ]),
         Line::from(""),
+        Line::from(vec![
+            Span::raw("  Local Output: "),
+            Span::styled(&local_output, Style::default().fg(Color::Green)),
+        ]),
+        Line::from(""),
         Line::from(""),
         Line::from("  Routing after split:"),
         Line::from(""),
@@ -170,7 +318,27 @@ fn draw_confirm(frame: &mut Frame, area: Rect, app: &App) {
         Line::from("        +---> [To Recording] ---> [OBS - full volume]"),
         Line::from("        |"),
         Line::from("        '---> [To Local] ---> [Speakers - adjustable]"),
+        Line::from(""),
+        Line::from("  Links cut:"),
     ];
+    let diff = &app.link_diff;
+    if diff.cut.is_empty() {
+        lines.push(Line::from("    (none)"));
+    }
+    for link in &diff.cut {
+        lines.push(Line::styled(
+            format!("    - {}", link),
+            Style::default().fg(Color::Red),
+        ));
+    }
+    lines.push(Line::from(""));
+    lines.push(Line::from("  Links made:"));
+    for link in &diff.made {
+        lines.push(Line::styled(
+            format!("    + {}", link),
+            Style::default().fg(Color::Green),
+        ));
+    }
 
     let paragraph = Paragraph::new(lines).block(
         Block::default()
@@ -181,57 +349,45 @@ fn draw_confirm(frame: &mut Frame, area: Rect, app: &App) {
     frame.render_widget(paragraph, area);
 }
 
-fn draw_active(frame: &mut Frame, area: Rect, app: &App) {
-    let state = match &app.active_split {
-        Some(s) => s,
-        None => {
-            let paragraph = Paragraph::new("No active split").block(
-                Block::default()
-                    .borders(Borders::ALL)
-                    .title(" Active Split "),
-            );
-            frame.render_widget(paragraph, area);
-            return;
-        }
-    };
+fn draw_creating(frame: &mut Frame, area: Rect, app: &App, current: SetupStep) {
+    const SPINNER: [&str; 4] = ["|", "/", "-", "\\"];
 
-    let lines = vec![
-        Line::from(""),
-        Line::from(vec![Span::styled(
-            "  SPLIT ACTIVE",
-            Style::default()
-                .fg(Color::Green)
-                .add_modifier(Modifier::BOLD),
-        )]),
-        Line::from(""),
-        Line::from(format!("  Source: {}", state.source_application_name)),
-        Line::from(format!(
-            "  Recording to: {}",
-            state.recording_dest_application_name
-        )),
-        Line::from(format!(
-            "  Local output: {}",
-            state.original_output_node_name
-        )),
-        Line::from(""),
-        Line::from("  Routing:"),
-        Line::from(format!("    [{}]", state.source_application_name)),
-        Line::from("        |"),
-        Line::from("        +---> [To Recording] ---> [OBS] (FULL VOLUME)"),
-        Line::from("        |"),
-        Line::from("        '---> [To Local] ---> [Speakers] (ADJUSTABLE)"),
-        Line::from(""),
-        Line::from(vec![Span::styled(
-            "  Adjust local volume in pwvucontrol",
-            Style::default().fg(Color::Yellow),
-        )]),
-        Line::from(format!("  Look for: \"{}\"", state.local_loopback_name)),
-    ];
+    let current_idx = SetupStep::ALL
+        .iter()
+        .position(|s| *s == current)
+        .unwrap_or_default();
+
+    let mut lines = vec![Line::from("")];
+    for (i, step) in SetupStep::ALL.iter().enumerate() {
+        let line = if i < current_idx {
+            Line::from(vec![
+                Span::styled("  [done] ", Style::default().fg(Color::Green)),
+                Span::raw(step.description()),
+            ])
+        } else if i == current_idx {
+            Line::from(vec![
+                Span::styled(
+                    format!("  [ {}  ] ", SPINNER[app.spinner_frame % SPINNER.len()]),
+                    Style::default().fg(Color::Yellow),
+                ),
+                Span::styled(
+                    step.description(),
+                    Style::default().add_modifier(Modifier::BOLD),
+                ),
+            ])
+        } else {
+            Line::from(vec![
+                Span::styled("  [    ] ", Style::default().fg(Color::DarkGray)),
+                Span::styled(step.description(), Style::default().fg(Color::DarkGray)),
+            ])
+        };
+        lines.push(line);
+    }
 
     let paragraph = Paragraph::new(lines).block(
         Block::default()
             .borders(Borders::ALL)
-            .title(" Active Split "),
+            .title(" Creating Split "),
     );
 
     frame.render_widget(paragraph, area);
@@ -264,7 +420,7 @@ fn draw_done(frame: &mut Frame, area: Rect) {
         Line::from(""),
         Line::from("  Original connections have been restored."),
         Line::from(""),
-        Line::from("  Press 'q' to quit or 'r' to create a new split."),
+        Line::from("  Press 'u' to undo, 'r' to create a new split or 'q' to quit."),
     ];
 
     let paragraph =
@@ -291,13 +447,32 @@ fn draw_status(frame: &mut Frame, area: Rect, app: &App) {
 
 fn draw_help(frame: &mut Frame, area: Rect, app: &App) {
     let help_text = match &app.state {
-        AppState::SelectSource | AppState::SelectDestination => {
-            "↑/↓: Navigate | Enter: Select | r: Refresh | q: Quit"
+        AppState::SelectSource | AppState::SelectDestination | AppState::SelectSink
+            if app.filter.editing =>
+        {
+            "Type to filter | Enter: Done | Esc: Clear filter"
+        }
+        AppState::SelectSource => {
+            "↑/↓: Navigate | /: Filter | Enter: Select | g: Split to Default | m: Monitors | r: Refresh | q: Quit"
+        }
+        AppState::SelectDestination => {
+            "↑/↓: Navigate | /: Filter | Space: Mark | Enter: Select | r: Refresh | q: Quit"
+        }
+        AppState::Confirm => "Enter: Confirm | o: Local Output | Esc: Back | q: Quit",
+        AppState::SelectSink => {
+            "↑/↓: Navigate | /: Filter | Space: Mark | Enter: Select | Esc: Back | q: Quit"
+        }
+        AppState::Creating(_) => "Please wait...",
+        AppState::Active if !app.pending_restarts.is_empty() => {
+            "r: Restart Crashed | +/-: Volume | m: Mute | Enter: Stop Split | q: Quit (keeps split running)"
+        }
+        AppState::Active => {
+            "+/-: Volume | </>: Rec Volume | m: Mute | [/]: Balance | v: Mixer | Enter: Stop Split | q: Quit (keeps split running)"
         }
-        AppState::Confirm => "Enter: Confirm | Esc: Back | q: Quit",
-        AppState::Active => "Enter: Stop Split | q: Quit (keeps split running)",
-        AppState::Error(_) => "Esc: Back | q: Quit",
-        AppState::Done => "r: New Split | q: Quit",
+        AppState::ConfirmStop => "y/Enter: Stop Split | n/Esc: Keep Running",
+        AppState::ConfirmQuit => "Enter/k: Keep Running & Quit | s: Stop & Quit | Esc: Cancel",
+        AppState::Error(_) => "r: Retry | q: Quit",
+        AppState::Done => "u: Undo Stop | r: New Split | q: Quit",
     };
 
     let paragraph = Paragraph::new(help_text).style(Style::default().fg(Color::DarkGray));